@@ -27,6 +27,7 @@ use ethereum_types::{Address, Bloom, H256, U256};
 use rlp::{encode_list, Decodable, DecoderError, Encodable, Rlp, RlpStream};
 use unexpected::{Mismatch, OutOfBounds};
 
+use confidential::SoftwareConfidentialCtx;
 use engines::EthEngine;
 use error::{BlockError, Error};
 use factory::Factories;
@@ -314,6 +315,12 @@ impl<'x> OpenBlock<'x> {
 		confidential_ctx: Option<Box<ConfidentialCtx>>,
 	) -> Result<Self, Error> {
 		let number = parent.number() + 1;
+		// development chains without an enclave use the software context
+		let confidential_ctx = confidential_ctx.or_else(|| {
+			engine.params().dev_confidential_secret.map(|secret| {
+				Box::new(SoftwareConfidentialCtx::new(secret.0)) as Box<ConfidentialCtx>
+			})
+		});
 		let state = State::from_existing(
 			mkvs,
 			db,
@@ -0,0 +1,697 @@
+// Copyright 2015-2018 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Software confidential context, for tests and development chains.
+//!
+//! Sessions use X25519 key agreement between the client and the contract, and
+//! everything is sealed with ChaCha20-Poly1305. Contract keys are derived from a
+//! secret held by the node, so the node operator can read all confidential state:
+//! this context stands in for an enclave, it does not replace one.
+
+use chacha20poly1305::aead::{generic_array::GenericArray, Aead, NewAead, Payload};
+use chacha20poly1305::ChaCha20Poly1305;
+use ethereum_types::{Address, H256};
+use hash::keccak;
+use vm::{self, AuthenticatedPayload, ConfidentialCtx};
+use x25519_dalek::{PublicKey, StaticSecret};
+
+/// Size of the nonce prefixing sealed data.
+pub const NONCE_SIZE: usize = 12;
+/// Size of an X25519 public key.
+pub const PUBLIC_KEY_SIZE: usize = 32;
+/// Size of the authentication tag appended to ciphertexts.
+pub const TAG_SIZE: usize = 16;
+
+/// Domain separators of the derived keys.
+const CONTRACT_SECRET: &[u8] = b"oasis-contract-secret";
+const STORAGE_KEY: &[u8] = b"oasis-storage-key";
+const SESSION_REQUEST: &[u8] = b"oasis-session-request";
+const SESSION_RESPONSE: &[u8] = b"oasis-session-response";
+const SESSION_LOG: &[u8] = b"oasis-session-log";
+const CONTRACT_LOG: &[u8] = b"oasis-contract-log";
+const LOG_TOPIC: &[u8] = b"oasis-log-topic";
+const NONCE: &[u8] = b"oasis-nonce";
+
+fn error(reason: &str) -> vm::Error {
+	vm::Error::Confidential(reason.to_owned())
+}
+
+/// Hashes the `material` under `domain`, prefixing every part with its length so
+/// that splitting the same bytes differently derives a different key.
+fn derive_key(domain: &[u8], material: &[&[u8]]) -> [u8; 32] {
+	let mut input = domain.to_vec();
+	for part in material {
+		input.extend_from_slice(&(part.len() as u64).to_be_bytes());
+		input.extend_from_slice(part);
+	}
+	keccak(&input).0
+}
+
+/// Seals `data` with `key`, returning `nonce || cipher`.
+fn seal(key: &[u8; 32], nonce: &[u8; NONCE_SIZE], aad: &[u8], data: &[u8]) -> vm::Result<Vec<u8>> {
+	let cipher = ChaCha20Poly1305::new(GenericArray::from_slice(key))
+		.encrypt(GenericArray::from_slice(nonce), Payload { msg: data, aad })
+		.map_err(|_| error("Cannot seal data"))?;
+	let mut sealed = nonce.to_vec();
+	sealed.extend_from_slice(&cipher);
+	Ok(sealed)
+}
+
+/// Opens `cipher`, sealed with `key` under `nonce`.
+fn open(key: &[u8; 32], nonce: &[u8], aad: &[u8], cipher: &[u8]) -> vm::Result<Vec<u8>> {
+	ChaCha20Poly1305::new(GenericArray::from_slice(key))
+		.decrypt(
+			GenericArray::from_slice(nonce),
+			Payload { msg: cipher, aad },
+		)
+		.map_err(|_| error("Cannot authenticate sealed data"))
+}
+
+/// Returns a nonce unique to the `material`, so that sealing is deterministic.
+fn synthetic_nonce(material: &[&[u8]]) -> [u8; NONCE_SIZE] {
+	let mut nonce = [0u8; NONCE_SIZE];
+	nonce.copy_from_slice(&derive_key(NONCE, material)[..NONCE_SIZE]);
+	nonce
+}
+
+/// Keys of a session between a client and a contract, derived from their key agreement.
+struct SessionKeys {
+	request: [u8; 32],
+	response: [u8; 32],
+	log: [u8; 32],
+}
+
+impl SessionKeys {
+	fn new(
+		secret: &StaticSecret,
+		peer: &[u8; PUBLIC_KEY_SIZE],
+		client: &[u8],
+		contract: &[u8],
+	) -> Self {
+		let shared = secret.diffie_hellman(&PublicKey::from(*peer));
+		SessionKeys {
+			request: derive_key(SESSION_REQUEST, &[shared.as_bytes(), client, contract]),
+			response: derive_key(SESSION_RESPONSE, &[shared.as_bytes(), client, contract]),
+			log: derive_key(SESSION_LOG, &[shared.as_bytes(), client, contract]),
+		}
+	}
+}
+
+/// Splits a session payload into its nonce, peer public key and cipher.
+fn split_session(payload: &[u8]) -> vm::Result<(&[u8], [u8; PUBLIC_KEY_SIZE], &[u8])> {
+	if payload.len() < NONCE_SIZE + PUBLIC_KEY_SIZE + TAG_SIZE {
+		return Err(error("Session payload is too short"));
+	}
+	let (nonce, rest) = payload.split_at(NONCE_SIZE);
+	let (peer, cipher) = rest.split_at(PUBLIC_KEY_SIZE);
+	let mut peer_key = [0u8; PUBLIC_KEY_SIZE];
+	peer_key.copy_from_slice(peer);
+	Ok((nonce, peer_key, cipher))
+}
+
+/// Seals a session payload as `NONCE || SENDER_PUBLIC_KEY || CIPHER`,
+/// authenticating the nonce and public key along with the data.
+fn seal_session(
+	key: &[u8; 32],
+	nonce: &[u8; NONCE_SIZE],
+	sender: &[u8; PUBLIC_KEY_SIZE],
+	data: &[u8],
+) -> vm::Result<Vec<u8>> {
+	let header = [&nonce[..], &sender[..]].concat();
+	let sealed = seal(key, nonce, &header, data)?;
+	Ok([&header[..], &sealed[NONCE_SIZE..]].concat())
+}
+
+fn open_session(key: &[u8; 32], payload: &[u8]) -> vm::Result<Vec<u8>> {
+	let header = &payload[..NONCE_SIZE + PUBLIC_KEY_SIZE];
+	let cipher = &payload[NONCE_SIZE + PUBLIC_KEY_SIZE..];
+	open(key, &payload[..NONCE_SIZE], header, cipher)
+}
+
+/// Returns the topic under which logs with `topic` are published by logs sealed with `key`.
+fn blind_topic(key: &[u8; 32], topic: &H256) -> H256 {
+	H256(derive_key(LOG_TOPIC, &[key, topic]))
+}
+
+/// Opens log `data` published with the blinded `topics`.
+fn open_log(key: &[u8; 32], topics: &[H256], data: &[u8]) -> vm::Result<Vec<u8>> {
+	if data.len() < NONCE_SIZE + TAG_SIZE {
+		return Err(error("Sealed log is too short"));
+	}
+	let aad = topics
+		.iter()
+		.flat_map(|topic| topic.to_vec())
+		.collect::<Vec<_>>();
+	open(key, &data[..NONCE_SIZE], &aad, &data[NONCE_SIZE..])
+}
+
+/// Client side of a session with a contract served by `SoftwareConfidentialCtx`.
+pub struct SessionClient {
+	secret: StaticSecret,
+	public_key: [u8; PUBLIC_KEY_SIZE],
+}
+
+impl SessionClient {
+	/// Creates a client with the given X25519 secret key.
+	pub fn new(secret: [u8; 32]) -> Self {
+		let secret = StaticSecret::from(secret);
+		let public_key = *PublicKey::from(&secret).as_bytes();
+		SessionClient { secret, public_key }
+	}
+
+	/// Returns the public key sent along with requests.
+	pub fn public_key(&self) -> [u8; PUBLIC_KEY_SIZE] {
+		self.public_key
+	}
+
+	/// Encrypts `data` for the contract with public key `contract`. Each request to
+	/// a contract must use a different `nonce`.
+	pub fn encrypt(
+		&self,
+		contract: &[u8; PUBLIC_KEY_SIZE],
+		nonce: &[u8; NONCE_SIZE],
+		data: &[u8],
+	) -> vm::Result<Vec<u8>> {
+		let keys = SessionKeys::new(&self.secret, contract, &self.public_key, contract);
+		seal_session(&keys.request, nonce, &self.public_key, data)
+	}
+
+	/// Decrypts a response of the contract with public key `contract`.
+	pub fn decrypt(&self, contract: &[u8; PUBLIC_KEY_SIZE], payload: &[u8]) -> vm::Result<Vec<u8>> {
+		let (_, sender, _) = split_session(payload)?;
+		if sender != *contract {
+			return Err(error("Response is not from the expected contract"));
+		}
+		let keys = SessionKeys::new(&self.secret, contract, &self.public_key, contract);
+		open_session(&keys.response, payload)
+	}
+
+	/// Returns the topic under which `topic` is published by the logs of a session
+	/// with the contract with public key `contract`.
+	pub fn log_topic(&self, contract: &[u8; PUBLIC_KEY_SIZE], topic: &H256) -> H256 {
+		let keys = SessionKeys::new(&self.secret, contract, &self.public_key, contract);
+		blind_topic(&keys.log, topic)
+	}
+
+	/// Decrypts the data of a log of a session with the contract with public key
+	/// `contract`, or of any contract it called.
+	pub fn decrypt_log(
+		&self,
+		contract: &[u8; PUBLIC_KEY_SIZE],
+		topics: &[H256],
+		data: &[u8],
+	) -> vm::Result<Vec<u8>> {
+		let keys = SessionKeys::new(&self.secret, contract, &self.public_key, contract);
+		open_log(&keys.log, topics, data)
+	}
+}
+
+/// Session opened by `decrypt_session`.
+struct Session {
+	/// Public key of the client.
+	peer: [u8; PUBLIC_KEY_SIZE],
+	/// Public key of the contract the client called.
+	contract: [u8; PUBLIC_KEY_SIZE],
+	/// Key sealing responses to the client.
+	response_key: [u8; 32],
+	/// Key sealing logs emitted during the session.
+	log_key: [u8; 32],
+	/// Nonce of the request which opened the session.
+	request_nonce: [u8; NONCE_SIZE],
+	/// Number of responses sealed so far.
+	responses: u64,
+}
+
+/// `ConfidentialCtx` deriving the keys of every contract from a node secret.
+///
+/// Storage is sealed deterministically, with nonces derived from the sealed data,
+/// so that every node computes the same state. Responses to a session are sealed
+/// under a key distinct from the request key, with nonces derived from the request
+/// nonce, the number of responses so far and the response itself: a request replayed
+/// in another transaction yields different responses under different nonces, and
+/// the same response under the same nonce. Logs are sealed deterministically too,
+/// with the number of logs emitted so far mixed into their nonce so that repeated
+/// logs look different.
+pub struct SoftwareConfidentialCtx {
+	/// Secret from which contract keys are derived.
+	node_secret: [u8; 32],
+	activated: bool,
+	/// Contract whose keys are in use.
+	contract: Option<Address>,
+	session: Option<Session>,
+	/// Sessions left open by calls from public contracts, see `enter_session`.
+	outer_sessions: Vec<Option<Session>>,
+	/// Number of logs sealed in the transaction, including while the context was
+	/// suspended. Logs of calls that are later reverted are discarded but still
+	/// counted: every node executes the same reverts, so the count stays
+	/// deterministic, and no nonce is sealed twice.
+	logs: u64,
+}
+
+impl SoftwareConfidentialCtx {
+	/// Creates a context deriving contract keys from `node_secret`.
+	pub fn new(node_secret: [u8; 32]) -> Self {
+		SoftwareConfidentialCtx {
+			node_secret,
+			activated: false,
+			contract: None,
+			session: None,
+			outer_sessions: Vec::new(),
+			logs: 0,
+		}
+	}
+
+	/// Returns the X25519 public key clients encrypt requests to `contract` with.
+	pub fn public_key(&self, contract: &Address) -> [u8; PUBLIC_KEY_SIZE] {
+		*PublicKey::from(&self.contract_secret(contract)).as_bytes()
+	}
+
+	fn contract_secret(&self, contract: &Address) -> StaticSecret {
+		StaticSecret::from(derive_key(CONTRACT_SECRET, &[&self.node_secret, contract]))
+	}
+
+	/// Returns the key sealing the storage of the active contract.
+	fn storage_key(&self) -> vm::Result<[u8; 32]> {
+		let contract = self
+			.contract
+			.as_ref()
+			.ok_or_else(|| error("No contract is active in the confidential context"))?;
+		Ok(derive_key(STORAGE_KEY, &[&self.node_secret, contract]))
+	}
+
+	/// Returns the key sealing logs: that of the session if there is one, so that
+	/// the client can read the logs of every contract it reaches, and otherwise
+	/// that of the active contract.
+	fn log_key(&self) -> vm::Result<[u8; 32]> {
+		if let Some(session) = &self.session {
+			return Ok(session.log_key);
+		}
+		let contract = self
+			.contract
+			.as_ref()
+			.ok_or_else(|| error("No contract is active in the confidential context"))?;
+		Ok(derive_key(CONTRACT_LOG, &[&self.node_secret, contract]))
+	}
+
+	/// Returns the topic under which `topic` is published by the logs `contract`
+	/// emits outside of a session.
+	pub fn contract_log_topic(&self, contract: &Address, topic: &H256) -> H256 {
+		blind_topic(
+			&derive_key(CONTRACT_LOG, &[&self.node_secret, contract]),
+			topic,
+		)
+	}
+
+	/// Decrypts the data of a log `contract` emitted outside of a session.
+	pub fn decrypt_contract_log(
+		&self,
+		contract: &Address,
+		topics: &[H256],
+		data: &[u8],
+	) -> vm::Result<Vec<u8>> {
+		open_log(
+			&derive_key(CONTRACT_LOG, &[&self.node_secret, contract]),
+			topics,
+			data,
+		)
+	}
+}
+
+impl ConfidentialCtx for SoftwareConfidentialCtx {
+	fn activate(&mut self, contract: Option<Address>) -> vm::Result<Option<Address>> {
+		self.activated = true;
+		Ok(std::mem::replace(&mut self.contract, contract))
+	}
+
+	fn deactivate(&mut self) {
+		self.suspend();
+		self.logs = 0;
+	}
+
+	fn suspend(&mut self) {
+		self.activated = false;
+		self.contract = None;
+		self.session = None;
+		self.outer_sessions.clear();
+	}
+
+	fn activated(&self) -> bool {
+		self.activated
+	}
+
+	fn is_encrypting(&self) -> bool {
+		self.activated && self.contract.is_some()
+	}
+
+	fn encrypt_session(&mut self, data: Vec<u8>) -> vm::Result<Vec<u8>> {
+		if !self.activated {
+			return Err(error("Confidential context is not active"));
+		}
+		let session = self
+			.session
+			.as_mut()
+			.ok_or_else(|| error("No session to encrypt for"))?;
+		let nonce = synthetic_nonce(&[
+			&session.response_key,
+			&session.request_nonce,
+			&session.responses.to_be_bytes(),
+			&data,
+		]);
+		session.responses += 1;
+		seal_session(&session.response_key, &nonce, &session.contract, &data)
+	}
+
+	fn decrypt_session(&mut self, encrypted_payload: Vec<u8>) -> vm::Result<AuthenticatedPayload> {
+		let contract = self
+			.contract
+			.ok_or_else(|| error("No contract is active in the confidential context"))?;
+		let (nonce, peer, _) = split_session(&encrypted_payload)?;
+
+		let secret = self.contract_secret(&contract);
+		let contract_key = *PublicKey::from(&secret).as_bytes();
+		let keys = SessionKeys::new(&secret, &peer, &peer, &contract_key);
+		let decrypted_data = open_session(&keys.request, &encrypted_payload)?;
+
+		let mut request_nonce = [0u8; NONCE_SIZE];
+		request_nonce.copy_from_slice(nonce);
+		self.session = Some(Session {
+			peer,
+			contract: contract_key,
+			response_key: keys.response,
+			log_key: keys.log,
+			request_nonce,
+			responses: 0,
+		});
+
+		Ok(AuthenticatedPayload {
+			decrypted_data,
+			// the client is identified by its session key
+			additional_data: peer.to_vec(),
+		})
+	}
+
+	fn enter_session(&mut self, encrypted_payload: Vec<u8>) -> vm::Result<AuthenticatedPayload> {
+		let outer_session = self.session.take();
+		match self.decrypt_session(encrypted_payload) {
+			Ok(payload) => {
+				self.outer_sessions.push(outer_session);
+				Ok(payload)
+			}
+			Err(err) => {
+				self.session = outer_session;
+				Err(err)
+			}
+		}
+	}
+
+	fn leave_session(&mut self) {
+		if let Some(outer_session) = self.outer_sessions.pop() {
+			self.session = outer_session;
+		}
+	}
+
+	fn peer(&self) -> Option<Vec<u8>> {
+		self.session.as_ref().map(|session| session.peer.to_vec())
+	}
+
+	fn encrypt_storage_value(
+		&mut self,
+		storage_key: Vec<u8>,
+		data: Vec<u8>,
+	) -> vm::Result<Vec<u8>> {
+		let key = self.storage_key()?;
+		let nonce = synthetic_nonce(&[&key, &storage_key, &data]);
+		// binding the value to its key keeps values from being moved across keys
+		seal(&key, &nonce, &storage_key, &data)
+	}
+
+	fn encrypt_storage_key(&self, data: Vec<u8>) -> vm::Result<Vec<u8>> {
+		let key = self.storage_key()?;
+		let nonce = synthetic_nonce(&[&key, &data]);
+		seal(&key, &nonce, &[], &data)
+	}
+
+	fn decrypt_storage_value(&self, storage_key: Vec<u8>, data: Vec<u8>) -> vm::Result<Vec<u8>> {
+		if data.len() < NONCE_SIZE + TAG_SIZE {
+			return Err(error("Sealed storage value is too short"));
+		}
+		let key = self.storage_key()?;
+		open(&key, &data[..NONCE_SIZE], &storage_key, &data[NONCE_SIZE..])
+	}
+
+	fn encrypt_log(
+		&mut self,
+		topics: Vec<H256>,
+		data: Vec<u8>,
+	) -> vm::Result<(Vec<H256>, Vec<u8>)> {
+		let key = self.log_key()?;
+		let topics = topics
+			.iter()
+			.map(|topic| blind_topic(&key, topic))
+			.collect::<Vec<_>>();
+		// the topics are authenticated along with the data
+		let aad = topics
+			.iter()
+			.flat_map(|topic| topic.to_vec())
+			.collect::<Vec<_>>();
+		let nonce = synthetic_nonce(&[&key, &self.logs.to_be_bytes(), &aad, &data]);
+		self.logs += 1;
+		Ok((topics, seal(&key, &nonce, &aad, &data)?))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use ethereum_types::H256;
+	use std::cell::RefCell;
+	use std::rc::Rc;
+	use test_helpers::get_temp_state;
+
+	fn ctx() -> SoftwareConfidentialCtx {
+		SoftwareConfidentialCtx::new([7; 32])
+	}
+
+	fn contract() -> Address {
+		Address::from(0x42)
+	}
+
+	#[test]
+	fn session_roundtrip() {
+		let mut ctx = ctx();
+		let contract_key = ctx.public_key(&contract());
+		let client = SessionClient::new([1; 32]);
+
+		let request = client
+			.encrypt(&contract_key, &[0; NONCE_SIZE], b"hello")
+			.unwrap();
+		assert_eq!(
+			&request[NONCE_SIZE..NONCE_SIZE + PUBLIC_KEY_SIZE],
+			&client.public_key()
+		);
+
+		ctx.activate(Some(contract())).unwrap();
+		let payload = ctx.decrypt_session(request).unwrap();
+		assert_eq!(payload.decrypted_data, b"hello".to_vec());
+		assert_eq!(payload.additional_data, client.public_key().to_vec());
+		assert_eq!(ctx.peer(), Some(client.public_key().to_vec()));
+
+		let first = ctx.encrypt_session(b"world".to_vec()).unwrap();
+		let second = ctx.encrypt_session(b"world".to_vec()).unwrap();
+		assert_ne!(first[..NONCE_SIZE], second[..NONCE_SIZE]);
+		assert_eq!(
+			client.decrypt(&contract_key, &first).unwrap(),
+			b"world".to_vec()
+		);
+		assert_eq!(
+			client.decrypt(&contract_key, &second).unwrap(),
+			b"world".to_vec()
+		);
+
+		ctx.deactivate();
+		assert_eq!(ctx.peer(), None);
+		assert!(ctx.encrypt_session(b"world".to_vec()).is_err());
+	}
+
+	#[test]
+	fn replayed_sessions_do_not_reuse_nonces() {
+		let client = SessionClient::new([1; 32]);
+		let contract_key = ctx().public_key(&contract());
+		let request = client
+			.encrypt(&contract_key, &[0; NONCE_SIZE], b"hello")
+			.unwrap();
+
+		// the same request, resubmitted in another transaction
+		let respond = |data: &[u8]| {
+			let mut ctx = ctx();
+			ctx.activate(Some(contract())).unwrap();
+			ctx.decrypt_session(request.clone()).unwrap();
+			ctx.encrypt_session(data.to_vec()).unwrap()
+		};
+		let first = respond(b"first");
+		let second = respond(b"second");
+		assert_ne!(first[..NONCE_SIZE], second[..NONCE_SIZE]);
+		assert_eq!(first, respond(b"first"));
+		assert_eq!(
+			client.decrypt(&contract_key, &second).unwrap(),
+			b"second".to_vec()
+		);
+	}
+
+	#[test]
+	fn session_rejects_tampering() {
+		let mut ctx = ctx();
+		let client = SessionClient::new([1; 32]);
+		let request = client
+			.encrypt(&ctx.public_key(&contract()), &[0; NONCE_SIZE], b"hello")
+			.unwrap();
+
+		// a contract only opens the sessions meant for it
+		ctx.activate(Some(Address::from(0x43))).unwrap();
+		assert!(ctx.decrypt_session(request.clone()).is_err());
+
+		ctx.activate(Some(contract())).unwrap();
+		let mut tampered = request.clone();
+		*tampered.last_mut().unwrap() ^= 1;
+		assert!(ctx.decrypt_session(tampered).is_err());
+		let mut tampered = request.clone();
+		tampered[0] ^= 1;
+		assert!(ctx.decrypt_session(tampered).is_err());
+		assert!(ctx.decrypt_session(request[..NONCE_SIZE].to_vec()).is_err());
+		assert!(ctx.decrypt_session(request).is_ok());
+	}
+
+	#[test]
+	fn nested_sessions_restore_the_outer_session() {
+		let mut ctx = ctx();
+		let (outer, inner) = (contract(), Address::from(0x43));
+		let (outer_key, inner_key) = (ctx.public_key(&outer), ctx.public_key(&inner));
+		let (alice, bob) = (SessionClient::new([1; 32]), SessionClient::new([2; 32]));
+
+		ctx.activate(Some(outer)).unwrap();
+		ctx.decrypt_session(alice.encrypt(&outer_key, &[0; NONCE_SIZE], b"a").unwrap())
+			.unwrap();
+
+		// a public contract relays the request of another client to `inner`
+		ctx.activate(Some(inner)).unwrap();
+		let request = bob.encrypt(&inner_key, &[0; NONCE_SIZE], b"b").unwrap();
+		let mut tampered = request.clone();
+		*tampered.last_mut().unwrap() ^= 1;
+		assert!(ctx.enter_session(tampered).is_err());
+		assert_eq!(ctx.peer(), Some(alice.public_key().to_vec()));
+		ctx.enter_session(request).unwrap();
+		assert_eq!(ctx.peer(), Some(bob.public_key().to_vec()));
+		let response = ctx.encrypt_session(b"to bob".to_vec()).unwrap();
+		assert_eq!(
+			bob.decrypt(&inner_key, &response).unwrap(),
+			b"to bob".to_vec()
+		);
+		ctx.leave_session();
+
+		ctx.activate(Some(outer)).unwrap();
+		assert_eq!(ctx.peer(), Some(alice.public_key().to_vec()));
+		let response = ctx.encrypt_session(b"to alice".to_vec()).unwrap();
+		assert_eq!(
+			alice.decrypt(&outer_key, &response).unwrap(),
+			b"to alice".to_vec()
+		);
+	}
+
+	#[test]
+	fn storage_is_sealed_per_contract() {
+		let mut ctx = ctx();
+		assert!(ctx.encrypt_storage_key(vec![1]).is_err());
+
+		ctx.activate(Some(contract())).unwrap();
+		let key = ctx.encrypt_storage_key(vec![1]).unwrap();
+		assert_eq!(key, ctx.encrypt_storage_key(vec![1]).unwrap());
+		let value = ctx.encrypt_storage_value(key.clone(), vec![2]).unwrap();
+		assert_eq!(
+			value,
+			ctx.encrypt_storage_value(key.clone(), vec![2]).unwrap()
+		);
+		assert_eq!(
+			ctx.decrypt_storage_value(key.clone(), value.clone())
+				.unwrap(),
+			vec![2]
+		);
+		assert!(ctx.decrypt_storage_value(vec![0], value.clone()).is_err());
+
+		ctx.activate(Some(Address::from(0x43))).unwrap();
+		assert_ne!(key, ctx.encrypt_storage_key(vec![1]).unwrap());
+		assert!(ctx.decrypt_storage_value(key, value).is_err());
+	}
+
+	#[test]
+	fn logs_are_sealed_deterministically() {
+		let mut ctx = ctx();
+		assert!(ctx.encrypt_log(vec![H256::from(1)], vec![2]).is_err());
+
+		// every node must publish the same logs
+		let emit = |ctx: &mut SoftwareConfidentialCtx| {
+			ctx.activate(Some(contract())).unwrap();
+			let logs = (0..2)
+				.map(|_| ctx.encrypt_log(vec![H256::from(1)], vec![2]).unwrap())
+				.collect::<Vec<_>>();
+			ctx.deactivate();
+			logs
+		};
+		let logs = emit(&mut ctx);
+		assert_eq!(logs, emit(&mut SoftwareConfidentialCtx::new([7; 32])));
+		assert_eq!(logs[0].0, logs[1].0);
+		assert_ne!(logs[0].1, logs[1].1);
+
+		// calls from public contracts suspend the context within the transaction
+		ctx.activate(Some(contract())).unwrap();
+		let first = ctx.encrypt_log(vec![], vec![2]).unwrap();
+		ctx.suspend();
+		ctx.activate(Some(contract())).unwrap();
+		assert_ne!(first, ctx.encrypt_log(vec![], vec![2]).unwrap());
+	}
+
+	#[test]
+	fn log_nonces_tell_topics_from_data() {
+		let topic = H256::from(1);
+		let seal = |topics: Vec<H256>, data: Vec<u8>| {
+			let mut ctx = ctx();
+			ctx.activate(Some(contract())).unwrap();
+			ctx.encrypt_log(topics, data).unwrap().1
+		};
+
+		// the blinded topic moved from the topics into the data
+		let blinded = ctx().contract_log_topic(&contract(), &topic);
+		let with_topic = seal(vec![topic], vec![2]);
+		let without_topic = seal(vec![], [&blinded[..], &[2]].concat());
+		assert_ne!(with_topic[..NONCE_SIZE], without_topic[..NONCE_SIZE]);
+	}
+
+	#[test]
+	fn state_storage_roundtrip() {
+		let mut state = get_temp_state();
+		let ctx: Box<ConfidentialCtx> = Box::new(ctx());
+		let ctx = Rc::new(RefCell::new(ctx));
+		state.confidential_ctx = Some(ctx.clone());
+		ctx.borrow_mut().activate(Some(contract())).unwrap();
+
+		let (key, value) = (H256::from(1), H256::from(2));
+		state.set_storage(&contract(), key, value).unwrap();
+		assert_eq!(state.storage_at(&contract(), &key).unwrap(), value);
+
+		// the plaintext is out of reach without the contract's keys
+		ctx.borrow_mut().deactivate();
+		assert_eq!(state.storage_at(&contract(), &key).unwrap(), H256::zero());
+	}
+}
@@ -814,15 +814,18 @@ impl<'a, B: 'a + StateBackend> Executive<'a, B> {
 		let mut subvmtracer = vm_tracer.prepare_subtrace(params.code.as_ref().expect("two ways into create (Externalities::create and Executive::transact_with_tracer); both place `Some(...)` `code` in `params`; qed"));
 
 		let mut subexttracer = ext_tracer.subtracer(&params.address);
-		let res = self.exec_vm(
-			schedule,
-			params,
-			&mut unconfirmed_substate,
-			OutputPolicy::InitContract(output.as_mut().or(trace_output.as_mut())),
-			&mut subtracer,
-			&mut subvmtracer,
-			&mut subexttracer,
-		);
+		let res = match self.state.vm_factory().check_create(&params, &schedule) {
+			Ok(()) => self.exec_vm(
+				schedule,
+				params,
+				&mut unconfirmed_substate,
+				OutputPolicy::InitContract(output.as_mut().or(trace_output.as_mut())),
+				&mut subtracer,
+				&mut subvmtracer,
+				&mut subexttracer,
+			),
+			Err(err) => Err(err),
+		};
 
 		vm_tracer.done_subtrace(subvmtracer);
 
@@ -969,6 +972,7 @@ impl<'a, B: 'a + StateBackend> Executive<'a, B> {
 			| Err(vm::Error::StackUnderflow { .. })
 			| Err(vm::Error::BuiltIn { .. })
 			| Err(vm::Error::Wasm { .. })
+			| Err(vm::Error::WasmTrap(_))
 			| Err(vm::Error::OutOfStack { .. })
 			| Err(vm::Error::MutableCallInStaticContext)
 			| Err(vm::Error::OutOfBounds)
@@ -1370,6 +1374,7 @@ mod tests {
 
 		let expected_vm_trace = VMTrace {
 			parent_step: 0,
+			wasm_output: vec![],
 			code: vec![124, 96, 16, 128, 96, 12, 96, 0, 57, 96, 0, 243, 0, 96, 0, 53, 84, 21, 96, 9, 87, 0, 91, 96, 32, 53, 96, 0, 53, 85, 96, 0, 82, 96, 29, 96, 3, 96, 23, 240, 96, 0, 85],
 			operations: vec![
 				VMOperation { pc: 0, instruction: 124, gas_cost: 3.into(), executed: Some(VMExecutedOperation { gas_used: 99997.into(), stack_push: vec_into![U256::from_dec_str("2589892687202724018173567190521546555304938078595079151649957320078677").unwrap()], mem_diff: None, store_diff: None }) },
@@ -1385,6 +1390,7 @@ mod tests {
 			subs: vec![
 				VMTrace {
 					parent_step: 6,
+					wasm_output: vec![],
 					code: vec![96, 16, 128, 96, 12, 96, 0, 57, 96, 0, 243, 0, 96, 0, 53, 84, 21, 96, 9, 87, 0, 91, 96, 32, 53, 96, 0, 53, 85],
 					operations: vec![
 						VMOperation { pc: 0, instruction: 96, gas_cost: 3.into(), executed: Some(VMExecutedOperation { gas_used: 67976.into(), stack_push: vec_into![16], mem_diff: None, store_diff: None }) },
@@ -1591,6 +1597,7 @@ mod tests {
 
 		let expected_vm_trace = VMTrace {
 			parent_step: 0,
+			wasm_output: vec![],
 			code: vec![
 				96, 16, 128, 96, 12, 96, 0, 57, 96, 0, 243, 0, 96, 0, 53, 84, 21, 96, 9, 87, 0, 91,
 				96, 32, 53, 96, 0, 53, 85,
@@ -19,6 +19,7 @@ use bytes::{Bytes, BytesRef};
 use ethereum_types::{Address, H256, U256};
 use evm::FinalizationResult;
 use executive::*;
+use log_entry::LogEntry;
 use machine::EthereumMachine as Machine;
 use state::{Backend as StateBackend, CleanupMode, State, Substate};
 use std::cmp;
@@ -29,8 +30,9 @@ use trace_ext::ExtTracer;
 use trace_ext::NoopExtTracer;
 use transaction::UNSIGNED_SENDER;
 use vm::{
-	self, ActionParams, ActionValue, CallType, ContractCreateResult, CreateContractAddress,
-	EnvInfo, Ext, MessageCallResult, OasisContract, OasisContractHeader, ReturnData, Schedule,
+	self, ActionParams, ActionValue, CallType, Comparison, ContractCreateResult,
+	CreateContractAddress, EnvInfo, Ext, MessageCallResult, OasisContract, OasisContractHeader,
+	ReturnData, Schedule,
 };
 
 /// Policy for handling output data on `RETURN` opcode.
@@ -248,15 +250,20 @@ where
 			// contract's header if it isn't already when the creator is a
 			// confidential contract. This is done to prevent confidential
 			// data from accidentially leaking into a non-confidential context.
+			// Public contracts called from a confidential one create public contracts.
 			match &self.state.confidential_ctx {
-				Some(ctx) if ctx.borrow().activated() => {
+				Some(ctx) if ctx.borrow().is_encrypting() => {
 					let mut new_header: Option<OasisContractHeader> = None;
 					let mut headerless_code = code.to_vec();
 					match OasisContract::from_code(code) {
 						Ok(Some(oc)) if !oc.confidential => {
+							// keep the cross-domain policy, if any
+							let flag = |allow: bool| if allow { Some(true) } else { None };
 							new_header = Some(OasisContractHeader::V1 {
 								confidential: Some(true),
 								expiry: oc.expiry,
+								allow_public_callees: flag(oc.allow_public_callees),
+								allow_public_callers: flag(oc.allow_public_callers),
 							});
 							headerless_code = oc.code.to_vec();
 						}
@@ -265,6 +272,8 @@ where
 							new_header = Some(OasisContractHeader::V1 {
 								confidential: Some(true),
 								expiry: None,
+								allow_public_callees: None,
+								allow_public_callers: None,
 							});
 						}
 						Err(_) => return ContractCreateResult::Failed,
@@ -459,6 +468,14 @@ where
 		Ok(self.state.code_size(address)?.unwrap_or(0))
 	}
 
+	fn extcodehash(&self, address: &Address) -> vm::Result<Option<H256>> {
+		if self.state.exists_and_not_null(address)? {
+			Ok(Some(self.state.code_hash(address)?))
+		} else {
+			Ok(None)
+		}
+	}
+
 	fn ret(mut self, gas: &U256, data: &ReturnData, apply_state: bool) -> vm::Result<U256>
 	where
 		Self: Sized,
@@ -500,23 +517,30 @@ where
 	}
 
 	fn log(&mut self, topics: Vec<H256>, data: &[u8]) -> vm::Result<()> {
-		use log_entry::LogEntry;
-
 		if self.static_flag {
 			return Err(vm::Error::MutableCallInStaticContext);
 		}
 
 		let address = self.origin_info.address.clone();
+		let (topics, data) = if self.schedule.confidential_logs {
+			self.state.to_log(topics, data.to_vec())?
+		} else {
+			(topics, data.to_vec())
+		};
 
 		self.substate.logs.push(LogEntry {
 			address: address,
 			topics: topics,
-			data: data.to_vec(),
+			data: data,
 		});
 
 		Ok(())
 	}
 
+	fn logs(&self) -> &[LogEntry] {
+		&self.substate.logs
+	}
+
 	fn suicide(&mut self, refund_address: &Address) -> vm::Result<()> {
 		if self.static_flag {
 			return Err(vm::Error::MutableCallInStaticContext);
@@ -591,12 +615,30 @@ where
 			.trace_executed(gas_used, stack_push, mem_diff, store_diff)
 	}
 
+	fn trace_wasm_output(&mut self, fd: u32, data: &[u8]) {
+		self.vm_tracer.trace_wasm_output(fd, data)
+	}
+
+	fn traces_wasm_output(&self) -> bool {
+		self.vm_tracer.traces_wasm_output()
+	}
+
+	fn trace_comparison(&mut self, comparison: &Comparison) {
+		self.vm_tracer.trace_comparison(comparison)
+	}
+
 	fn is_confidential_contract(&self, contract: &Address) -> vm::Result<bool> {
 		self.state
 			.is_confidential_contract(contract)
 			.map_err(|err| vm::Error::Confidential(err))
 	}
 
+	fn oasis_contract(&self, contract: &Address) -> vm::Result<Option<OasisContract>> {
+		self.state
+			.oasis_contract(contract)
+			.map_err(|err| vm::Error::Confidential(err))
+	}
+
 	fn as_kvstore(&self) -> &dyn blockchain_traits::KVStore {
 		self
 	}
@@ -1071,24 +1113,34 @@ mod tests {
 		expect_code(Some(OasisContractHeader::V1 {
 			confidential: None,
 			expiry: None,
+			allow_public_callees: None,
+			allow_public_callers: None,
 		}));
 		expect_code(Some(OasisContractHeader::V1 {
 			confidential: Some(false),
 			expiry: Some(1),
+			allow_public_callees: None,
+			allow_public_callers: None,
 		}));
 		expect_code(Some(OasisContractHeader::V1 {
 			confidential: None,
 			expiry: Some(2),
+			allow_public_callees: None,
+			allow_public_callers: None,
 		}));
 		expect_code(Some(OasisContractHeader::V1 {
 			confidential: Some(true),
 			expiry: Some(3),
+			allow_public_callees: None,
+			allow_public_callers: None,
 		}));
 
 		// expect that invalid Oasis header causes failure
 		let header = OasisContractHeader::V1 {
 			confidential: None,
 			expiry: None,
+			allow_public_callees: None,
+			allow_public_callers: None,
 		};
 		let mut invalid_headered_deploycode = vm::OASIS_HEADER_PREFIX.to_vec();
 		invalid_headered_deploycode.extend(&deploycode);
@@ -1097,4 +1149,94 @@ mod tests {
 			_ => false,
 		});
 	}
+
+	#[test]
+	fn log_confidential() {
+		use confidential::{SessionClient, SoftwareConfidentialCtx, NONCE_SIZE};
+		use ethereum_types::BloomInput;
+		use std::{cell::RefCell, rc::Rc};
+		use vm::ConfidentialCtx;
+
+		let (caller, callee) = (Address::from(0x0a), Address::from(0x0b));
+		let topic = H256::from(1);
+		let ctx = SoftwareConfidentialCtx::new([7; 32]);
+		let caller_key = ctx.public_key(&caller);
+		let client = SessionClient::new([1; 32]);
+
+		let mut setup = TestSetup::new();
+		let ctx: Box<ConfidentialCtx> = Box::new(ctx);
+		let ctx = Rc::new(RefCell::new(ctx));
+		setup.state.confidential_ctx = Some(ctx.clone());
+
+		// a session with the caller, which calls the callee
+		ctx.borrow_mut().activate(Some(caller)).unwrap();
+		let request = client
+			.encrypt(&caller_key, &[0; NONCE_SIZE], b"call")
+			.unwrap();
+		ctx.borrow_mut().decrypt_session(request).unwrap();
+
+		let log = |setup: &mut TestSetup, address: Address, data: &[u8]| {
+			let mut tracer = NoopTracer;
+			let mut vm_tracer = NoopVMTracer;
+			let mut ext_tracer = NoopExtTracer;
+			let mut origin = get_test_origin();
+			origin.address = address;
+			let mut ext = Externalities::new(
+				&mut setup.state,
+				&setup.env_info,
+				&setup.machine,
+				0,
+				origin,
+				&mut setup.sub_state,
+				OutputPolicy::InitContract(None),
+				&mut tracer,
+				&mut vm_tracer,
+				&mut ext_tracer,
+				false,
+			);
+			ext.schedule.confidential_logs = true;
+			ext.log(vec![topic], data).unwrap();
+		};
+
+		log(&mut setup, caller, b"outer");
+		let previous = ctx.borrow_mut().activate(Some(callee)).unwrap();
+		log(&mut setup, callee, b"inner");
+		ctx.borrow_mut().activate(previous).unwrap();
+		log(&mut setup, caller, b"outer");
+
+		// the client reads and filters the logs of every contract reached by its session
+		let blinded = client.log_topic(&caller_key, &topic);
+		let logs = &setup.sub_state.logs;
+		assert_eq!(logs.len(), 3);
+		for (entry, data) in logs.iter().zip(&[&b"outer"[..], b"inner", b"outer"]) {
+			assert_eq!(entry.topics, vec![blinded]);
+			assert!(entry.bloom().contains_input(BloomInput::Raw(&blinded)));
+			assert!(!entry.bloom().contains_input(BloomInput::Raw(&topic)));
+			assert_ne!(&entry.data[..], *data);
+			assert_eq!(
+				client
+					.decrypt_log(&caller_key, &entry.topics, &entry.data)
+					.unwrap(),
+				data.to_vec()
+			);
+		}
+		// identical logs are not linkable through their data
+		assert_ne!(logs[0].data, logs[2].data);
+
+		// without a session, logs are sealed for the contract emitting them
+		ctx.borrow_mut().deactivate();
+		ctx.borrow_mut().activate(Some(callee)).unwrap();
+		log(&mut setup, callee, b"create");
+		let entry = setup.sub_state.logs.last().unwrap();
+		let ctx = SoftwareConfidentialCtx::new([7; 32]);
+		assert_eq!(entry.topics, vec![ctx.contract_log_topic(&callee, &topic)]);
+		assert_eq!(
+			ctx.decrypt_contract_log(&callee, &entry.topics, &entry.data)
+				.unwrap(),
+			b"create".to_vec()
+		);
+		assert!(ctx
+			.decrypt_contract_log(&caller, &entry.topics, &entry.data)
+			.is_err());
+	}
 }
@@ -16,10 +16,14 @@
 
 use bytes::Bytes;
 use evm::{Factory as EvmFactory, VMType};
-use std::{cell::RefCell, rc::Rc};
-use vm::{ssvm::Ssvm, ActionParams, ConfidentialCtx, OasisVm, Schedule, Vm};
+use std::{cell::RefCell, rc::Rc, sync::Arc};
+use vm::{
+	self,
+	ssvm::{EvmcInstances, Ssvm},
+	ActionParams, ConfidentialCtx, DifferentialVm, OasisVm, Schedule, Vm, WasmBackend,
+};
 
-use wasm::WasmInterpreter;
+use wasm::{ModuleCache, WasmInterpreter};
 
 const WASM_MAGIC_NUMBER: &'static [u8; 4] = b"\0asm";
 
@@ -27,6 +31,12 @@ const WASM_MAGIC_NUMBER: &'static [u8; 4] = b"\0asm";
 #[derive(Default, Clone)]
 pub struct VmFactory {
 	evm: EvmFactory,
+	/// Compiled modules shared by the wasmi interpreters.
+	wasm_cache: Arc<ModuleCache>,
+	/// EVMC libraries loaded by the SSVM vms.
+	evmc_instances: Arc<EvmcInstances>,
+	/// Runtime for wasm contracts, overriding the one chosen by the chain spec.
+	wasm_backend: Option<WasmBackend>,
 }
 
 impl VmFactory {
@@ -36,12 +46,22 @@ impl VmFactory {
 		params: &ActionParams,
 		schedule: &Schedule,
 	) -> Box<Vm> {
-		let vm = {
-			if schedule.wasm.is_some()
-				&& params.code.as_ref().map_or(false, |code| {
-					code.len() > 4 && &code[0..4] == WASM_MAGIC_NUMBER
-				}) {
-				Box::new(Ssvm::new())
+		let vm: Box<Vm> = {
+			if is_wasm(params, schedule) {
+				match self.wasm_backend.unwrap_or(schedule.wasm_backend) {
+					WasmBackend::Wasmi => Box::new(WasmInterpreter::new(self.wasm_cache.clone())),
+					WasmBackend::Evmc => Box::new(Ssvm::from_schedule(
+						self.evmc_instances.clone(),
+						&schedule.evmc,
+					)),
+					WasmBackend::Differential => Box::new(DifferentialVm::new(
+						Box::new(WasmInterpreter::new(self.wasm_cache.clone())),
+						Box::new(Ssvm::from_schedule(
+							self.evmc_instances.clone(),
+							&schedule.evmc,
+						)),
+					)),
+				}
 			} else {
 				self.evm.create(&params.gas)
 			}
@@ -49,19 +69,62 @@ impl VmFactory {
 		Box::new(OasisVm::new(ctx, vm))
 	}
 
+	/// Checks the code of a contract being deployed with `params` before it is
+	/// executed: against the wasm policy whichever runtime executes it, and once
+	/// `schedule.wasm_validation` is set, fully validated if wasmi executes it.
+	pub fn check_create(&self, params: &ActionParams, schedule: &Schedule) -> vm::Result<()> {
+		if !is_wasm(params, schedule) {
+			return Ok(());
+		}
+		match self.wasm_backend.unwrap_or(schedule.wasm_backend) {
+			WasmBackend::Wasmi | WasmBackend::Differential if schedule.wasm_validation => {
+				wasm::validate(params, schedule)
+			}
+			_ => wasm::check_policy(params, schedule),
+		}
+	}
+
 	pub fn new(evm: VMType, cache_size: usize) -> Self {
 		VmFactory {
 			evm: EvmFactory::new(evm, cache_size),
+			wasm_cache: Arc::new(ModuleCache::new(cache_size)),
+			evmc_instances: Default::default(),
+			wasm_backend: None,
 		}
 	}
+
+	/// Executes wasm contracts with `backend`, whatever the chain spec says.
+	pub fn with_wasm_backend(mut self, backend: WasmBackend) -> Self {
+		self.wasm_backend = Some(backend);
+		self
+	}
+
+	/// Unloads the EVMC library at `library`, so that the next execution loads it
+	/// again. Returns true if it was loaded.
+	pub fn unload_evmc_library(&self, library: &str) -> bool {
+		self.evmc_instances.unload(library)
+	}
 }
 
 impl From<EvmFactory> for VmFactory {
 	fn from(evm: EvmFactory) -> Self {
-		VmFactory { evm: evm }
+		VmFactory {
+			evm: evm,
+			wasm_cache: Default::default(),
+			evmc_instances: Default::default(),
+			wasm_backend: None,
+		}
 	}
 }
 
+/// Whether `params` hold wasm code, to be run as such under `schedule`.
+fn is_wasm(params: &ActionParams, schedule: &Schedule) -> bool {
+	schedule.wasm.is_some()
+		&& params.code.as_ref().map_or(false, |code| {
+			code.len() > 4 && &code[0..4] == WASM_MAGIC_NUMBER
+		})
+}
+
 /// Collection of factories.
 #[derive(Default, Clone)]
 pub struct Factories {
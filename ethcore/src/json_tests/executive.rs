@@ -21,6 +21,7 @@ use evm::{Finalize, VMType};
 use executive::*;
 use externalities::*;
 use hash::keccak;
+use log_entry::LogEntry;
 use machine::EthereumMachine as Machine;
 use rlp::RlpStream;
 use state::{Backend as StateBackend, State, Substate};
@@ -187,10 +188,18 @@ where
 		self.ext.extcodesize(address)
 	}
 
+	fn extcodehash(&self, address: &Address) -> vm::Result<Option<H256>> {
+		self.ext.extcodehash(address)
+	}
+
 	fn log(&mut self, topics: Vec<H256>, data: &[u8]) -> vm::Result<()> {
 		self.ext.log(topics, data)
 	}
 
+	fn logs(&self) -> &[LogEntry] {
+		self.ext.logs()
+	}
+
 	fn ret(self, gas: &U256, data: &ReturnData, apply_state: bool) -> Result<U256, vm::Error> {
 		self.ext.ret(gas, data, apply_state)
 	}
@@ -61,6 +61,7 @@
 extern crate bloomchain;
 extern crate bn;
 extern crate byteorder;
+extern crate chacha20poly1305;
 pub extern crate common_types as types;
 extern crate ethcore_bloom_journal as bloom_journal;
 extern crate ethcore_bytes as bytes;
@@ -96,6 +97,7 @@ extern crate rustc_hex;
 extern crate triehash;
 extern crate unexpected;
 extern crate util_error;
+extern crate x25519_dalek;
 pub extern crate vm;
 
 extern crate wasm;
@@ -130,6 +132,7 @@ extern crate evm;
 pub mod views;
 
 pub mod block;
+pub mod confidential;
 pub mod db;
 pub mod encoded;
 pub mod engines;
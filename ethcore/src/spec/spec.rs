@@ -121,6 +121,12 @@ pub struct CommonParams {
 	pub eip214_transition: BlockNumber,
 	/// Number of first block where EIP-145 rules begin.
 	pub eip145_transition: BlockNumber,
+	/// Number of first block where the net gas metering of EIP-1283 begins. The
+	/// EVM of this client does not meter gas that way, so this only selects the
+	/// Constantinople revision for EVMC.
+	pub eip1283_transition: BlockNumber,
+	/// Number of first block where EIP-1283 is disabled again, as in Petersburg.
+	pub eip1283_disable_transition: BlockNumber,
 	/// Number of first block where dust cleanup rules (EIP-168 and EIP169) begin.
 	pub dust_protection_transition: BlockNumber,
 	/// Nonce cap increase per block. Nonce cap is only checked if dust protection is enabled.
@@ -129,6 +135,29 @@ pub struct CommonParams {
 	pub remove_dust_contracts: bool,
 	/// Wasm activation blocknumber, if any disabled initially.
 	pub wasm_activation_transition: BlockNumber,
+	/// Runtime wasm contracts are executed with.
+	pub wasm_backend: ::vm::WasmBackend,
+	/// Restrictions on the wasm modules that can be deployed.
+	pub wasm_policy: ::vm::WasmPolicy,
+	/// Number of first block where `wasm_policy` is enforced.
+	pub wasm_policy_transition: BlockNumber,
+	/// Number of first block where wasm contracts run by wasmi are validated on deploy.
+	pub wasm_validation_transition: BlockNumber,
+	/// Number of first block where WASI contracts get the second version of environment variables.
+	pub wasi_environ_v2_transition: BlockNumber,
+	/// Number of first block where WASI contracts have directories and symbolic links.
+	pub wasi_dirs_transition: BlockNumber,
+	/// Number of first block where WASI clocks advance with gas and can be waited on.
+	pub wasi_clocks_transition: BlockNumber,
+	/// Number of first block where WASI contracts receive the call metadata as arguments.
+	pub wasi_args_transition: BlockNumber,
+	/// Number of first block where logs emitted in a confidential context are encrypted.
+	pub confidential_logs_transition: BlockNumber,
+	/// EVMC library wasm contracts are executed with, if pinned by the spec.
+	pub evmc_library: Option<String>,
+	/// Secret from which a development chain without an enclave derives the keys of
+	/// confidential contracts, with `SoftwareConfidentialCtx`.
+	pub dev_confidential_secret: Option<H256>,
 	/// Gas limit bound divisor (how much gas limit can change per block)
 	pub gas_limit_bound_divisor: U256,
 	/// Registrar contract address.
@@ -151,7 +180,10 @@ impl CommonParams {
 	/// Schedule for an EVM in the post-EIP-150-era of the Ethereum main net.
 	pub fn schedule(&self, block_number: u64) -> ::vm::Schedule {
 		if block_number < self.eip150_transition {
-			::vm::Schedule::new_homestead()
+			let mut schedule = ::vm::Schedule::new_homestead();
+			schedule.evmc.library = self.evmc_library.clone();
+			schedule.evmc.revision = ::vm::EvmcRevision::Homestead;
+			schedule
 		} else {
 			let max_code_size = self.max_code_size(block_number);
 			let mut schedule = ::vm::Schedule::new_post_eip150(
@@ -192,7 +224,51 @@ impl CommonParams {
 			};
 		}
 		if block_number >= self.wasm_activation_transition {
-			schedule.wasm = Some(Default::default());
+			let policy = if block_number >= self.wasm_policy_transition {
+				self.wasm_policy.clone()
+			} else {
+				Default::default()
+			};
+			schedule.wasm = Some(::vm::WasmCosts {
+				policy,
+				..Default::default()
+			});
+		}
+		schedule.wasm_backend = self.wasm_backend;
+		schedule.wasm_validation = block_number >= self.wasm_validation_transition;
+		if block_number >= self.wasi_environ_v2_transition {
+			schedule.wasi_environ = ::vm::WasiEnviron::V2;
+		}
+		schedule.wasi_dirs = block_number >= self.wasi_dirs_transition;
+		schedule.wasi_clocks = block_number >= self.wasi_clocks_transition;
+		schedule.wasi_args = block_number >= self.wasi_args_transition;
+		schedule.confidential_logs = block_number >= self.confidential_logs_transition;
+		schedule.evmc.library = self.evmc_library.clone();
+		schedule.evmc.revision = self.evmc_revision(block_number);
+	}
+
+	/// EVMC revision matching the hard forks activated at the given block, past EIP-150.
+	///
+	/// Past Byzantium, the revision is Constantinople while the net gas metering of
+	/// EIP-1283 is enabled, and Petersburg otherwise.
+	fn evmc_revision(&self, block_number: u64) -> ::vm::EvmcRevision {
+		if block_number >= self.eip145_transition {
+			if block_number >= self.eip1283_transition
+				&& block_number < self.eip1283_disable_transition
+			{
+				::vm::EvmcRevision::Constantinople
+			} else {
+				::vm::EvmcRevision::Petersburg
+			}
+		} else if block_number >= self.eip140_transition
+			&& block_number >= self.eip211_transition
+			&& block_number >= self.eip214_transition
+		{
+			::vm::EvmcRevision::Byzantium
+		} else if block_number >= self.eip161abc_transition {
+			::vm::EvmcRevision::SpuriousDragon
+		} else {
+			::vm::EvmcRevision::TangerineWhistle
 		}
 	}
 
@@ -202,6 +278,20 @@ impl CommonParams {
 	}
 }
 
+fn wasm_policy(policy: ethjson::spec::WasmPolicy) -> ::vm::WasmPolicy {
+	let default = ::vm::WasmPolicy::default();
+	::vm::WasmPolicy {
+		forbid_floats: policy.forbid_floats.unwrap_or(default.forbid_floats),
+		max_initial_pages: policy
+			.max_initial_pages
+			.map_or(default.max_initial_pages, Into::into),
+		max_pages: policy.max_pages.map_or(default.max_pages, Into::into),
+		allowed_imports: policy
+			.allowed_imports
+			.map(|imports| imports.into_iter().collect()),
+	}
+}
+
 impl From<ethjson::spec::Params> for CommonParams {
 	fn from(p: ethjson::spec::Params) -> Self {
 		CommonParams {
@@ -250,6 +340,12 @@ impl From<ethjson::spec::Params> for CommonParams {
 			eip145_transition: p
 				.eip145_transition
 				.map_or_else(BlockNumber::max_value, Into::into),
+			eip1283_transition: p
+				.eip1283_transition
+				.map_or_else(BlockNumber::max_value, Into::into),
+			eip1283_disable_transition: p
+				.eip1283_disable_transition
+				.map_or_else(BlockNumber::max_value, Into::into),
 			eip214_transition: p
 				.eip214_transition
 				.map_or_else(BlockNumber::max_value, Into::into),
@@ -273,6 +369,35 @@ impl From<ethjson::spec::Params> for CommonParams {
 			wasm_activation_transition: p
 				.wasm_activation_transition
 				.map_or_else(BlockNumber::max_value, Into::into),
+			wasm_backend: match p.wasm_backend {
+				Some(ethjson::spec::WasmBackend::Wasmi) => ::vm::WasmBackend::Wasmi,
+				Some(ethjson::spec::WasmBackend::Differential) => ::vm::WasmBackend::Differential,
+				Some(ethjson::spec::WasmBackend::Evmc) | None => ::vm::WasmBackend::Evmc,
+			},
+			wasm_policy: p.wasm_policy.map_or_else(Default::default, wasm_policy),
+			wasm_policy_transition: p
+				.wasm_policy_transition
+				.map_or_else(BlockNumber::max_value, Into::into),
+			wasm_validation_transition: p
+				.wasm_validation_transition
+				.map_or_else(BlockNumber::max_value, Into::into),
+			wasi_environ_v2_transition: p
+				.wasi_environ_v2_transition
+				.map_or_else(BlockNumber::max_value, Into::into),
+			wasi_dirs_transition: p
+				.wasi_dirs_transition
+				.map_or_else(BlockNumber::max_value, Into::into),
+			wasi_clocks_transition: p
+				.wasi_clocks_transition
+				.map_or_else(BlockNumber::max_value, Into::into),
+			wasi_args_transition: p
+				.wasi_args_transition
+				.map_or_else(BlockNumber::max_value, Into::into),
+			confidential_logs_transition: p
+				.confidential_logs_transition
+				.map_or_else(BlockNumber::max_value, Into::into),
+			evmc_library: p.evmc.and_then(|evmc| evmc.library),
+			dev_confidential_secret: p.dev_confidential_secret.map(Into::into),
 			benchmarking: p.benchmarking.unwrap_or(false),
 		}
 	}
@@ -979,6 +1104,52 @@ mod tests {
 		assert!(Spec::load(&[] as &[u8]).is_err());
 	}
 
+	#[test]
+	fn evmc_revision_follows_transitions() {
+		let mut params = CommonParams::default();
+		params.evmc_library = Some("/ssvm/libssvm-evmc.so".to_owned());
+		params.eip150_transition = 5;
+		params.eip161abc_transition = 10;
+		params.eip140_transition = 20;
+		params.eip211_transition = 20;
+		params.eip214_transition = 20;
+		params.eip145_transition = 30;
+
+		let revision_at = |block_number| {
+			let schedule = params.schedule(block_number);
+			assert_eq!(
+				schedule.evmc.library,
+				Some("/ssvm/libssvm-evmc.so".to_owned())
+			);
+			schedule.evmc.revision
+		};
+		assert_eq!(revision_at(0), ::vm::EvmcRevision::Homestead);
+		assert_eq!(revision_at(5), ::vm::EvmcRevision::TangerineWhistle);
+		assert_eq!(revision_at(10), ::vm::EvmcRevision::SpuriousDragon);
+		assert_eq!(revision_at(20), ::vm::EvmcRevision::Byzantium);
+		assert_eq!(revision_at(29), ::vm::EvmcRevision::Byzantium);
+		assert_eq!(revision_at(30), ::vm::EvmcRevision::Petersburg);
+
+		params.eip1283_transition = 30;
+		params.eip1283_disable_transition = 40;
+		let revision_at = |block_number| params.schedule(block_number).evmc.revision;
+		assert_eq!(revision_at(30), ::vm::EvmcRevision::Constantinople);
+		assert_eq!(revision_at(39), ::vm::EvmcRevision::Constantinople);
+		assert_eq!(revision_at(40), ::vm::EvmcRevision::Petersburg);
+	}
+
+	#[test]
+	fn wasm_policy_follows_transition() {
+		let mut params = CommonParams::default();
+		params.wasm_activation_transition = 0;
+		params.wasm_policy.forbid_floats = true;
+		params.wasm_policy_transition = 10;
+
+		let policy_at = |block_number| params.schedule(block_number).wasm.unwrap().policy;
+		assert_eq!(policy_at(9), ::vm::WasmPolicy::default());
+		assert_eq!(policy_at(10), params.wasm_policy);
+	}
+
 	/*
 	#[test]
 	fn test_chain() {
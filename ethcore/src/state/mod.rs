@@ -76,6 +76,9 @@ pub struct ApplyOutcome<T, V> {
 	pub trace: Vec<T>,
 	/// The VM trace for the applied transaction, None if tracing was not produced.
 	pub vm_trace: Option<V>,
+	/// Why the applied transaction failed, if it did. The receipt only records whether
+	/// it did, see `TransactionOutcome`.
+	pub error: Option<trace::TraceError>,
 }
 
 /// Result type for the execution ("application") of a transaction.
@@ -934,6 +937,7 @@ impl<B: Backend> State<B> {
 		};
 
 		let output = e.output;
+		let error = e.exception.as_ref().map(trace::TraceError::from);
 		let receipt = Receipt::new(outcome, e.cumulative_gas_used, e.logs);
 		trace!(target: "state", "Transaction receipt: {:?}", receipt);
 
@@ -942,6 +946,7 @@ impl<B: Backend> State<B> {
 			output,
 			trace: e.trace,
 			vm_trace: e.vm_trace,
+			error,
 		})
 	}
 
@@ -1346,18 +1351,19 @@ impl<B: Backend> State<B> {
 	}
 
 	pub fn is_confidential_contract(&self, address: &Address) -> Result<bool, String> {
-		let code = self.code(address).map_err(|err| err.to_string())?;
-		let contract = {
-			if let Some(ref code) = code.clone() {
-				OasisContract::from_code(code)?
-			} else {
-				None
-			}
-		};
-
+		let contract = self.oasis_contract(address)?;
 		Ok(contract.as_ref().map_or(false, |c| c.confidential))
 	}
 
+	/// Returns the header of the contract at `address`, if it has one.
+	pub fn oasis_contract(&self, address: &Address) -> Result<Option<OasisContract>, String> {
+		let code = self.code(address).map_err(|err| err.to_string())?;
+		match code {
+			Some(ref code) => OasisContract::from_code(code),
+			None => Ok(None),
+		}
+	}
+
 	pub fn is_encrypting(&self) -> bool {
 		self.confidential_ctx.is_some()
 			&& self
@@ -1420,6 +1426,21 @@ impl<B: Backend> State<B> {
 		}
 	}
 
+	/// Returns the given log topics and data in the form in which they are published.
+	/// If a confidential context is open, then encrypts them. Otherwise returns them
+	/// as given.
+	pub fn to_log(&self, topics: Vec<H256>, data: Vec<u8>) -> vm::Result<(Vec<H256>, Vec<u8>)> {
+		if self.is_encrypting() {
+			self.confidential_ctx
+				.as_ref()
+				.expect("Cannot encrypt without a confidential context")
+				.borrow_mut()
+				.encrypt_log(topics, data)
+		} else {
+			Ok((topics, data))
+		}
+	}
+
 	/// Transforms the given value--from storage--into its plaintext representation.
 	/// If a confidential context is open, then decrypts the value, otherwise returns
 	/// the value as given.
@@ -1632,6 +1653,7 @@ mod tests {
 		}];
 
 		assert_eq!(result.trace, expected_trace);
+		assert_eq!(result.error, Some(TraceError::OutOfGas));
 	}
 
 	#[test]
@@ -585,4 +585,12 @@ impl ConfidentialCtx for MockConfidentialContext {
 	fn decrypt_storage_value(&self, storage_key: Vec<u8>, data: Vec<u8>) -> vm::Result<Vec<u8>> {
 		Ok(data)
 	}
+
+	fn encrypt_log(
+		&mut self,
+		topics: Vec<H256>,
+		data: Vec<u8>,
+	) -> vm::Result<(Vec<H256>, Vec<u8>)> {
+		Ok((topics, data))
+	}
 }
@@ -20,7 +20,7 @@ use bytes::Bytes;
 use ethereum_types::{Address, U256};
 use trace::trace::{
 	Action, Call, CallResult, Create, CreateResult, MemoryDiff, Res, Reward, RewardType,
-	StorageDiff, Suicide, VMExecutedOperation, VMOperation, VMTrace,
+	StorageDiff, Suicide, VMExecutedOperation, VMOperation, VMTrace, WasmOutput,
 };
 use trace::{FlatTrace, TraceError, Tracer, VMTracer};
 use vm::ActionParams;
@@ -262,6 +262,7 @@ impl ExecutiveVMTracer {
 				code: vec![],
 				operations: vec![Default::default()], // prefill with a single entry so that prepare_subtrace can get the parent_step
 				subs: vec![],
+				wasm_output: vec![],
 			},
 		}
 	}
@@ -309,6 +310,17 @@ impl VMTracer for ExecutiveVMTracer {
 			.executed = Some(ex);
 	}
 
+	fn trace_wasm_output(&mut self, fd: u32, data: &[u8]) {
+		self.data.wasm_output.push(WasmOutput {
+			fd,
+			data: data.to_vec(),
+		});
+	}
+
+	fn traces_wasm_output(&self) -> bool {
+		true
+	}
+
 	fn prepare_subtrace(&self, code: &[u8]) -> Self {
 		ExecutiveVMTracer {
 			data: VMTrace {
@@ -316,6 +328,7 @@ impl VMTracer for ExecutiveVMTracer {
 				code: code.to_vec(),
 				operations: vec![],
 				subs: vec![],
+				wasm_output: vec![],
 			},
 		}
 	}
@@ -34,7 +34,7 @@ pub use self::types::error::Error as TraceError;
 pub use self::types::filter::{AddressesFilter, Filter};
 pub use self::types::flat::{FlatBlockTraces, FlatTrace, FlatTransactionTraces};
 pub use self::types::trace::{
-	MemoryDiff, RewardType, StorageDiff, VMExecutedOperation, VMOperation, VMTrace,
+	MemoryDiff, RewardType, StorageDiff, VMExecutedOperation, VMOperation, VMTrace, WasmOutput,
 };
 pub use self::types::{filter, flat, localized, trace, Tracing};
 
@@ -43,7 +43,7 @@ use bytes::Bytes;
 use ethereum_types::{Address, H256, U256};
 use header::BlockNumber;
 use kvdb::DBTransaction;
-use vm::ActionParams;
+use vm::{ActionParams, Comparison};
 
 /// This trait is used by executive to build traces.
 pub trait Tracer: Send {
@@ -129,6 +129,17 @@ pub trait VMTracer: Send {
 	) {
 	}
 
+	/// Trace bytes a wasm contract wrote to its standard output (`fd` 1) or error (`fd` 2).
+	fn trace_wasm_output(&mut self, _fd: u32, _data: &[u8]) {}
+
+	/// Whether `trace_wasm_output` records anything.
+	fn traces_wasm_output(&self) -> bool {
+		false
+	}
+
+	/// Trace how a differential run of the current call compared on both vms.
+	fn trace_comparison(&mut self, _comparison: &Comparison) {}
+
 	/// Spawn subtracer which will be used to trace deeper levels of execution.
 	fn prepare_subtrace(&self, code: &[u8]) -> Self
 	where
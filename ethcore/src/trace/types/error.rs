@@ -18,7 +18,7 @@
 
 use rlp::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
 use std::fmt;
-use vm::Error as VmError;
+use vm::{Error as VmError, WasmFrame, WasmTrap};
 
 /// Trace evm errors.
 #[derive(Debug, PartialEq, Clone)]
@@ -43,6 +43,8 @@ pub enum Error {
 	MutableCallInStaticContext,
 	/// Wasm error
 	Wasm,
+	/// Wasm contract trapped.
+	WasmTrap(WasmTrap),
 	/// Contract tried to access past the return data buffer.
 	OutOfBounds,
 	/// Execution has been reverted with REVERT instruction.
@@ -63,6 +65,7 @@ impl<'a> From<&'a VmError> for Error {
 			VmError::OutOfStack { .. } => Error::OutOfStack,
 			VmError::BuiltIn { .. } => Error::BuiltIn,
 			VmError::Wasm { .. } => Error::Wasm,
+			VmError::WasmTrap(ref trap) => Error::WasmTrap(trap.clone()),
 			VmError::Internal(_) => Error::Internal,
 			VmError::MutableCallInStaticContext => Error::MutableCallInStaticContext,
 			VmError::OutOfBounds => Error::OutOfBounds,
@@ -82,6 +85,9 @@ impl From<VmError> for Error {
 impl fmt::Display for Error {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 		use self::Error::*;
+		if let WasmTrap(ref trap) = *self {
+			return write!(f, "Wasm contract trap: {}", trap);
+		}
 		let message = match *self {
 			OutOfGas => "Out of gas",
 			BadJumpDestination => "Bad jump destination",
@@ -90,6 +96,7 @@ impl fmt::Display for Error {
 			OutOfStack => "Out of stack",
 			BuiltIn => "Built-in failed",
 			Wasm => "Wasm runtime error",
+			WasmTrap(_) => unreachable!("formatted above; qed"),
 			Internal => "Internal error",
 			MutableCallInStaticContext => "Mutable Call In Static Context",
 			OutOfBounds => "Out of bounds",
@@ -114,6 +121,18 @@ impl Encodable for Error {
 			BuiltIn => 6,
 			MutableCallInStaticContext => 7,
 			Wasm => 8,
+			WasmTrap(ref trap) => {
+				s.begin_list(3);
+				s.append(&14u8);
+				s.append(&trap.kind);
+				s.begin_list(trap.backtrace.len());
+				for frame in &trap.backtrace {
+					s.begin_list(2);
+					s.append(&frame.index);
+					s.append(&frame.name.as_ref().map_or("", |name| name.as_str()));
+				}
+				return;
+			}
 			OutOfBounds => 9,
 			Reverted => 10,
 			ContractExpired => 11,
@@ -127,6 +146,27 @@ impl Encodable for Error {
 impl Decodable for Error {
 	fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
 		use self::Error::*;
+		if rlp.is_list() {
+			let value: u8 = rlp.val_at(0)?;
+			if value != 14 {
+				return Err(DecoderError::Custom("Invalid error type"));
+			}
+			let backtrace = rlp
+				.at(2)?
+				.iter()
+				.map(|frame| {
+					let name: String = frame.val_at(1)?;
+					Ok(WasmFrame {
+						index: frame.val_at(0)?,
+						name: if name.is_empty() { None } else { Some(name) },
+					})
+				})
+				.collect::<Result<_, DecoderError>>()?;
+			return Ok(WasmTrap(::vm::WasmTrap {
+				kind: rlp.val_at(1)?,
+				backtrace,
+			}));
+		}
 		let value: u8 = rlp.as_val()?;
 		match value {
 			0 => Ok(OutOfGas),
@@ -151,6 +191,7 @@ impl Decodable for Error {
 mod tests {
 	use super::Error;
 	use rlp::*;
+	use vm::{WasmFrame, WasmTrap};
 
 	#[test]
 	fn encode_error() {
@@ -163,4 +204,30 @@ mod tests {
 		assert!(s.is_finished(), "List should be finished now");
 		s.out();
 	}
+
+	#[test]
+	fn encode_wasm_trap() {
+		let err = Error::WasmTrap(WasmTrap {
+			kind: "Unreachable".to_owned(),
+			backtrace: vec![
+				WasmFrame {
+					index: 7,
+					name: Some("rust_panic".to_owned()),
+				},
+				WasmFrame {
+					index: 3,
+					name: None,
+				},
+			],
+		});
+		assert_eq!(decode::<Error>(&encode(&err)).unwrap(), err);
+		assert_eq!(decode::<Error>(&encode(&Error::Wasm)).unwrap(), Error::Wasm);
+		// errors encoded before wasm traps were recorded decode as before
+		assert_eq!(decode::<Error>(&[0x08]).unwrap(), Error::Wasm);
+		assert_eq!(decode::<Error>(&[0x0c]).unwrap(), Error::Confidential);
+		assert_eq!(
+			err.to_string(),
+			"Wasm contract trap: Unreachable in rust_panic (#7), called from #3"
+		);
+	}
 }
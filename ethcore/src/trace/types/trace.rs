@@ -419,7 +419,16 @@ pub struct VMOperation {
 	pub executed: Option<VMExecutedOperation>,
 }
 
-#[derive(Debug, Clone, PartialEq, Default, RlpEncodable, RlpDecodable)]
+#[derive(Debug, Clone, PartialEq, RlpEncodable, RlpDecodable)]
+/// Bytes a wasm contract wrote to its standard output or error.
+pub struct WasmOutput {
+	/// The file descriptor written to, 1 for stdout and 2 for stderr.
+	pub fd: u32,
+	/// The bytes written.
+	pub data: Bytes,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
 /// A record of a full VM trace for a CALL/CREATE.
 pub struct VMTrace {
 	/// The step (i.e. index into operations) at which this trace corresponds.
@@ -431,4 +440,70 @@ pub struct VMTrace {
 	/// The sub traces for each interior action performed as part of this call/create.
 	/// Thre is a 1:1 correspondance between these and a CALL/CREATE/CALLCODE/DELEGATECALL instruction.
 	pub subs: Vec<VMTrace>,
+	/// What a wasm contract wrote to its standard output and error, as captured by the runtime.
+	pub wasm_output: Vec<WasmOutput>,
+}
+
+// Traces without wasm output are encoded as before it was recorded, and traces
+// encoded before then still decode.
+impl Encodable for VMTrace {
+	fn rlp_append(&self, s: &mut RlpStream) {
+		s.begin_list(if self.wasm_output.is_empty() { 4 } else { 5 });
+		s.append(&self.parent_step);
+		s.append(&self.code);
+		s.append_list(&self.operations);
+		s.append_list(&self.subs);
+		if !self.wasm_output.is_empty() {
+			s.append_list(&self.wasm_output);
+		}
+	}
+}
+
+impl Decodable for VMTrace {
+	fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+		let wasm_output = match rlp.item_count()? {
+			4 => vec![],
+			5 => rlp.list_at(4)?,
+			_ => return Err(DecoderError::RlpIncorrectListLen),
+		};
+		let res = VMTrace {
+			parent_step: rlp.val_at(0)?,
+			code: rlp.val_at(1)?,
+			operations: rlp.list_at(2)?,
+			subs: rlp.list_at(3)?,
+			wasm_output,
+		};
+
+		Ok(res)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{VMTrace, WasmOutput};
+	use rlp::{decode, encode, RlpStream};
+
+	#[test]
+	fn vm_trace_without_wasm_output_decodes() {
+		let mut s = RlpStream::new_list(4);
+		s.append(&1usize);
+		s.append(&vec![0x60u8, 0x00]);
+		s.begin_list(0);
+		s.begin_list(0);
+		let old = s.out();
+
+		let trace: VMTrace = decode(&old).unwrap();
+		assert_eq!(trace.parent_step, 1);
+		assert!(trace.wasm_output.is_empty());
+		assert_eq!(&encode(&trace)[..], &old[..]);
+
+		let traced = VMTrace {
+			wasm_output: vec![WasmOutput {
+				fd: 1,
+				data: b"hello".to_vec(),
+			}],
+			..trace
+		};
+		assert_eq!(decode::<VMTrace>(&encode(&traced)).unwrap(), traced);
+	}
 }
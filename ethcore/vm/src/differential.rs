@@ -0,0 +1,768 @@
+//! Differential execution of a contract on two vms.
+//!
+//! The candidate vm runs first against a fork of the state which buffers every
+//! write, then the reference vm runs against the real state. The two runs should
+//! agree on their outcome, gas left, return data, storage writes and logs.
+//!
+//! The result of the reference vm is always the one returned, so consensus never
+//! depends on the candidate; every comparison is reported to `Ext::trace_comparison`.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::mem;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use ethereum_types::{Address, H256, U256};
+use types::log_entry::LogEntry;
+
+use crate::{
+	ActionParams, CallType, ContractCreateResult, CreateContractAddress, EnvInfo, Error, Ext,
+	GasLeft, MessageCallResult, OasisContract, Result, ReturnData, Schedule, Vm,
+};
+
+/// What a differential run disagreed on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DivergenceKind {
+	/// One run succeeded, reverted or failed differently from the other.
+	Outcome,
+	/// Different amounts of gas left.
+	GasLeft,
+	/// Different return or revert data.
+	ReturnData,
+	/// Different storage writes.
+	Storage,
+	/// Different logs.
+	Logs,
+}
+
+/// Disagreement between the reference and the candidate vm.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+	/// What the runs disagreed on.
+	pub kind: DivergenceKind,
+	/// What the reference vm produced.
+	pub reference: String,
+	/// What the candidate vm produced.
+	pub candidate: String,
+}
+
+impl fmt::Display for Divergence {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(
+			f,
+			"{:?} diverged: reference {}, candidate {}",
+			self.kind, self.reference, self.candidate
+		)
+	}
+}
+
+/// How the last run of a `DifferentialVm` compared.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Comparison {
+	/// Both vms agreed.
+	Agreed,
+	/// The vms disagreed.
+	Diverged(Divergence),
+	/// The runs could not be compared, as the candidate cannot replay the named
+	/// nested operation.
+	Skipped(&'static str),
+}
+
+/// Side effects of a run, as observed through `ForkedExt`.
+#[derive(Debug, Default, PartialEq)]
+struct Effects {
+	storage: BTreeMap<H256, H256>,
+	storage_bytes: BTreeMap<H256, Vec<u8>>,
+	kvstore: BTreeMap<Vec<u8>, Option<Vec<u8>>>,
+	logs: Vec<(Vec<H256>, Vec<u8>)>,
+	sstore_clears: Vec<u64>,
+	suicide: Option<Address>,
+}
+
+/// Externalities recording the side effects of a run.
+///
+/// When `forward` is false, writes are buffered and shadow the underlying state
+/// for later reads, which leaves the state untouched. Nested calls and creates
+/// cannot be forked this way, so they fail and mark the run as `unsupported`.
+struct ForkedExt<'a> {
+	ext: &'a mut dyn Ext,
+	forward: bool,
+	effects: Effects,
+	unsupported: Option<&'static str>,
+}
+
+impl<'a> ForkedExt<'a> {
+	fn new(ext: &'a mut dyn Ext, forward: bool) -> Self {
+		ForkedExt {
+			ext,
+			forward,
+			effects: Effects::default(),
+			unsupported: None,
+		}
+	}
+}
+
+impl<'a> Ext for ForkedExt<'a> {
+	fn storage_at(&self, key: &H256) -> Result<H256> {
+		match self.effects.storage.get(key) {
+			Some(value) if !self.forward => Ok(*value),
+			_ => self.ext.storage_at(key),
+		}
+	}
+
+	fn set_storage(&mut self, key: H256, value: H256) -> Result<()> {
+		if self.forward {
+			self.ext.set_storage(key, value)?;
+		}
+		self.effects.storage.insert(key, value);
+		Ok(())
+	}
+
+	fn storage_bytes_at(&self, key: &H256) -> Result<Vec<u8>> {
+		match self.effects.storage_bytes.get(key) {
+			Some(value) if !self.forward => Ok(value.clone()),
+			_ => self.ext.storage_bytes_at(key),
+		}
+	}
+
+	fn storage_bytes_len(&self, key: &H256) -> Result<u64> {
+		match self.effects.storage_bytes.get(key) {
+			Some(value) if !self.forward => Ok(value.len() as u64),
+			_ => self.ext.storage_bytes_len(key),
+		}
+	}
+
+	fn set_storage_bytes(&mut self, key: H256, value: Vec<u8>) -> Result<()> {
+		if self.forward {
+			self.ext.set_storage_bytes(key, value.clone())?;
+		}
+		self.effects.storage_bytes.insert(key, value);
+		Ok(())
+	}
+
+	fn storage_expiry(&self, address: &Address) -> Result<u64> {
+		self.ext.storage_expiry(address)
+	}
+
+	fn seconds_until_expiry(&self) -> Result<u64> {
+		self.ext.seconds_until_expiry()
+	}
+
+	fn exists(&self, address: &Address) -> Result<bool> {
+		self.ext.exists(address)
+	}
+
+	fn exists_and_not_null(&self, address: &Address) -> Result<bool> {
+		self.ext.exists_and_not_null(address)
+	}
+
+	fn origin_balance(&self) -> Result<U256> {
+		self.ext.origin_balance()
+	}
+
+	fn origin_nonce(&self) -> U256 {
+		self.ext.origin_nonce()
+	}
+
+	fn balance(&self, address: &Address) -> Result<U256> {
+		self.ext.balance(address)
+	}
+
+	fn blockhash(&mut self, number: &U256) -> H256 {
+		self.ext.blockhash(number)
+	}
+
+	fn create(
+		&mut self,
+		gas: &U256,
+		value: &U256,
+		code: &[u8],
+		address: CreateContractAddress,
+	) -> ContractCreateResult {
+		if self.forward {
+			return self.ext.create(gas, value, code, address);
+		}
+		self.unsupported = Some("create");
+		ContractCreateResult::Failed
+	}
+
+	fn call(
+		&mut self,
+		gas: &U256,
+		sender_address: &Address,
+		receive_address: &Address,
+		value: Option<U256>,
+		data: &[u8],
+		code_address: &Address,
+		output: &mut [u8],
+		call_type: CallType,
+	) -> MessageCallResult {
+		if self.forward {
+			return self.ext.call(
+				gas,
+				sender_address,
+				receive_address,
+				value,
+				data,
+				code_address,
+				output,
+				call_type,
+			);
+		}
+		self.unsupported = Some("call");
+		MessageCallResult::Failed
+	}
+
+	fn extcode(&self, address: &Address) -> Result<Arc<Bytes>> {
+		self.ext.extcode(address)
+	}
+
+	fn extcodesize(&self, address: &Address) -> Result<usize> {
+		self.ext.extcodesize(address)
+	}
+
+	fn extcodehash(&self, address: &Address) -> Result<Option<H256>> {
+		self.ext.extcodehash(address)
+	}
+
+	fn log(&mut self, topics: Vec<H256>, data: &[u8]) -> Result<()> {
+		if self.forward {
+			self.ext.log(topics.clone(), data)?;
+		}
+		self.effects.logs.push((topics, data.to_vec()));
+		Ok(())
+	}
+
+	fn logs(&self) -> &[LogEntry] {
+		// buffered logs lack an address, and nested calls never add logs to a fork
+		self.ext.logs()
+	}
+
+	fn ret(self, gas: &U256, _data: &ReturnData, _apply_state: bool) -> Result<U256> {
+		// The executive finalizes the call on the externalities it owns once the vm
+		// returns, and `ret` consumes the externalities so cannot reach them through a
+		// borrow. Vms never call it themselves, so a fork just hands back the gas.
+		Ok(*gas)
+	}
+
+	fn suicide(&mut self, refund_address: &Address) -> Result<()> {
+		if self.forward {
+			self.ext.suicide(refund_address)?;
+		}
+		self.effects.suicide = Some(*refund_address);
+		Ok(())
+	}
+
+	fn schedule(&self) -> &Schedule {
+		self.ext.schedule()
+	}
+
+	fn env_info(&self) -> &EnvInfo {
+		self.ext.env_info()
+	}
+
+	fn depth(&self) -> usize {
+		self.ext.depth()
+	}
+
+	fn inc_sstore_clears(&mut self, bytes_len: u64) -> Result<()> {
+		if self.forward {
+			self.ext.inc_sstore_clears(bytes_len)?;
+		}
+		self.effects.sstore_clears.push(bytes_len);
+		Ok(())
+	}
+
+	fn trace_wasm_output(&mut self, fd: u32, data: &[u8]) {
+		// only the run applied to the state is traced
+		if self.forward {
+			self.ext.trace_wasm_output(fd, data);
+		}
+	}
+
+	fn traces_wasm_output(&self) -> bool {
+		self.forward && self.ext.traces_wasm_output()
+	}
+
+	fn trace_comparison(&mut self, comparison: &Comparison) {
+		// comparisons of calls nested in the reference run
+		if self.forward {
+			self.ext.trace_comparison(comparison);
+		}
+	}
+
+	fn is_static(&self) -> bool {
+		self.ext.is_static()
+	}
+
+	fn is_create(&self) -> bool {
+		self.ext.is_create()
+	}
+
+	fn is_confidential_contract(&self, contract: &Address) -> Result<bool> {
+		self.ext.is_confidential_contract(contract)
+	}
+
+	fn oasis_contract(&self, contract: &Address) -> Result<Option<OasisContract>> {
+		self.ext.oasis_contract(contract)
+	}
+
+	fn as_kvstore(&self) -> &dyn blockchain_traits::KVStore {
+		self
+	}
+
+	fn as_kvstore_mut(&mut self) -> &mut dyn blockchain_traits::KVStoreMut {
+		self
+	}
+}
+
+impl<'a> blockchain_traits::KVStore for ForkedExt<'a> {
+	fn contains(&self, key: &[u8]) -> bool {
+		match self.effects.kvstore.get(key) {
+			Some(value) if !self.forward => value.is_some(),
+			_ => self.ext.as_kvstore().contains(key),
+		}
+	}
+
+	fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+		match self.effects.kvstore.get(key) {
+			Some(value) if !self.forward => value.clone(),
+			_ => self.ext.as_kvstore().get(key),
+		}
+	}
+}
+
+impl<'a> blockchain_traits::KVStoreMut for ForkedExt<'a> {
+	fn set(&mut self, key: &[u8], value: &[u8]) {
+		if self.forward {
+			self.ext.as_kvstore_mut().set(key, value);
+		}
+		self.effects
+			.kvstore
+			.insert(key.to_vec(), Some(value.to_vec()));
+	}
+
+	fn remove(&mut self, key: &[u8]) {
+		if self.forward {
+			self.ext.as_kvstore_mut().remove(key);
+		}
+		self.effects.kvstore.insert(key.to_vec(), None);
+	}
+}
+
+/// Runs every call on a reference and a candidate vm, tracing how their results
+/// compared.
+///
+/// Only the reference run is applied to the state, and only its result is returned.
+/// Calls which make nested calls or creates cannot be replayed on the candidate, so
+/// they are traced as `Comparison::Skipped`.
+pub struct DifferentialVm {
+	reference: Box<Vm>,
+	candidate: Box<Vm>,
+}
+
+impl DifferentialVm {
+	/// Creates a vm checking `candidate` against `reference`.
+	pub fn new(reference: Box<Vm>, candidate: Box<Vm>) -> Self {
+		DifferentialVm {
+			reference,
+			candidate,
+		}
+	}
+}
+
+impl Vm for DifferentialVm {
+	fn prepare(&mut self, params: &ActionParams, ext: &mut Ext) -> Result<()> {
+		self.reference.prepare(params, ext)?;
+		self.candidate.prepare(params, ext)
+	}
+
+	fn exec(&mut self, params: ActionParams, ext: &mut Ext) -> Result<GasLeft> {
+		let (candidate, candidate_effects, unsupported) = {
+			let mut fork = ForkedExt::new(ext, false);
+			let result = self.candidate.exec(params.clone(), &mut fork);
+			(result, fork.effects, fork.unsupported)
+		};
+		let (reference, reference_effects) = {
+			let mut fork = ForkedExt::new(ext, true);
+			let result = self.reference.exec(params, &mut fork);
+			(result, fork.effects)
+		};
+
+		let comparison = match unsupported {
+			Some(operation) => {
+				warn!(
+					target: "differential",
+					"Skipping comparison, candidate cannot replay a nested {}",
+					operation
+				);
+				Comparison::Skipped(operation)
+			}
+			None => match compare(
+				&reference,
+				&reference_effects,
+				&candidate,
+				&candidate_effects,
+			) {
+				Some(divergence) => {
+					warn!(target: "differential", "{}", divergence);
+					Comparison::Diverged(divergence)
+				}
+				None => Comparison::Agreed,
+			},
+		};
+		ext.trace_comparison(&comparison);
+
+		reference
+	}
+}
+
+/// Returns the first difference between the two runs, if any.
+fn compare(
+	reference: &Result<GasLeft>,
+	reference_effects: &Effects,
+	candidate: &Result<GasLeft>,
+	candidate_effects: &Effects,
+) -> Option<Divergence> {
+	let divergence = |kind, reference: String, candidate: String| {
+		Some(Divergence {
+			kind,
+			reference,
+			candidate,
+		})
+	};
+
+	let (reference_gas, reference_data, candidate_gas, candidate_data) =
+		match (reference, candidate) {
+			(Err(reference), Err(candidate)) if same_class(reference, candidate) => return None,
+			(Ok(reference), Ok(candidate)) if outcome(reference) == outcome(candidate) => {
+				let (reference_gas, reference_data) = gas_and_data(reference);
+				let (candidate_gas, candidate_data) = gas_and_data(candidate);
+				(reference_gas, reference_data, candidate_gas, candidate_data)
+			}
+			_ => {
+				return divergence(
+					DivergenceKind::Outcome,
+					describe(reference),
+					describe(candidate),
+				)
+			}
+		};
+
+	if reference_gas != candidate_gas {
+		return divergence(
+			DivergenceKind::GasLeft,
+			reference_gas.to_string(),
+			candidate_gas.to_string(),
+		);
+	}
+	if reference_data != candidate_data {
+		return divergence(
+			DivergenceKind::ReturnData,
+			format!("{:?}", reference_data),
+			format!("{:?}", candidate_data),
+		);
+	}
+
+	// storage writes of a reverted run are discarded anyway
+	let apply_state = match reference {
+		Ok(GasLeft::NeedsReturn { apply_state, .. }) => *apply_state,
+		_ => true,
+	};
+	if apply_state {
+		let reference_state = state_differences(reference_effects, candidate_effects);
+		if !reference_state.is_empty() {
+			return divergence(
+				DivergenceKind::Storage,
+				reference_state,
+				state_differences(candidate_effects, reference_effects),
+			);
+		}
+	}
+	if apply_state && reference_effects.logs != candidate_effects.logs {
+		return divergence(
+			DivergenceKind::Logs,
+			format!("{:?}", reference_effects.logs),
+			format!("{:?}", candidate_effects.logs),
+		);
+	}
+
+	None
+}
+
+/// Whether two errors are the same failure. The vms describe failures with different
+/// payloads, e.g. a trap with or without a backtrace, so only the variants are
+/// compared, and wasm runtime errors are all alike.
+fn same_class(reference: &Error, candidate: &Error) -> bool {
+	let is_wasm = |err: &Error| match *err {
+		Error::Wasm(_) | Error::WasmTrap(_) => true,
+		_ => false,
+	};
+	(is_wasm(reference) && is_wasm(candidate))
+		|| mem::discriminant(reference) == mem::discriminant(candidate)
+}
+
+/// Describes the state changes of `effects` which `other` does not share, or returns
+/// an empty string if there are none.
+fn state_differences(effects: &Effects, other: &Effects) -> String {
+	let mut differences = Vec::new();
+	if effects.storage != other.storage {
+		differences.push(format!("storage {:?}", effects.storage));
+	}
+	if effects.storage_bytes != other.storage_bytes {
+		differences.push(format!("storage bytes {:?}", effects.storage_bytes));
+	}
+	if effects.kvstore != other.kvstore {
+		differences.push(format!("kvstore {:?}", effects.kvstore));
+	}
+	if effects.sstore_clears != other.sstore_clears {
+		differences.push(format!("sstore clears {:?}", effects.sstore_clears));
+	}
+	if effects.suicide != other.suicide {
+		differences.push(format!("suicide {:?}", effects.suicide));
+	}
+	differences.join(", ")
+}
+
+/// Whether the run returned, reverted or stopped.
+fn outcome(gas_left: &GasLeft) -> Option<bool> {
+	match *gas_left {
+		GasLeft::Known(_) => None,
+		GasLeft::NeedsReturn { apply_state, .. } => Some(apply_state),
+	}
+}
+
+fn gas_and_data(gas_left: &GasLeft) -> (U256, &[u8]) {
+	match *gas_left {
+		GasLeft::Known(gas) => (gas, &[]),
+		GasLeft::NeedsReturn {
+			gas_left, ref data, ..
+		} => (gas_left, &data[..]),
+	}
+}
+
+fn describe(result: &Result<GasLeft>) -> String {
+	match *result {
+		Ok(GasLeft::Known(_)) => "stop".to_owned(),
+		Ok(GasLeft::NeedsReturn {
+			apply_state: true, ..
+		}) => "return".to_owned(),
+		Ok(GasLeft::NeedsReturn {
+			apply_state: false, ..
+		}) => "revert".to_owned(),
+		Err(ref err) => format!("error ({})", err),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::tests::FakeExt;
+	use crate::{WasmFrame, WasmTrap};
+
+	/// A vm writing one storage slot and returning a fixed amount of gas.
+	struct StoreVm {
+		value: H256,
+		gas_left: u64,
+		call: bool,
+	}
+
+	impl Vm for StoreVm {
+		fn prepare(&mut self, _params: &ActionParams, _ext: &mut Ext) -> Result<()> {
+			Ok(())
+		}
+
+		fn exec(&mut self, _params: ActionParams, ext: &mut Ext) -> Result<GasLeft> {
+			ext.set_storage(H256::from(1), self.value)?;
+			if self.call {
+				ext.call(
+					&U256::zero(),
+					&Address::zero(),
+					&Address::zero(),
+					None,
+					&[],
+					&Address::zero(),
+					&mut [],
+					CallType::Call,
+				);
+			}
+			Ok(GasLeft::NeedsReturn {
+				gas_left: self.gas_left.into(),
+				data: ReturnData::new(vec![1, 2, 3], 0, 3),
+				apply_state: true,
+			})
+		}
+	}
+
+	/// A vm failing with the given error.
+	struct FailVm(Error);
+
+	impl Vm for FailVm {
+		fn prepare(&mut self, _params: &ActionParams, _ext: &mut Ext) -> Result<()> {
+			Ok(())
+		}
+
+		fn exec(&mut self, _params: ActionParams, _ext: &mut Ext) -> Result<GasLeft> {
+			Err(self.0.clone())
+		}
+	}
+
+	/// A vm writing `key` to the kvstore.
+	struct KvStoreVm(&'static [u8]);
+
+	impl Vm for KvStoreVm {
+		fn prepare(&mut self, _params: &ActionParams, _ext: &mut Ext) -> Result<()> {
+			Ok(())
+		}
+
+		fn exec(&mut self, _params: ActionParams, ext: &mut Ext) -> Result<GasLeft> {
+			ext.as_kvstore_mut().set(self.0, b"value");
+			Ok(GasLeft::Known(100.into()))
+		}
+	}
+
+	fn store_vm(value: u64, gas_left: u64) -> Box<Vm> {
+		Box::new(StoreVm {
+			value: H256::from(value),
+			gas_left,
+			call: false,
+		})
+	}
+
+	fn exec(
+		reference: Box<Vm>,
+		candidate: Box<Vm>,
+		ext: &mut FakeExt,
+	) -> (Result<GasLeft>, Comparison) {
+		let mut vm = DifferentialVm::new(reference, candidate);
+		let result = vm.exec(ActionParams::default(), ext);
+		assert_eq!(ext.comparisons.len(), 1);
+		(result, ext.comparisons.pop().expect("a call was executed"))
+	}
+
+	fn divergence(comparison: Comparison) -> Divergence {
+		match comparison {
+			Comparison::Diverged(divergence) => divergence,
+			comparison => panic!("expected a divergence, got {:?}", comparison),
+		}
+	}
+
+	#[test]
+	fn agreeing_runs_apply_reference_once() {
+		let mut ext = FakeExt::new();
+		let (result, comparison) = exec(store_vm(7, 100), store_vm(7, 100), &mut ext);
+
+		match result {
+			Ok(GasLeft::NeedsReturn { gas_left, data, .. }) => {
+				assert_eq!(gas_left, U256::from(100));
+				assert_eq!(&data[..], &[1, 2, 3]);
+			}
+			result => panic!("unexpected result {:?}", result),
+		}
+		assert_eq!(comparison, Comparison::Agreed);
+		assert_eq!(ext.storage_at(&H256::from(1)).unwrap(), H256::from(7));
+	}
+
+	#[test]
+	fn candidate_runs_on_a_fork() {
+		let mut ext = FakeExt::new();
+		let _ = exec(store_vm(7, 100), store_vm(8, 100), &mut ext);
+
+		assert_eq!(ext.storage_at(&H256::from(1)).unwrap(), H256::from(7));
+	}
+
+	#[test]
+	fn divergence_is_reported() {
+		let mut ext = FakeExt::new();
+		let (_, comparison) = exec(store_vm(7, 100), store_vm(7, 99), &mut ext);
+		let divergence = divergence(comparison);
+		assert_eq!(divergence.kind, DivergenceKind::GasLeft);
+		assert_eq!(divergence.reference, "100");
+		assert_eq!(divergence.candidate, "99");
+
+		let mut ext = FakeExt::new();
+		let (_, comparison) = exec(store_vm(7, 100), store_vm(8, 100), &mut ext);
+		assert_eq!(divergence(comparison).kind, DivergenceKind::Storage);
+	}
+
+	#[test]
+	fn divergence_returns_the_reference_result() {
+		let mut ext = FakeExt::new();
+		let (result, _) = exec(
+			store_vm(7, 100),
+			Box::new(FailVm(Error::OutOfGas)),
+			&mut ext,
+		);
+		match result {
+			Ok(GasLeft::NeedsReturn { gas_left, .. }) => assert_eq!(gas_left, U256::from(100)),
+			result => panic!("unexpected result {:?}", result),
+		}
+		assert_eq!(ext.storage_at(&H256::from(1)).unwrap(), H256::from(7));
+
+		let mut ext = FakeExt::new();
+		let (result, _) = exec(
+			Box::new(FailVm(Error::OutOfGas)),
+			store_vm(7, 100),
+			&mut ext,
+		);
+		assert_eq!(result.unwrap_err(), Error::OutOfGas);
+	}
+
+	#[test]
+	fn errors_are_compared_by_class() {
+		let trap = Error::WasmTrap(WasmTrap {
+			kind: "Unreachable".to_owned(),
+			backtrace: vec![WasmFrame {
+				index: 1,
+				name: Some("main".to_owned()),
+			}],
+		});
+		let mut ext = FakeExt::new();
+		let (result, comparison) = exec(
+			Box::new(FailVm(trap)),
+			Box::new(FailVm(Error::Wasm("Wasm contract trap".to_owned()))),
+			&mut ext,
+		);
+		assert!(match result {
+			Err(Error::WasmTrap(_)) => true,
+			_ => false,
+		});
+		assert_eq!(comparison, Comparison::Agreed);
+
+		let mut ext = FakeExt::new();
+		let (_, comparison) = exec(
+			Box::new(FailVm(Error::OutOfGas)),
+			Box::new(FailVm(Error::Wasm("Wasm contract trap".to_owned()))),
+			&mut ext,
+		);
+		assert_eq!(divergence(comparison).kind, DivergenceKind::Outcome);
+	}
+
+	#[test]
+	fn state_divergence_names_what_differs() {
+		let mut ext = FakeExt::new();
+		let (_, comparison) = exec(
+			Box::new(KvStoreVm(b"reference")),
+			Box::new(KvStoreVm(b"candidate")),
+			&mut ext,
+		);
+		let divergence = divergence(comparison);
+		assert_eq!(divergence.kind, DivergenceKind::Storage);
+		assert!(divergence.reference.starts_with("kvstore"));
+		assert!(divergence.candidate.starts_with("kvstore"));
+	}
+
+	#[test]
+	fn nested_calls_skip_comparison() {
+		let mut ext = FakeExt::new();
+		let candidate = Box::new(StoreVm {
+			value: H256::from(8),
+			gas_left: 100,
+			call: true,
+		});
+		let (result, comparison) = exec(store_vm(7, 100), candidate, &mut ext);
+		assert!(result.is_ok());
+		assert_eq!(comparison, Comparison::Skipped("call"));
+	}
+}
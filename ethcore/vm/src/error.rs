@@ -65,6 +65,8 @@ pub enum Error {
 	Internal(String),
 	/// Wasm runtime error
 	Wasm(String),
+	/// Wasm contract trapped
+	WasmTrap(WasmTrap),
 	/// Out of bounds access in RETURNDATACOPY.
 	OutOfBounds,
 	/// Execution has been reverted with REVERT.
@@ -75,6 +77,48 @@ pub enum Error {
 	Confidential(String),
 }
 
+/// A function on the call stack of a trapped wasm contract.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WasmFrame {
+	/// Index of the function in the contract module.
+	pub index: u32,
+	/// Name of the function, if the module has a name section.
+	pub name: Option<String>,
+}
+
+/// Diagnostics of a trapped wasm contract.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WasmTrap {
+	/// Why the contract trapped, e.g. `Unreachable` or `MemoryAccessOutOfBounds`.
+	pub kind: String,
+	/// Functions on the call stack when the contract trapped, innermost first.
+	pub backtrace: Vec<WasmFrame>,
+}
+
+impl fmt::Display for WasmFrame {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self.name {
+			Some(ref name) => write!(f, "{} (#{})", name, self.index),
+			None => write!(f, "#{}", self.index),
+		}
+	}
+}
+
+impl fmt::Display for WasmTrap {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self.kind)?;
+		for (i, frame) in self.backtrace.iter().enumerate() {
+			write!(
+				f,
+				"{} {}",
+				if i == 0 { " in" } else { ", called from" },
+				frame
+			)?;
+		}
+		Ok(())
+	}
+}
+
 impl From<Box<trie::TrieError>> for Error {
 	fn from(err: Box<trie::TrieError>) -> Self {
 		Error::Internal(format!("Internal error: {}", err))
@@ -110,6 +154,7 @@ impl fmt::Display for Error {
 			Internal(ref msg) => write!(f, "Internal error: {}", msg),
 			MutableCallInStaticContext => write!(f, "Mutable call in static context"),
 			Wasm(ref msg) => write!(f, "Internal error: {}", msg),
+			WasmTrap(ref trap) => write!(f, "Wasm contract trap: {}", trap),
 			OutOfBounds => write!(f, "Out of bounds"),
 			Reverted => write!(f, "Reverted"),
 			ContractExpired => write!(f, "Contract Expired"),
@@ -18,12 +18,15 @@
 
 use bytes::Bytes;
 use call_type::CallType;
+use differential::Comparison;
 use env_info::EnvInfo;
 use error::Result;
 use ethereum_types::{Address, H256, U256};
+use oasis_contract::OasisContract;
 use return_data::ReturnData;
 use schedule::Schedule;
 use std::sync::Arc;
+use types::log_entry::LogEntry;
 
 /// Result of externalities create function.
 pub enum ContractCreateResult {
@@ -141,9 +144,16 @@ pub trait Ext: blockchain_traits::KVStore + blockchain_traits::KVStoreMut {
 	/// Returns code size at given address
 	fn extcodesize(&self, address: &Address) -> Result<usize>;
 
+	/// Returns code hash at given address.
+	/// Returns None if the account does not exist or is null.
+	fn extcodehash(&self, address: &Address) -> Result<Option<H256>>;
+
 	/// Creates log entry with given topics and data
 	fn log(&mut self, topics: Vec<H256>, data: &[u8]) -> Result<()>;
 
+	/// Returns the log entries created so far by this call and the calls it has made.
+	fn logs(&self) -> &[LogEntry];
+
 	/// Should be called when transaction calls `RETURN` opcode.
 	/// Returns gas_left if cost of returning the data is not too high.
 	fn ret(self, gas: &U256, data: &ReturnData, apply_state: bool) -> Result<U256>;
@@ -185,6 +195,19 @@ pub trait Ext: blockchain_traits::KVStore + blockchain_traits::KVStoreMut {
 	) {
 	}
 
+	/// Trace bytes a wasm contract wrote to its standard output (`fd` 1) or error (`fd` 2).
+	///
+	/// Only the wasmi interpreter reports them: SSVM writes them to the stdio of the node.
+	fn trace_wasm_output(&mut self, _fd: u32, _data: &[u8]) {}
+
+	/// Whether `trace_wasm_output` records anything, so that the output need not be captured otherwise.
+	fn traces_wasm_output(&self) -> bool {
+		false
+	}
+
+	/// Trace how a differential run of the current call compared on both vms.
+	fn trace_comparison(&mut self, _comparison: &Comparison) {}
+
 	/// Check if running in static context.
 	fn is_static(&self) -> bool;
 
@@ -194,6 +217,9 @@ pub trait Ext: blockchain_traits::KVStore + blockchain_traits::KVStoreMut {
 	/// Returns true if the given contract is confidential.
 	fn is_confidential_contract(&self, contract: &Address) -> Result<bool>;
 
+	/// Returns the header of the given contract, or None if it has no header.
+	fn oasis_contract(&self, contract: &Address) -> Result<Option<OasisContract>>;
+
 	fn as_kvstore(&self) -> &dyn blockchain_traits::KVStore;
 	fn as_kvstore_mut(&mut self) -> &mut dyn blockchain_traits::KVStoreMut;
 }
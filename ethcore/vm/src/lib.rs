@@ -35,6 +35,7 @@ extern crate evmc_client;
 
 mod action_params;
 mod call_type;
+mod differential;
 mod env_info;
 mod error;
 mod ext;
@@ -48,13 +49,17 @@ pub mod tests;
 
 pub use action_params::{ActionParams, ActionValue, ParamsType};
 pub use call_type::CallType;
+pub use differential::{Comparison, DifferentialVm, Divergence, DivergenceKind};
 pub use env_info::{EnvInfo, LastHashes};
-pub use error::{Error, Result};
+pub use error::{Error, Result, WasmFrame, WasmTrap};
 pub use ext::{ContractCreateResult, CreateContractAddress, Ext, MessageCallResult};
 pub use oasis_contract::{OasisContract, OasisContractHeader, OASIS_HEADER_PREFIX};
 pub use oasis_vm::{AuthenticatedPayload, ConfidentialCtx, OasisVm};
 pub use return_data::{GasLeft, ReturnData};
-pub use schedule::{CleanDustMode, Schedule, WasmCosts};
+pub use schedule::{
+	CleanDustMode, EvmcRevision, EvmcSchedule, Schedule, WasiEnviron, WasmBackend, WasmCosts,
+	WasmPolicy,
+};
 
 /// Virtual Machine interface
 pub trait Vm {
@@ -67,6 +72,7 @@ pub trait Vm {
 	/// - Performs the code compilation and parses arguments, which speeds up exec() when it is later called.
 	/// - This function must be called before exec() and will panic if not coop
 	/// For wasmi-backed runtimes:
-	/// - This function is a no-op.
+	/// - Compiles the module, or takes it from the module cache, for the following exec().
+	///   Invalid code is not reported here but by exec(), so that it fails the call.
 	fn prepare(&mut self, params: &ActionParams, ext: &mut Ext) -> Result<()>;
 }
@@ -15,6 +15,11 @@ pub struct OasisContract {
 	pub confidential: bool,
 	/// Expiration timestamp for contract's storage (None if unspecified).
 	pub expiry: Option<u64>,
+	/// Flag indicating whether a confidential contract may call non-confidential contracts.
+	pub allow_public_callees: bool,
+	/// Flag indicating whether a confidential contract may be called by non-confidential
+	/// contracts. Such calls carry a session request, whose response is encrypted.
+	pub allow_public_callers: bool,
 	/// Header, to be prepended to stored bytecode.
 	pub header: Vec<u8>,
 	/// Copy of the contract code with header removed.
@@ -25,6 +30,8 @@ pub enum OasisContractHeader {
 	V1 {
 		confidential: Option<bool>,
 		expiry: Option<u64>,
+		allow_public_callees: Option<bool>,
+		allow_public_callers: Option<bool>,
 	},
 }
 
@@ -34,14 +41,23 @@ impl OasisContractHeader {
 			OasisContractHeader::V1 {
 				confidential,
 				expiry,
-			} => OasisContract::make_header_unsafe(
-				1usize,
-				json!({
+				allow_public_callees,
+				allow_public_callers,
+			} => {
+				let mut header = json!({
 					"confidential": confidential,
 					"expiry": expiry
-				})
-				.to_string(),
-			),
+				});
+				// the cross-domain policy is only written if given, so that
+				// headers without one keep their encoding
+				if let Some(allow) = allow_public_callees {
+					header["allow_public_callees"] = json!(allow);
+				}
+				if let Some(allow) = allow_public_callers {
+					header["allow_public_callers"] = json!(allow);
+				}
+				OasisContract::make_header_unsafe(1usize, header.to_string())
+			}
 		}
 	}
 }
@@ -51,6 +67,8 @@ impl OasisContractHeader {
 struct Header {
 	confidential: Option<bool>,
 	expiry: Option<u64>,
+	allow_public_callees: Option<bool>,
+	allow_public_callers: Option<bool>,
 }
 
 impl OasisContract {
@@ -101,6 +119,8 @@ impl OasisContract {
 			header_version,
 			confidential: h.confidential.unwrap_or(false),
 			expiry: h.expiry,
+			allow_public_callees: h.allow_public_callees.unwrap_or(false),
+			allow_public_callers: h.allow_public_callers.unwrap_or(false),
 			header: raw_header,
 			code: Arc::new(code),
 		}))
@@ -200,6 +220,48 @@ mod tests {
 		assert_eq!(contract.expiry, Some(1577836800));
 	}
 
+	#[test]
+	fn test_cross_domain_policy() {
+		let data = make_data_payload(
+			1,
+			json!({
+				"confidential": true,
+				"allow_public_callees": true,
+			})
+			.to_string(),
+		);
+
+		let contract = OasisContract::from_code(&data).unwrap().unwrap();
+
+		assert_eq!(contract.allow_public_callees, true);
+		assert_eq!(contract.allow_public_callers, false);
+
+		let header = OasisContractHeader::V1 {
+			confidential: Some(true),
+			expiry: None,
+			allow_public_callees: None,
+			allow_public_callers: Some(true),
+		}
+		.to_vec();
+		let contract = OasisContract::from_code(&header).unwrap().unwrap();
+
+		assert_eq!(contract.allow_public_callees, false);
+		assert_eq!(contract.allow_public_callers, true);
+
+		// headers without a policy are encoded as before
+		let header = OasisContractHeader::V1 {
+			confidential: Some(true),
+			expiry: Some(1),
+			allow_public_callees: None,
+			allow_public_callers: None,
+		}
+		.to_vec();
+		assert_eq!(
+			&header[OASIS_HEADER_PREFIX.len() + 4..],
+			&br#"{"confidential":true,"expiry":1}"#[..]
+		);
+	}
+
 	#[test]
 	fn test_invalid_version() {
 		let data = make_data_payload(
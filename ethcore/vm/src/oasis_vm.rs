@@ -126,7 +126,8 @@ impl ConfidentialVm {
 			}
 		};
 
-		if result.is_err() {
+		// Cross contract transactions restore the context of their caller themselves.
+		if result.is_err() && ext.depth() == 0 {
 			trace!("ConfidentialVm::exec_confidential(..) error={:?}", result);
 			self.ctx.borrow_mut().deactivate();
 		}
@@ -216,7 +217,8 @@ impl ConfidentialVm {
 
 	/// Executes a cross contract transaction.
 	fn cross_contract_tx(&mut self, params: ActionParams, ext: &mut Ext) -> Result<GasLeft> {
-		assert!(self.ctx.borrow().activated() && ext.depth() > 0);
+		// the context is not activated yet if a public contract calls a confidential one
+		assert!(ext.depth() > 0);
 
 		if params.call_type == CallType::None {
 			self.cross_contract_create(params, ext)
@@ -225,10 +227,17 @@ impl ConfidentialVm {
 		}
 	}
 
-	/// Creates a confidential contract from within a confidential contract.
+	/// Creates a contract from within a confidential transaction. The contract is
+	/// confidential if its creator is, see `Externalities::create`, and otherwise
+	/// its init code runs without encryption.
 	fn cross_contract_create(&mut self, params: ActionParams, ext: &mut Ext) -> Result<GasLeft> {
+		let address = match params.oasis_contract {
+			Some(ref contract) if contract.confidential => Some(params.address),
+			_ => None,
+		};
+
 		// Swap the confidential context to the new contract we're creating.
-		let old_contract = self.ctx.borrow_mut().activate(Some(params.address))?;
+		let old_contract = self.ctx.borrow_mut().activate(address)?;
 
 		// Execute the init code with the underlying vm.
 		let result = self.vm.exec(params, ext);
@@ -251,11 +260,15 @@ impl ConfidentialVm {
 	/// since we're already in the enclave, theres no need to encrypt the call from
 	/// conf_contract_A -> conf_contract_B.
 	///
-	/// However, we could do such additional encryption/decryption *here*. This would be
-	/// useful if we wanted to exit out of the enclave with encrypted data and enter into
-	/// a new enclave in the future.
+	/// Calls across the confidential boundary must be allowed by the header of the
+	/// confidential contract, see `exec_across`.
 	fn cross_contract_call(&mut self, params: ActionParams, ext: &mut Ext) -> Result<GasLeft> {
 		self.check_cross_contract_call(&params, ext)?;
+
+		// A public contract calling a confidential one may activate the context, in
+		// which case the context is shut down again once the call returns.
+		let activated = self.ctx.borrow().activated();
+
 		let address = {
 			if ext.is_confidential_contract(&params.address)? {
 				Some(params.address)
@@ -277,15 +290,88 @@ impl ConfidentialVm {
 		// the address whose storage context we're executing in.
 		let old_contract = self.ctx.borrow_mut().activate(address)?;
 		// Run the contract execution.
-		let result = self.vm.exec(params, ext);
+		let result = self.exec_across(old_contract, address, params, ext);
 		// Swap back the confidential ctx to use the keys prior to the cross contract call.
-		let _old_contract = self.ctx.borrow_mut().activate(old_contract)?;
+		if activated {
+			self.ctx.borrow_mut().activate(old_contract)?;
+		} else {
+			self.ctx.borrow_mut().suspend();
+		}
 
 		result
 	}
 
+	/// Executes a call from the contract whose keys were in use, `caller`, to the
+	/// contract whose keys are now in use, `callee`, where either is None if public.
+	///
+	/// A confidential contract may call public contracts if its header has
+	/// `allow_public_callees`: the call data is then handed to the callee in plaintext,
+	/// since a public contract could not read it otherwise, and so is the result,
+	/// which is public already. Sealing that result for the session of the caller is
+	/// a non-goal: it is returned to the code of the caller, within the confidential
+	/// context, rather than to the session peer, which only ever sees what the caller
+	/// itself returns, encrypted by `encrypt_vm_result`.
+	///
+	/// A confidential contract may be called by public contracts if its header has
+	/// `allow_public_callers`. Such calls are requests of a session, just like
+	/// confidential transactions: the call data is decrypted at the boundary, and the
+	/// result is encrypted for the session peer, so that the public caller only ever
+	/// relays ciphertext.
+	fn exec_across(
+		&mut self,
+		caller: Option<Address>,
+		callee: Option<Address>,
+		mut params: ActionParams,
+		ext: &mut Ext,
+	) -> Result<GasLeft> {
+		match (caller, callee) {
+			(Some(caller), None) => {
+				if !ext
+					.oasis_contract(&caller)?
+					.map_or(false, |caller| caller.allow_public_callees)
+				{
+					return Err(Error::Confidential(
+						"cannot call a non-confidential contract from confidential".to_string(),
+					));
+				}
+				// the result is handed to the caller in plaintext, see above
+				self.vm.exec(params, ext)
+			}
+			(None, Some(callee)) => {
+				if !ext
+					.oasis_contract(&callee)?
+					.map_or(false, |callee| callee.allow_public_callers)
+				{
+					return Err(Error::Confidential(
+						"cannot call a confidential contract from non-confidential".to_string(),
+					));
+				}
+
+				// Replace the call data with the unencrypted version.
+				let unencrypted_data = self
+					.ctx
+					.borrow_mut()
+					.enter_session(params.data.take().unwrap_or_default())?;
+				params.data = Some(unencrypted_data.decrypted_data);
+				params.aad = Some(unencrypted_data.additional_data);
+
+				// Execute the code and encrypt the result.
+				let result = match self.vm.exec(params, ext) {
+					Ok(result) => self.encrypt_vm_result(result),
+					Err(err) => Err(err),
+				};
+
+				// Restore the session of the caller, if any.
+				self.ctx.borrow_mut().leave_session();
+
+				result
+			}
+			_ => self.vm.exec(params, ext),
+		}
+	}
+
 	/// Checks the cross contract call preconditions, returning an error if any invariant is
-	/// violated.
+	/// violated. Creates never get here, see `cross_contract_create`.
 	fn check_cross_contract_call(&self, params: &ActionParams, ext: &mut Ext) -> Result<()> {
 		trace!(
 			"ConfidentialVm::check_cross_contract_call(..), activated={:?}, ext.is_confidential({:?})={:?}",
@@ -294,31 +380,11 @@ impl ConfidentialVm {
 			ext.is_confidential_contract(&params.address)?
 		);
 
-		if params.call_type == CallType::None {
-			return Err(Error::Confidential(
-				"Cannot create a contract after executing a confidential contract".to_string(),
-			));
-		}
 		if params.data.is_none() {
 			return Err(Error::Confidential(
 				"Cannot execute a confidential call without a data field".to_string(),
 			));
 		}
-
-		// To enable cross contract calls across confidential domains, remove the following checks.
-
-		// Assert confidential contracts can only call confidential contracts.
-		if self.ctx.borrow().activated() && !ext.is_confidential_contract(&params.address)? {
-			return Err(Error::Confidential(
-				"cannot call a non-confidential contract from confidential".to_string(),
-			));
-		}
-		// Assert non-confidnetial contracts can only call non-confidential contracts.
-		if !self.ctx.borrow().activated() && ext.is_confidential_contract(&params.address)? {
-			return Err(Error::Confidential(
-				"cannot call a confidential contract from non-confidential".to_string(),
-			));
-		}
 		Ok(())
 	}
 }
@@ -351,6 +417,14 @@ pub trait ConfidentialCtx {
 	/// Deactivates the context. If called, subsequent calls to `encrypt_*` should fail.
 	fn deactivate(&mut self);
 
+	/// Deactivates the context when a call from a public contract to a confidential
+	/// one returns, in the middle of a transaction. Unlike `deactivate`, which ends
+	/// the transaction, keeps what must last for the whole transaction, such as the
+	/// number of logs sealed so far.
+	fn suspend(&mut self) {
+		self.deactivate()
+	}
+
 	/// Returns true if a confidential contract has previously been called.
 	fn activated(&self) -> bool;
 
@@ -369,6 +443,22 @@ pub trait ConfidentialCtx {
 	/// Assumes `encrypted_payload` is of the form	NONCE || PEER_PUBLIC_SESSION_KEY || CIPHER.
 	fn decrypt_session(&mut self, encrypted_payload: Vec<u8>) -> Result<AuthenticatedPayload>;
 
+	/// Opens a session for a public contract calling a confidential one, decrypting
+	/// `encrypted_payload` as `decrypt_session` does, and keeps the session already open,
+	/// if any, for `leave_session` to restore.
+	///
+	/// Contexts that cannot nest sessions refuse such calls.
+	fn enter_session(&mut self, _encrypted_payload: Vec<u8>) -> Result<AuthenticatedPayload> {
+		Err(Error::Confidential(
+			"calls from public contracts are not supported by this confidential context"
+				.to_string(),
+		))
+	}
+
+	/// Closes the session opened by the last successful `enter_session`, restoring the
+	/// session which was open before it.
+	fn leave_session(&mut self) {}
+
 	/// Returns the public key of the peer connecting through an encrypted session to the runtime.
 	/// Returns None if no such key exists, e.g., if a confidential contract is being created.
 	fn peer(&self) -> Option<Vec<u8>>;
@@ -385,9 +475,303 @@ pub trait ConfidentialCtx {
 	/// Analog to `encrypt_storage_value` for decrypting storage values.
 	/// The `storage_key` should be encrypted in a c10l context.
 	fn decrypt_storage_value(&self, storage_key: Vec<u8>, data: Vec<u8>) -> Result<Vec<u8>>;
+
+	/// Encrypts a log emitted under the context, for the session `peer()` if there is one,
+	/// and otherwise for the active contract.
+	///
+	/// The data is encrypted, and each topic is replaced by a blinded topic which only
+	/// holders of the same key can compute, so that they can still filter logs with blooms.
+	///
+	/// Contexts that cannot seal logs refuse them, so that confidential contracts never
+	/// emit logs in the clear.
+	fn encrypt_log(&mut self, _topics: Vec<H256>, _data: Vec<u8>) -> Result<(Vec<H256>, Vec<u8>)> {
+		Err(Error::Confidential(
+			"logs are not supported by this confidential context".to_string(),
+		))
+	}
 }
 
 pub struct AuthenticatedPayload {
 	pub decrypted_data: Vec<u8>,
 	pub additional_data: Vec<u8>,
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::tests::FakeExt;
+	use crate::OasisContractHeader;
+	use std::{cell::Cell, sync::Arc};
+
+	/// A context recording the contract whose keys are in use and the sessions entered,
+	/// passing data through and prefixing session responses with `sealed:`.
+	struct KeysCtx {
+		contract: Rc<Cell<Option<Address>>>,
+		activated: Rc<Cell<bool>>,
+		sessions: Rc<Cell<usize>>,
+	}
+
+	impl ConfidentialCtx for KeysCtx {
+		fn activate(&mut self, contract: Option<Address>) -> Result<Option<Address>> {
+			self.activated.set(true);
+			Ok(self.contract.replace(contract))
+		}
+
+		fn deactivate(&mut self) {
+			self.activated.set(false);
+			self.contract.set(None);
+			self.sessions.set(0);
+		}
+
+		fn activated(&self) -> bool {
+			self.activated.get()
+		}
+
+		fn is_encrypting(&self) -> bool {
+			self.contract.get().is_some()
+		}
+
+		fn encrypt_session(&mut self, data: Vec<u8>) -> Result<Vec<u8>> {
+			Ok([&b"sealed:"[..], &data[..]].concat())
+		}
+
+		fn decrypt_session(&mut self, encrypted_payload: Vec<u8>) -> Result<AuthenticatedPayload> {
+			Ok(AuthenticatedPayload {
+				decrypted_data: encrypted_payload,
+				additional_data: Vec::new(),
+			})
+		}
+
+		fn enter_session(&mut self, encrypted_payload: Vec<u8>) -> Result<AuthenticatedPayload> {
+			self.sessions.set(self.sessions.get() + 1);
+			self.decrypt_session(encrypted_payload)
+		}
+
+		fn leave_session(&mut self) {
+			self.sessions.set(self.sessions.get() - 1);
+		}
+
+		fn peer(&self) -> Option<Vec<u8>> {
+			None
+		}
+
+		fn encrypt_storage_value(
+			&mut self,
+			_storage_key: Vec<u8>,
+			data: Vec<u8>,
+		) -> Result<Vec<u8>> {
+			Ok(data)
+		}
+
+		fn encrypt_storage_key(&self, data: Vec<u8>) -> Result<Vec<u8>> {
+			Ok(data)
+		}
+
+		fn decrypt_storage_value(&self, _storage_key: Vec<u8>, data: Vec<u8>) -> Result<Vec<u8>> {
+			Ok(data)
+		}
+
+		fn encrypt_log(
+			&mut self,
+			topics: Vec<H256>,
+			data: Vec<u8>,
+		) -> Result<(Vec<H256>, Vec<u8>)> {
+			Ok((topics, data))
+		}
+	}
+
+	/// A vm recording the contract whose keys are in use when it runs, and the
+	/// number of sessions entered, returning its call data.
+	struct KeysVm {
+		contract: Rc<Cell<Option<Address>>>,
+		sessions: Rc<Cell<usize>>,
+		seen: Rc<Cell<Option<(Option<Address>, usize)>>>,
+	}
+
+	impl Vm for KeysVm {
+		fn prepare(&mut self, _params: &ActionParams, _ext: &mut Ext) -> Result<()> {
+			Ok(())
+		}
+
+		fn exec(&mut self, params: ActionParams, _ext: &mut Ext) -> Result<GasLeft> {
+			self.seen
+				.set(Some((self.contract.get(), self.sessions.get())));
+			let data = params.data.unwrap_or_default();
+			let size = data.len();
+			Ok(GasLeft::NeedsReturn {
+				gas_left: 0.into(),
+				data: ReturnData::new(data, 0, size),
+				apply_state: true,
+			})
+		}
+	}
+
+	struct Setup {
+		vm: OasisVm,
+		contract: Rc<Cell<Option<Address>>>,
+		activated: Rc<Cell<bool>>,
+		sessions: Rc<Cell<usize>>,
+		seen: Rc<Cell<Option<(Option<Address>, usize)>>>,
+	}
+
+	/// Sets up a vm whose context is using the keys of `active`, if any, and is
+	/// activated if `activated`.
+	fn with_keys(active: Option<Address>, activated: bool) -> Setup {
+		let contract = Rc::new(Cell::new(active));
+		let activated = Rc::new(Cell::new(activated));
+		let sessions = Rc::new(Cell::new(0));
+		let seen = Rc::new(Cell::new(None));
+		let ctx: Box<ConfidentialCtx> = Box::new(KeysCtx {
+			contract: contract.clone(),
+			activated: activated.clone(),
+			sessions: sessions.clone(),
+		});
+		let vm = Box::new(KeysVm {
+			contract: contract.clone(),
+			sessions: sessions.clone(),
+			seen: seen.clone(),
+		});
+		Setup {
+			vm: OasisVm::new(Some(Rc::new(RefCell::new(ctx))), vm),
+			contract,
+			activated,
+			sessions,
+			seen,
+		}
+	}
+
+	fn confidential_code(allow_public_callees: bool, allow_public_callers: bool) -> Arc<Vec<u8>> {
+		let mut code = OasisContractHeader::V1 {
+			confidential: Some(true),
+			expiry: None,
+			allow_public_callees: Some(allow_public_callees),
+			allow_public_callers: Some(allow_public_callers),
+		}
+		.to_vec();
+		code.extend_from_slice(b"contract code");
+		Arc::new(code)
+	}
+
+	fn call(caller: Address, callee: Address) -> ActionParams {
+		let mut params = ActionParams::default();
+		params.sender = caller;
+		params.address = callee;
+		params.code_address = callee;
+		params.call_type = CallType::Call;
+		params.data = Some(b"data".to_vec());
+		params
+	}
+
+	fn is_rejected(result: Result<GasLeft>) -> bool {
+		match result {
+			Err(Error::Confidential(_)) => true,
+			_ => false,
+		}
+	}
+
+	fn returned(result: Result<GasLeft>) -> Vec<u8> {
+		match result {
+			Ok(GasLeft::NeedsReturn { data, .. }) => data.to_vec(),
+			_ => panic!("expected a return, got {:?}", result.map(|_| ())),
+		}
+	}
+
+	#[test]
+	fn public_caller_needs_allow_public_callers() {
+		let (caller, callee) = (Address::from(1), Address::from(2));
+		let mut ext = FakeExt::new();
+		ext.depth = 1;
+
+		ext.codes.insert(callee, confidential_code(true, false));
+		let mut setup = with_keys(None, false);
+		assert!(is_rejected(setup.vm.exec(call(caller, callee), &mut ext)));
+		assert_eq!(setup.seen.get(), None);
+		assert!(!setup.activated.get());
+
+		ext.codes.insert(callee, confidential_code(false, true));
+		let mut setup = with_keys(None, false);
+		let result = setup.vm.exec(call(caller, callee), &mut ext);
+		// the callee runs under its own keys in a session of its own, and its
+		// result is sealed for the session peer
+		assert_eq!(setup.seen.get(), Some((Some(callee), 1)));
+		assert_eq!(returned(result), b"sealed:data".to_vec());
+		// the context is shut down again, since the call activated it
+		assert_eq!(setup.contract.get(), None);
+		assert_eq!(setup.sessions.get(), 0);
+		assert!(!setup.activated.get());
+	}
+
+	#[test]
+	fn public_caller_keeps_the_active_context() {
+		let (caller, callee) = (Address::from(1), Address::from(2));
+		let mut ext = FakeExt::new();
+		ext.depth = 2;
+		ext.codes.insert(callee, confidential_code(false, true));
+
+		// a public contract called within a confidential transaction
+		let mut setup = with_keys(None, true);
+		let result = setup.vm.exec(call(caller, callee), &mut ext);
+		assert_eq!(setup.seen.get(), Some((Some(callee), 1)));
+		assert_eq!(returned(result), b"sealed:data".to_vec());
+		assert_eq!(setup.contract.get(), None);
+		assert_eq!(setup.sessions.get(), 0);
+		assert!(setup.activated.get());
+	}
+
+	#[test]
+	fn confidential_caller_needs_allow_public_callees() {
+		let (caller, callee) = (Address::from(1), Address::from(2));
+		let mut ext = FakeExt::new();
+		ext.depth = 1;
+
+		ext.codes.insert(caller, confidential_code(false, true));
+		let mut setup = with_keys(Some(caller), true);
+		assert!(is_rejected(setup.vm.exec(call(caller, callee), &mut ext)));
+		assert_eq!(setup.seen.get(), None);
+		assert_eq!(setup.contract.get(), Some(caller));
+		assert!(setup.activated.get());
+
+		ext.codes.insert(caller, confidential_code(true, false));
+		let mut setup = with_keys(Some(caller), true);
+		let result = setup.vm.exec(call(caller, callee), &mut ext);
+		// the callee runs unencrypted on the call data, and its public result is
+		// handed back as is
+		assert_eq!(setup.seen.get(), Some((None, 0)));
+		assert_eq!(returned(result), b"data".to_vec());
+		// the caller's keys are restored afterwards
+		assert_eq!(setup.contract.get(), Some(caller));
+	}
+
+	#[test]
+	fn caller_policy_is_that_of_the_active_contract() {
+		let (origin, caller, callee) = (Address::from(1), Address::from(2), Address::from(3));
+		let mut ext = FakeExt::new();
+		ext.depth = 1;
+		ext.codes.insert(origin, confidential_code(false, false));
+		ext.codes.insert(caller, confidential_code(true, false));
+
+		// the sender need not be the contract whose keys are in use, e.g. when the
+		// caller is itself running in the storage context of another contract
+		let mut setup = with_keys(Some(caller), true);
+		setup.vm.exec(call(origin, callee), &mut ext).unwrap();
+		assert_eq!(setup.seen.get(), Some((None, 0)));
+
+		let mut setup = with_keys(Some(origin), true);
+		assert!(is_rejected(setup.vm.exec(call(caller, callee), &mut ext)));
+	}
+
+	#[test]
+	fn confidential_calls_need_no_policy() {
+		let (caller, callee) = (Address::from(1), Address::from(2));
+		let mut ext = FakeExt::new();
+		ext.depth = 1;
+		ext.codes.insert(caller, confidential_code(false, false));
+		ext.codes.insert(callee, confidential_code(false, false));
+
+		let mut setup = with_keys(Some(caller), true);
+		let result = setup.vm.exec(call(caller, callee), &mut ext);
+		assert_eq!(setup.seen.get(), Some((Some(callee), 0)));
+		assert_eq!(returned(result), b"data".to_vec());
+		assert_eq!(setup.contract.get(), Some(caller));
+	}
+}
@@ -17,6 +17,7 @@
 //! Cost schedule and other parameterisations for the EVM.
 
 use ethereum_types::U256;
+use std::collections::BTreeSet;
 
 /// Definition of the cost schedule and other parameterisations for the EVM.
 pub struct Schedule {
@@ -117,11 +118,29 @@ pub struct Schedule {
 	pub kill_dust: CleanDustMode,
 	/// Wasm extra schedule settings, if wasm activated
 	pub wasm: Option<WasmCosts>,
+	/// Runtime wasm contracts are executed with
+	pub wasm_backend: WasmBackend,
+	/// Whether wasm contracts run by wasmi are validated on deploy, and their imports
+	/// resolved from the module they name
+	pub wasm_validation: bool,
+	/// Environment variables given to WASI contracts
+	pub wasi_environ: WasiEnviron,
+	/// Whether WASI contracts have directories and symbolic links below their home directory
+	pub wasi_dirs: bool,
+	/// Whether WASI clocks advance with the gas consumed, and `poll_oneoff` waits on them
+	pub wasi_clocks: bool,
+	/// Whether WASI contracts receive the call metadata as arguments
+	pub wasi_args: bool,
+	/// Whether logs emitted in a confidential context are encrypted by the confidential context
+	pub confidential_logs: bool,
+	/// EVMC runtime settings, used when wasm contracts are executed through EVMC
+	pub evmc: EvmcSchedule,
 	/// Default storage duration (in seconds)
 	pub default_storage_duration: u64,
 }
 
 /// Wasm cost table
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct WasmCosts {
 	/// Default opcode cost
 	pub regular: u32,
@@ -147,6 +166,10 @@ pub struct WasmCosts {
 	pub opcodes_mul: u32,
 	/// Cost of wasm opcode is calculated as TABLE_ENTRY_COST * `opcodes_mul` / `opcodes_div`
 	pub opcodes_div: u32,
+	/// Nanoseconds the WASI clocks advance per unit of wasm gas consumed
+	pub clock_nanos_per_gas: u32,
+	/// Restrictions on the modules that can be deployed and instantiated
+	pub policy: WasmPolicy,
 }
 
 impl Default for WasmCosts {
@@ -178,10 +201,120 @@ impl Default for WasmCosts {
 			max_stack_height: 4096 * 1024,
 			opcodes_mul: 1,
 			opcodes_div: 1,
+			clock_nanos_per_gas: 1,
+			policy: WasmPolicy::default(),
 		}
 	}
 }
 
+/// Wasm module policy
+///
+/// The default policy accepts any module the runtime can instantiate.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct WasmPolicy {
+	/// Reject modules using floating point instructions
+	pub forbid_floats: bool,
+	/// Maximum amount of initial memory a module can request, in pages (64kb)
+	pub max_initial_pages: u32,
+	/// Maximum amount of memory a module can grow to, in pages (64kb)
+	pub max_pages: u32,
+	/// Functions modules are allowed to import, as `module.field`; `None` allows all of them
+	pub allowed_imports: Option<BTreeSet<String>>,
+}
+
+impl Default for WasmPolicy {
+	fn default() -> Self {
+		WasmPolicy {
+			forbid_floats: false,
+			max_initial_pages: u32::max_value(),
+			max_pages: u32::max_value(),
+			allowed_imports: None,
+		}
+	}
+}
+
+impl WasmPolicy {
+	/// Whether modules are allowed to import `field` from `module`
+	pub fn allows_import(&self, module: &str, field: &str) -> bool {
+		self.allowed_imports.as_ref().map_or(true, |allowed| {
+			allowed.contains(&format!("{}.{}", module, field))
+		})
+	}
+}
+
+/// Runtime used to execute wasm contracts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WasmBackend {
+	/// The in-tree wasmi interpreter.
+	Wasmi,
+	/// An EVMC shared library, as configured by `EvmcSchedule`.
+	Evmc,
+	/// Both, checking the EVMC library against the wasmi interpreter.
+	Differential,
+}
+
+impl Default for WasmBackend {
+	fn default() -> Self {
+		WasmBackend::Evmc
+	}
+}
+
+/// Version of the set of environment variables given to WASI contracts.
+///
+/// Each version extends the previous one, so contracts relying on an older set
+/// keep working once a newer one is activated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum WasiEnviron {
+	/// `ADDRESS`, `SENDER`, `AAD` and `VALUE`.
+	V1,
+	/// Adds `ORIGIN`, `CODE_ADDRESS`, `BLOCK_NUMBER`, `TIMESTAMP`, `GAS_PRICE`,
+	/// `EXPIRY` and `CONFIDENTIAL`.
+	V2,
+}
+
+impl Default for WasiEnviron {
+	fn default() -> Self {
+		WasiEnviron::V1
+	}
+}
+
+/// EVMC runtime settings
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvmcSchedule {
+	/// Path of the EVMC shared library, `None` to use the node default
+	pub library: Option<String>,
+	/// EVMC revision contracts are executed with
+	pub revision: EvmcRevision,
+}
+
+impl Default for EvmcSchedule {
+	fn default() -> Self {
+		EvmcSchedule {
+			library: None,
+			revision: EvmcRevision::Byzantium,
+		}
+	}
+}
+
+/// EVMC revisions (hard forks) a runtime can be asked to execute with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum EvmcRevision {
+	/// Frontier.
+	Frontier,
+	/// Homestead.
+	Homestead,
+	/// Tangerine Whistle (EIP-150).
+	TangerineWhistle,
+	/// Spurious Dragon (EIP-155, EIP-158).
+	SpuriousDragon,
+	/// Byzantium.
+	Byzantium,
+	/// Constantinople.
+	Constantinople,
+	/// Petersburg (Constantinople without EIP-1283).
+	Petersburg,
+}
+
 /// Dust accounts cleanup mode.
 #[derive(PartialEq, Eq)]
 pub enum CleanDustMode {
@@ -261,6 +394,14 @@ impl Schedule {
 			have_static_call: false,
 			kill_dust: CleanDustMode::Off,
 			wasm: None,
+			wasm_backend: Default::default(),
+			wasm_validation: false,
+			wasi_environ: Default::default(),
+			wasi_dirs: false,
+			wasi_clocks: false,
+			wasi_args: false,
+			confidential_logs: false,
+			evmc: Default::default(),
 			default_storage_duration: 3155695200, // 100 years
 		}
 	}
@@ -332,6 +473,14 @@ impl Schedule {
 			have_static_call: false,
 			kill_dust: CleanDustMode::Off,
 			wasm: None,
+			wasm_backend: Default::default(),
+			wasm_validation: false,
+			wasi_environ: Default::default(),
+			wasi_dirs: false,
+			wasi_clocks: false,
+			wasi_args: false,
+			confidential_logs: false,
+			evmc: Default::default(),
 			default_storage_duration: 3155695200, // 100 years
 		}
 	}
@@ -1,15 +1,167 @@
 use crate::{
-	ActionParams, CallType, ContractCreateResult, CreateContractAddress, Ext, GasLeft,
-	MessageCallResult, Result, ReturnData, Vm,
+	ActionParams, CallType, ContractCreateResult, CreateContractAddress, Error, EvmcRevision,
+	EvmcSchedule, Ext, GasLeft, MessageCallResult, Result, ReturnData, Schedule, Vm, WasmTrap,
 };
 
 use ethereum_types::{Address, H256, U256};
-use evmc_client::{host::HostContext as HostInterface, load, types as evmc_types};
-use std::convert::TryInto;
-use std::{env, ptr, sync::Arc};
+use evmc_client::{host::HostContext as HostInterface, load, types as evmc_types, EvmcVm};
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, ThreadId};
+use std::{cmp, ptr};
+
+/// Opcode reported for EVMC invalid and undefined instruction failures (EVM `INVALID`).
+const INVALID_INSTRUCTION: u8 = 0xfe;
+
+/// Gas EVMC runtimes charge themselves for a write reported as `EVMC_STORAGE_ADDED`.
+const EVMC_SSTORE_SET_GAS: u64 = 20000;
+
+/// Gas EVMC runtimes charge themselves for a write reported as `EVMC_STORAGE_MODIFIED`
+/// or `EVMC_STORAGE_DELETED`.
+const EVMC_SSTORE_RESET_GAS: u64 = 5000;
+
+/// Location of the SSVM EVMC library used when the chain spec does not name one.
+const DEFAULT_EVMC_LIBRARY: &str = "/ssvm/libssvm-evmc.so";
+
+/// Address of the host extension.
+///
+/// The EVMC host interface only knows about 32-byte storage, so contracts reach the
+/// other `Ext` capabilities by calling this address. The call input is a one byte
+/// `HostExtension` selector followed by the operation's arguments, where keys and
+/// words are 32 bytes and addresses are 20 bytes; words are returned big-endian.
+/// Operations reading state are charged `sload_gas`, plus `sload_gas` per 32 bytes
+/// read, out of the call's gas.
+pub const HOST_EXTENSION_ADDRESS: evmc_types::Address = [
+	0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0x01,
+];
+
+/// Operations of the host extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostExtension {
+	/// `key` -> the value stored under `key`.
+	StorageBytesAt = 1,
+	/// `key` -> the length of the value stored under `key`, as a word.
+	StorageBytesLen = 2,
+	/// `key || value` -> nothing. Stores a value of any length under `key`.
+	SetStorageBytes = 3,
+	/// `address` -> the storage expiry of `address`, as a word.
+	StorageExpiry = 4,
+	/// nothing -> the seconds until the current contract expires, as a word.
+	SecondsUntilExpiry = 5,
+	/// `address` -> 1 if the contract at `address` is confidential, else 0, as a word.
+	IsConfidentialContract = 6,
+}
+
+impl HostExtension {
+	fn from_selector(selector: u8) -> Option<Self> {
+		use self::HostExtension::*;
+
+		Some(match selector {
+			1 => StorageBytesAt,
+			2 => StorageBytesLen,
+			3 => SetStorageBytes,
+			4 => StorageExpiry,
+			5 => SecondsUntilExpiry,
+			6 => IsConfidentialContract,
+			_ => return None,
+		})
+	}
+}
+
+/// EVMC instances loaded for the vms of a `VmFactory`, keyed by library path and by
+/// the thread that loaded them.
+///
+/// EVMC does not promise that `execute` may be called concurrently on one instance,
+/// so each thread executing contracts loads its own. Failed loads are not cached, so
+/// a library installed after a failure is picked up by the next execution. Instances
+/// loaded by threads which have since exited stay loaded until `unload`.
+#[derive(Default)]
+pub struct EvmcInstances {
+	instances: Mutex<HashMap<(ThreadId, String), Arc<EvmcInstance>>>,
+}
+
+impl EvmcInstances {
+	/// Creates an empty set of instances.
+	pub fn new() -> Self {
+		EvmcInstances::default()
+	}
+
+	/// Returns the current thread's instance for the library at `path`, loading it if
+	/// it is not loaded yet.
+	pub fn load(&self, path: &str) -> Result<Arc<EvmcInstance>> {
+		let key = (thread::current().id(), path.to_owned());
+		let mut instances = self.instances.lock().expect("no panics while loading; qed");
+		if let Some(instance) = instances.get(&key) {
+			return Ok(instance.clone());
+		}
+		let instance = Arc::new(EvmcInstance::load(path)?);
+		instances.insert(key, instance.clone());
+		Ok(instance)
+	}
+
+	/// Unloads the instances of every thread for the library at `path`. Returns true if
+	/// any was loaded.
+	///
+	/// The VM is destroyed once executions still holding an instance have finished.
+	pub fn unload(&self, path: &str) -> bool {
+		let mut instances = self.instances.lock().expect("no panics while loading; qed");
+		let loaded = instances.len();
+		instances.retain(|&(_, ref library), _| library != path);
+		instances.len() != loaded
+	}
+}
+
+/// A loaded EVMC library together with the VM instance it created.
+///
+/// The instance is shared by every `Ssvm` executing code from the same library on
+/// the thread that loaded it, including nested call frames, and is destroyed when
+/// the last handle is dropped.
+pub struct EvmcInstance {
+	path: String,
+	vm: EvmcVm,
+}
+
+// `EvmcInstances` only hands an instance out to the thread that loaded it. Another
+// thread may only drop the last handle, once no execution holds the instance.
+unsafe impl Send for EvmcInstance {}
+unsafe impl Sync for EvmcInstance {}
+
+impl EvmcInstance {
+	/// Path of the shared library this instance was loaded from.
+	pub fn path(&self) -> &str {
+		&self.path
+	}
+
+	fn load(path: &str) -> Result<EvmcInstance> {
+		let (vm, result) = load(path);
+		match result {
+			Ok(_) => {
+				debug!(target: "ssvm", "Loaded EVMC library {}", path);
+				Ok(EvmcInstance {
+					path: path.to_owned(),
+					vm,
+				})
+			}
+			Err(err) => {
+				let msg = format!("Failed to load EVMC library {}: {:?}", path, err);
+				warn!(target: "ssvm", "{}", msg);
+				Err(Error::Internal(msg))
+			}
+		}
+	}
+}
+
+impl Drop for EvmcInstance {
+	fn drop(&mut self) {
+		debug!(target: "ssvm", "Destroying EVMC instance from {}", self.path);
+		self.vm.destroy();
+	}
+}
 
 struct RuntimeContext {
-	coinbase: Address,
+	/// Address whose storage the executing code runs against.
+	address: Address,
 	origin: Address,
 	gas_price: U256,
 }
@@ -17,6 +169,32 @@ struct RuntimeContext {
 struct HostContext<'a> {
 	context: RuntimeContext,
 	ext: &'a mut dyn Ext,
+	/// Gas, in wasm units, the execution started with.
+	gas_limit: U256,
+	/// Gas, in wasm units, the host knows to be spent: what the runtime charged
+	/// itself for storage writes, and the storage gas paid out of nested calls.
+	spent_gas: U256,
+	/// Gas, in wasm units, charged for storage written through the host and not yet
+	/// paid. EVMC gives the host no way to take gas from the runtime, so it is paid
+	/// out of the gas handed to the next nested call or create, or out of the gas
+	/// left once execution finishes.
+	storage_gas: U256,
+	/// First error raised while the runtime could not be told about it. Once set,
+	/// storage writes, calls and creates fail.
+	error: Option<Error>,
+}
+
+impl<'a> HostContext<'a> {
+	fn new(context: RuntimeContext, ext: &'a mut dyn Ext, gas_limit: U256) -> Self {
+		HostContext {
+			context,
+			ext,
+			gas_limit,
+			spent_gas: U256::zero(),
+			storage_gas: U256::zero(),
+			error: None,
+		}
+	}
 }
 
 impl HostInterface for HostContext<'_> {
@@ -41,23 +219,43 @@ impl HostInterface for HostContext<'_> {
 		key: &evmc_types::Bytes32,
 		value: &evmc_types::Bytes32,
 	) -> evmc_types::StorageStatus {
-		let ret: evmc_types::StorageStatus;
-		let orig_v = self
-			.ext
-			.storage_at(&H256::from_slice(key))
-			.unwrap_or(H256::zero());
+		let key = H256::from_slice(key);
+		if self.error.is_some() {
+			return evmc_types::StorageStatus::EVMC_STORAGE_UNCHANGED;
+		}
+		let orig_v = self.ext.storage_at(&key).unwrap_or(H256::zero());
 		let new_v = H256::from_slice(value);
-		if orig_v.is_zero() {
-			ret = evmc_types::StorageStatus::EVMC_STORAGE_ADDED;
-		} else if orig_v == new_v {
-			ret = evmc_types::StorageStatus::EVMC_STORAGE_UNCHANGED;
+		let ret = if orig_v == new_v {
+			evmc_types::StorageStatus::EVMC_STORAGE_UNCHANGED
+		} else if orig_v.is_zero() {
+			evmc_types::StorageStatus::EVMC_STORAGE_ADDED
+		} else if new_v.is_zero() {
+			evmc_types::StorageStatus::EVMC_STORAGE_DELETED
 		} else {
-			ret = evmc_types::StorageStatus::EVMC_STORAGE_MODIFIED;
-		}
+			evmc_types::StorageStatus::EVMC_STORAGE_MODIFIED
+		};
 		if ret != evmc_types::StorageStatus::EVMC_STORAGE_UNCHANGED {
-			self.ext.set_storage(H256::from_slice(key), new_v);
+			// priced like the wasmi runtime: by size and time until expiry, with
+			// cleared slots accruing a refund. EVMC runtimes charge the EVM price for
+			// the status returned, so only the rest is charged here.
+			let reset = !orig_v.is_zero();
+			let metered = U256::from(if reset {
+				EVMC_SSTORE_RESET_GAS
+			} else {
+				EVMC_SSTORE_SET_GAS
+			});
+			let result = self.storage_cost(32, reset).and_then(|gas| {
+				self.charge_storage(gas.saturating_sub(metered), metered)?;
+				if new_v.is_zero() {
+					self.ext.inc_sstore_clears(32)?;
+				}
+				self.ext.set_storage(key, new_v)
+			});
+			if let Err(err) = result {
+				self.error.get_or_insert(err);
+			}
 		}
-		return ret;
+		ret
 	}
 	fn get_balance(&mut self, addr: &evmc_types::Address) -> evmc_types::Bytes32 {
 		self.ext
@@ -66,10 +264,18 @@ impl HostInterface for HostContext<'_> {
 			.into()
 	}
 	fn get_code_size(&mut self, addr: &evmc_types::Address) -> usize {
-		self.ext.extcodesize(&Address::from_slice(addr)).unwrap()
+		self.ext
+			.extcodesize(&Address::from_slice(addr))
+			.unwrap_or(0)
 	}
-	fn get_code_hash(&mut self, _addr: &evmc_types::Address) -> evmc_types::Bytes32 {
-		unimplemented!()
+	fn get_code_hash(&mut self, addr: &evmc_types::Address) -> evmc_types::Bytes32 {
+		// EVMC expects zero for accounts that do not exist.
+		self.ext
+			.extcodehash(&Address::from_slice(addr))
+			.ok()
+			.and_then(|hash| hash)
+			.unwrap_or_else(H256::zero)
+			.into()
 	}
 	fn copy_code(
 		&mut self,
@@ -78,19 +284,20 @@ impl HostInterface for HostContext<'_> {
 		buffer_data: &*mut u8,
 		buffer_size: &usize,
 	) -> usize {
-		let code = self.ext.extcode(&Address::from_slice(addr)).unwrap();
-		let remain_size = code.len() - offset;
-		let buffer_size = buffer_size.to_owned();
+		let code = match self.ext.extcode(&Address::from_slice(addr)) {
+			Ok(code) => code,
+			Err(_) => return 0,
+		};
+		if *offset >= code.len() {
+			return 0;
+		}
+		let copy_size = cmp::min(code.len() - *offset, *buffer_size);
+		// `offset + copy_size <= code.len()` and the vm guarantees that `buffer_data`
+		// points to at least `buffer_size` writable bytes.
 		unsafe {
-			let src = code.as_ptr().offset(offset.to_owned().try_into().unwrap());
-			ptr::copy(src, buffer_data.to_owned(), buffer_size);
+			ptr::copy_nonoverlapping(code.as_ptr().add(*offset), *buffer_data, copy_size);
 		}
-		let ret = if remain_size >= buffer_size {
-			buffer_size
-		} else {
-			remain_size
-		};
-		return ret;
+		copy_size
 	}
 	fn selfdestruct(&mut self, _addr: &evmc_types::Address, beneficiary: &evmc_types::Address) {
 		self.ext.suicide(&Address::from_slice(beneficiary));
@@ -110,7 +317,7 @@ impl HostInterface for HostContext<'_> {
 		return (
 			self.context.gas_price.into(),
 			self.context.origin.into(),
-			self.context.coinbase.into(),
+			info.author.into(),
 			info.number as i64,
 			info.timestamp as i64,
 			info.gas_limit.low_u64() as i64,
@@ -118,6 +325,10 @@ impl HostInterface for HostContext<'_> {
 		);
 	}
 	fn get_block_hash(&mut self, number: i64) -> evmc_types::Bytes32 {
+		// a negative height is as unknown as one out of range
+		if number < 0 {
+			return [0u8; 32];
+		}
 		self.ext.blockhash(&U256::from(number)).into()
 	}
 	fn emit_log(
@@ -140,109 +351,418 @@ impl HostInterface for HostContext<'_> {
 		value: &evmc_types::Bytes32,
 		input: &[u8],
 		gas: i64,
-		_depth: i32,
+		depth: i32,
 		is_static: bool,
 		salt: &evmc_types::Bytes32,
 	) -> (Vec<u8>, i64, evmc_types::Address, evmc_types::StatusCode) {
-		fn convert_calltype(kind: evmc_types::CallKind, _is_static: bool) -> CallType {
-			if _is_static {
-				return CallType::StaticCall;
-			} else if kind == evmc_types::CallKind::EVMC_CALL {
-				return CallType::Call;
-			} else if kind == evmc_types::CallKind::EVMC_CALLCODE {
-				return CallType::CallCode;
-			} else if kind == evmc_types::CallKind::EVMC_DELEGATECALL {
-				return CallType::DelegateCall;
-			} else {
-				return CallType::None;
+		if self.error.is_some() {
+			return (
+				vec![],
+				0,
+				[0u8; evmc_types::ADDRESS_LENGTH],
+				evmc_types::StatusCode::EVMC_FAILURE,
+			);
+		}
+
+		// `depth` is that of the frame being entered, i.e. one more than ours.
+		let max_depth = self.ext.schedule().max_depth;
+		if self.ext.depth() >= max_depth || depth < 0 || depth as usize > max_depth {
+			return (
+				vec![],
+				gas,
+				[0u8; evmc_types::ADDRESS_LENGTH],
+				evmc_types::StatusCode::EVMC_CALL_DEPTH_EXCEEDED,
+			);
+		}
+
+		match kind {
+			evmc_types::CallKind::EVMC_CALL if *destination == HOST_EXTENSION_ADDRESS => {
+				self.host_extension(U256::from(value), input, gas, is_static)
 			}
+			evmc_types::CallKind::EVMC_CREATE => self.create(
+				&Address::from_slice(sender),
+				U256::from(value),
+				input,
+				gas,
+				CreateContractAddress::FromSenderAndNonce,
+			),
+			evmc_types::CallKind::EVMC_CREATE2 => self.create(
+				&Address::from_slice(sender),
+				U256::from(value),
+				input,
+				gas,
+				CreateContractAddress::FromSenderSaltAndCodeHash(H256::from_slice(salt)),
+			),
+			_ => self.message_call(
+				kind,
+				&Address::from_slice(destination),
+				&Address::from_slice(sender),
+				U256::from(value),
+				input,
+				gas,
+				is_static,
+			),
 		}
+	}
+}
 
-		if kind == evmc_types::CallKind::EVMC_CREATE || kind == evmc_types::CallKind::EVMC_CREATE2 {
-			let contract_code = &mut [];
-			let result = self.ext.create(
-				&U256::from(gas),
-				&U256::from(value),
-				contract_code,
-				if kind == evmc_types::CallKind::EVMC_CREATE {
-					CreateContractAddress::FromSenderAndNonce
-				} else {
-					CreateContractAddress::FromSenderSaltAndCodeHash(H256::from_slice(salt))
-				},
+impl HostContext<'_> {
+	/// Deploys `init_code` on behalf of `sender`, mirroring the EVM `CREATE`/`CREATE2`
+	/// instructions: the endowment must be covered by the sender's balance, unused gas
+	/// is handed back to the caller, and a failed deployment consumes all of it.
+	fn create(
+		&mut self,
+		sender: &Address,
+		endowment: U256,
+		init_code: &[u8],
+		gas: i64,
+		address_scheme: CreateContractAddress,
+	) -> (Vec<u8>, i64, evmc_types::Address, evmc_types::StatusCode) {
+		let no_address = [0u8; evmc_types::ADDRESS_LENGTH];
+
+		if self.ext.is_static() {
+			return (
+				vec![],
+				gas,
+				no_address,
+				evmc_types::StatusCode::EVMC_STATIC_MODE_VIOLATION,
 			);
-			match result {
-				ContractCreateResult::Created(address, gas_left) => {
+		}
+		if self.ext.balance(sender).unwrap_or_else(|_| U256::zero()) < endowment {
+			return (
+				vec![],
+				gas,
+				no_address,
+				evmc_types::StatusCode::EVMC_FAILURE,
+			);
+		}
+
+		let gas = self.pay_storage_gas(gas);
+		let result = self.ext.create(
+			&U256::from(cmp::max(gas, 0) as u64),
+			&endowment,
+			init_code,
+			address_scheme,
+		);
+		match result {
+			ContractCreateResult::Created(address, gas_left) => (
+				vec![],
+				gas_left.low_u64() as i64,
+				address.into(),
+				evmc_types::StatusCode::EVMC_SUCCESS,
+			),
+			ContractCreateResult::Failed => {
+				(vec![], 0, no_address, evmc_types::StatusCode::EVMC_FAILURE)
+			}
+			ContractCreateResult::Reverted(gas_left, return_data) => (
+				return_data.to_vec(),
+				gas_left.low_u64() as i64,
+				no_address,
+				evmc_types::StatusCode::EVMC_REVERT,
+			),
+		}
+	}
+
+	/// Makes a message call, mirroring the EVM `CALL`/`CALLCODE`/`DELEGATECALL`/`STATICCALL`
+	/// instructions. `destination` is always the address of the code to run; for
+	/// `CALLCODE` and `DELEGATECALL` it runs against the storage of the current contract.
+	fn message_call(
+		&mut self,
+		kind: evmc_types::CallKind,
+		destination: &Address,
+		sender: &Address,
+		value: U256,
+		input: &[u8],
+		gas: i64,
+		is_static: bool,
+	) -> (Vec<u8>, i64, evmc_types::Address, evmc_types::StatusCode) {
+		let no_address = [0u8; evmc_types::ADDRESS_LENGTH];
+
+		let (call_type, receive_address, value) = match kind {
+			evmc_types::CallKind::EVMC_CALL if is_static => {
+				(CallType::StaticCall, *destination, None)
+			}
+			evmc_types::CallKind::EVMC_CALL => (CallType::Call, *destination, Some(value)),
+			evmc_types::CallKind::EVMC_CALLCODE => {
+				(CallType::CallCode, self.context.address, Some(value))
+			}
+			evmc_types::CallKind::EVMC_DELEGATECALL => {
+				(CallType::DelegateCall, self.context.address, None)
+			}
+			_ => {
+				return (
+					vec![],
+					gas,
+					no_address,
+					evmc_types::StatusCode::EVMC_REJECTED,
+				)
+			}
+		};
+
+		if let Some(value) = value {
+			if !value.is_zero() {
+				if self.ext.is_static() {
 					return (
-						contract_code.to_vec(),
-						gas_left.as_u64() as i64,
-						address.into(),
-						evmc_types::StatusCode::EVMC_SUCCESS,
+						vec![],
+						gas,
+						no_address,
+						evmc_types::StatusCode::EVMC_STATIC_MODE_VIOLATION,
 					);
 				}
-				ContractCreateResult::Failed => {
+				if self
+					.ext
+					.balance(&self.context.address)
+					.unwrap_or_else(|_| U256::zero())
+					< value
+				{
 					return (
 						vec![],
 						gas,
-						[0u8; evmc_types::ADDRESS_LENGTH],
+						no_address,
 						evmc_types::StatusCode::EVMC_FAILURE,
 					);
 				}
-				ContractCreateResult::Reverted(gas_left, return_data) => {
-					return (
-						return_data.to_vec(),
-						gas_left.as_u64() as i64,
-						[0u8; evmc_types::ADDRESS_LENGTH],
-						evmc_types::StatusCode::EVMC_REVERT,
-					);
-				}
 			}
-		} else {
-			let result = self.ext.call(
-				&U256::from(gas),
-				&Address::from_slice(sender),
-				&Address::from_slice(destination),
-				Some(U256::from(value)),
-				input,
-				&Address::from_slice(destination),
-				&mut [],
-				convert_calltype(kind, is_static),
-			);
+		}
 
-			match result {
-				MessageCallResult::Success(gas_left, return_data) => {
-					return (
-						return_data.to_vec(),
-						gas_left.as_u64() as i64,
-						[0u8; evmc_types::ADDRESS_LENGTH],
-						evmc_types::StatusCode::EVMC_SUCCESS,
-					);
-				}
-				MessageCallResult::Failed => {
+		let gas = self.pay_storage_gas(gas);
+		let result = self.ext.call(
+			&U256::from(cmp::max(gas, 0) as u64),
+			sender,
+			&receive_address,
+			value,
+			input,
+			destination,
+			&mut [],
+			call_type,
+		);
+		match result {
+			MessageCallResult::Success(gas_left, return_data) => (
+				return_data.to_vec(),
+				gas_left.low_u64() as i64,
+				no_address,
+				evmc_types::StatusCode::EVMC_SUCCESS,
+			),
+			MessageCallResult::Failed => {
+				(vec![], 0, no_address, evmc_types::StatusCode::EVMC_FAILURE)
+			}
+			MessageCallResult::Reverted(gas_left, return_data) => (
+				return_data.to_vec(),
+				gas_left.low_u64() as i64,
+				no_address,
+				evmc_types::StatusCode::EVMC_REVERT,
+			),
+		}
+	}
+}
+
+impl HostContext<'_> {
+	/// Runs a `HostExtension` operation on behalf of the current contract. Malformed
+	/// input fails the call, consuming all of its gas, like a call to a failing builtin.
+	/// So does a call carrying value, which the extension would have nowhere to put.
+	fn host_extension(
+		&mut self,
+		value: U256,
+		input: &[u8],
+		gas: i64,
+		is_static: bool,
+	) -> (Vec<u8>, i64, evmc_types::Address, evmc_types::StatusCode) {
+		let no_address = [0u8; evmc_types::ADDRESS_LENGTH];
+		let failure = (vec![], 0, no_address, evmc_types::StatusCode::EVMC_FAILURE);
+
+		if !value.is_zero() {
+			return failure;
+		}
+
+		let (operation, args) = match input.split_first() {
+			Some((selector, args)) => match HostExtension::from_selector(*selector) {
+				Some(operation) => (operation, args),
+				None => return failure,
+			},
+			None => return failure,
+		};
+		let word = |value: U256| H256::from(value).to_vec();
+
+		let mut gas_left = gas;
+		let output = match operation {
+			HostExtension::StorageBytesAt if args.len() == 32 => {
+				// the lookup of the length is paid before the bytes it reports
+				let key = H256::from_slice(args);
+				self.charge_read(0, &mut gas_left)
+					.and_then(|_| self.ext.storage_bytes_len(&key))
+					.and_then(|len| {
+						let bytes_gas = self.read_gas(len)? - self.read_gas(0)?;
+						charge(bytes_gas, &mut gas_left)
+					})
+					.and_then(|_| self.ext.storage_bytes_at(&key))
+			}
+			HostExtension::StorageBytesLen if args.len() == 32 => self
+				.charge_read(0, &mut gas_left)
+				.and_then(|_| self.ext.storage_bytes_len(&H256::from_slice(args)))
+				.map(|len| word(len.into())),
+			HostExtension::SetStorageBytes if args.len() >= 32 => {
+				if is_static || self.ext.is_static() {
 					return (
 						vec![],
 						gas,
-						[0u8; evmc_types::ADDRESS_LENGTH],
-						evmc_types::StatusCode::EVMC_FAILURE,
+						no_address,
+						evmc_types::StatusCode::EVMC_STATIC_MODE_VIOLATION,
 					);
 				}
-				MessageCallResult::Reverted(gas_left, return_data) => {
+				let (key, value) = args.split_at(32);
+				let key = H256::from_slice(key);
+				let prev_len = match self.ext.storage_bytes_len(&key) {
+					Ok(prev_len) => prev_len,
+					Err(_) => return failure,
+				};
+				let cost = match self.storage_cost(value.len() as u64, prev_len > 0) {
+					Ok(cost) => cost,
+					Err(_) => return failure,
+				};
+				if cost > U256::from(gas_left) {
 					return (
-						return_data.to_vec(),
-						gas_left.as_u64() as i64,
-						[0u8; evmc_types::ADDRESS_LENGTH],
-						evmc_types::StatusCode::EVMC_REVERT,
+						vec![],
+						0,
+						no_address,
+						evmc_types::StatusCode::EVMC_OUT_OF_GAS,
 					);
 				}
+				gas_left -= cost.low_u64() as i64;
+				let cleared = if value.is_empty() && prev_len > 0 {
+					self.ext.inc_sstore_clears(prev_len)
+				} else {
+					Ok(())
+				};
+				cleared
+					.and_then(|_| self.ext.set_storage_bytes(key, value.to_vec()))
+					.map(|_| vec![])
 			}
+			HostExtension::StorageExpiry if args.len() == 20 => self
+				.charge_read(0, &mut gas_left)
+				.and_then(|_| self.ext.storage_expiry(&Address::from_slice(args)))
+				.map(|expiry| word(expiry.into())),
+			HostExtension::SecondsUntilExpiry if args.is_empty() => self
+				.charge_read(0, &mut gas_left)
+				.and_then(|_| self.ext.seconds_until_expiry())
+				.map(|seconds| word(seconds.into())),
+			HostExtension::IsConfidentialContract if args.len() == 20 => self
+				.charge_read(0, &mut gas_left)
+				.and_then(|_| {
+					self.ext
+						.is_confidential_contract(&Address::from_slice(args))
+				})
+				.map(|confidential| word((confidential as u64).into())),
+			_ => return failure,
+		};
+
+		match output {
+			Ok(output) => (
+				output,
+				gas_left,
+				no_address,
+				evmc_types::StatusCode::EVMC_SUCCESS,
+			),
+			Err(Error::OutOfGas) => (
+				vec![],
+				0,
+				no_address,
+				evmc_types::StatusCode::EVMC_OUT_OF_GAS,
+			),
+			Err(_) => failure,
+		}
+	}
+
+	/// Charges a state lookup reading `bytes_len` bytes out of `gas_left`, priced like
+	/// `Runtime::storage_read_charge` in the wasmi runtime with `sload_gas` added for
+	/// the lookup itself. Fails with `Error::OutOfGas` if `gas_left` does not cover it.
+	fn charge_read(&self, bytes_len: u64, gas_left: &mut i64) -> Result<()> {
+		charge(self.read_gas(bytes_len)?, gas_left)
+	}
+
+	/// Gas, in wasm units, of a state lookup reading `bytes_len` bytes.
+	fn read_gas(&self, bytes_len: u64) -> Result<U256> {
+		let schedule = self.ext.schedule();
+		let sload_gas = U256::from(schedule.sload_gas);
+		// sload_gas + ceiling(sload_gas * bytes_len / 32)
+		let gas = sload_gas + (sload_gas * U256::from(bytes_len) + U256::from(31)) / U256::from(32);
+		Ok(to_wasm_gas(wasm_gas_scale(schedule)?, gas))
+	}
+
+	/// Gas, in wasm units, for storing `bytes_len` bytes until the contract expires,
+	/// priced like `Runtime::storage_bytes_charge` in the wasmi runtime.
+	fn storage_cost(&self, bytes_len: u64, reset: bool) -> Result<U256> {
+		let duration_secs = self.ext.seconds_until_expiry()?;
+		let schedule = self.ext.schedule();
+		let gas = if reset {
+			schedule.prorated_sstore_reset_gas(duration_secs, bytes_len)
+		} else {
+			schedule.prorated_sstore_set_gas(duration_secs, bytes_len)
+		};
+		Ok(to_wasm_gas(wasm_gas_scale(schedule)?, gas))
+	}
+
+	/// Charges `gas`, in wasm units, for a storage write the runtime charged itself
+	/// `metered` for, failing if the execution cannot have that much left.
+	///
+	/// EVMC does not tell the host how much gas is left while storage is written, so
+	/// this only accounts for the gas the host knows to be spent; gas spent otherwise
+	/// is accounted for once the execution finishes, see `Ssvm::exec`.
+	fn charge_storage(&mut self, gas: U256, metered: U256) -> Result<()> {
+		let spent_gas = self.spent_gas.saturating_add(metered);
+		let storage_gas = self.storage_gas.saturating_add(gas);
+		if spent_gas.saturating_add(storage_gas) > self.gas_limit {
+			return Err(Error::OutOfGas);
 		}
+		self.spent_gas = spent_gas;
+		self.storage_gas = storage_gas;
+		Ok(())
+	}
+
+	/// Pays the storage gas charged so far out of `gas`, which the runtime is handing
+	/// to a nested call or create, and returns what is left for the callee. The
+	/// runtime takes whatever the callee does not give back, so the caller is charged.
+	fn pay_storage_gas(&mut self, gas: i64) -> i64 {
+		let paid = cmp::min(U256::from(cmp::max(gas, 0) as u64), self.storage_gas);
+		self.storage_gas = self.storage_gas - paid;
+		self.spent_gas = self.spent_gas.saturating_add(paid);
+		gas - paid.low_u64() as i64
 	}
 }
 
-pub struct Ssvm;
+/// Executes WASM contracts on an EVMC-compatible runtime (SSVM by default).
+pub struct Ssvm {
+	/// Instances of the loaded EVMC libraries.
+	instances: Arc<EvmcInstances>,
+	/// Path of the EVMC shared library to execute with.
+	library: String,
+}
 
 impl Ssvm {
-	pub fn new() -> Self {
-		Ssvm {}
+	/// Creates a vm backed by the default SSVM library, loaded into `instances`.
+	pub fn new(instances: Arc<EvmcInstances>) -> Self {
+		Ssvm::with_library(instances, DEFAULT_EVMC_LIBRARY)
+	}
+
+	/// Creates a vm backed by the EVMC library at `library`, loaded into `instances`.
+	pub fn with_library<S: Into<String>>(instances: Arc<EvmcInstances>, library: S) -> Self {
+		Ssvm {
+			instances,
+			library: library.into(),
+		}
+	}
+
+	/// Creates a vm backed by the library named by the `EVMC_LIBRARY` environment
+	/// variable, which lets a node keep the library pinned in the chain spec at another
+	/// path. Otherwise, uses the library pinned in `schedule`, falling back to
+	/// `Ssvm::new` if the chain spec does not name one.
+	pub fn from_schedule(instances: Arc<EvmcInstances>, schedule: &EvmcSchedule) -> Self {
+		if let Ok(library) = env::var("EVMC_LIBRARY") {
+			return Ssvm::with_library(instances, library);
+		}
+		match schedule.library {
+			Some(ref library) => Ssvm::with_library(instances, library.as_str()),
+			None => Ssvm::new(instances),
+		}
 	}
 }
 
@@ -252,51 +772,49 @@ impl Vm for Ssvm {
 	}
 	fn exec(&mut self, params: ActionParams, ext: &mut dyn Ext) -> Result<GasLeft> {
 		if cfg!(target_env = "sgx") {
-			return Ok(GasLeft::NeedsReturn {
-				gas_left: U256::zero(),
-				data: ReturnData::empty(),
-				apply_state: true,
-			});
+			return Err(Error::Internal(
+				"EVMC libraries cannot be loaded inside an enclave".to_owned(),
+			));
 		}
 
-		let lib_path = match env::var("EVMC_LIBRARY") {
-			Ok(val) => val,
-			_ => "/ssvm/libssvm-evmc.so".to_string(),
-		};
-		let (vm, result) = load(&lib_path);
-		if result.is_err() {
-			return Ok(GasLeft::NeedsReturn {
-				gas_left: U256::zero(),
-				data: ReturnData::empty(),
-				apply_state: false,
-			});
+		let instance = self.instances.load(&self.library)?;
+
+		// gas is metered in wasm units, scaled like in the wasmi interpreter
+		let scale = wasm_gas_scale(ext.schedule())?;
+		let adjusted_gas = to_wasm_gas(scale, params.gas);
+		if adjusted_gas > U256::from(i64::max_value()) {
+			return Err(Error::Wasm(
+				"EVMC cannot run contracts with gas (wasm adjusted) >= 2^63".to_owned(),
+			));
 		}
-		let mut host_context = HostContext {
-			context: RuntimeContext {
-				coinbase: params.sender,
-				origin: params.origin,
-				gas_price: params.gas_price,
-			},
-			ext: ext,
-		};
+
+		// STATICCALL is an EVMC_CALL with the static flag set, which is also inherited
+		// by every frame below a static one.
 		let call_type = match params.call_type {
-			CallType::Call => evmc_types::CallKind::EVMC_CALL,
 			CallType::CallCode => evmc_types::CallKind::EVMC_CALLCODE,
 			CallType::DelegateCall => evmc_types::CallKind::EVMC_DELEGATECALL,
-			CallType::StaticCall => evmc_types::CallKind::EVMC_CALL,
 			_ => evmc_types::CallKind::EVMC_CALL,
 		};
-		let is_static = match params.call_type {
-			CallType::StaticCall => true,
-			_ => false,
-		};
-		let (output, gas_left, status_code) = vm.execute(
+		let is_static = params.call_type == CallType::StaticCall || ext.is_static();
+		let depth = ext.depth() as i32;
+		let revision = into_evmc_revision(ext.schedule().evmc.revision);
+		let mut host_context = HostContext::new(
+			RuntimeContext {
+				address: params.address,
+				origin: params.origin,
+				gas_price: params.gas_price,
+			},
+			ext,
+			adjusted_gas,
+		);
+		let (output, gas_left, status_code) = instance.vm.execute(
 			&mut host_context,
-			evmc_types::Revision::EVMC_BYZANTIUM,
+			revision,
 			call_type,
 			is_static,
-			0,
-			params.gas.as_u64() as i64,
+			depth,
+			// cannot overflow, checked above
+			adjusted_gas.low_u64() as i64,
 			&params.address.into(),
 			&params.sender.into(),
 			&params.data.unwrap_or(Vec::new()),
@@ -304,19 +822,749 @@ impl Vm for Ssvm {
 			&params.code.unwrap_or(Arc::new(Vec::new())),
 			&[0u8; 32],
 		);
-		vm.destroy();
 
-		if status_code != evmc_types::StatusCode::EVMC_SUCCESS {
-			return Ok(GasLeft::NeedsReturn {
-				gas_left: U256::zero(),
-				data: ReturnData::empty(),
-				apply_state: false,
-			});
+		if let Some(err) = host_context.error {
+			return Err(err);
 		}
-		Ok(GasLeft::NeedsReturn {
-			gas_left: gas_left.into(),
-			data: ReturnData::new(output.to_vec(), 0, output.len()),
-			apply_state: true,
+		let storage_gas = host_context.storage_gas;
+		let result = match status_code {
+			evmc_types::StatusCode::EVMC_SUCCESS | evmc_types::StatusCode::EVMC_REVERT
+				if gas_left >= 0 && U256::from(gas_left) < storage_gas =>
+			{
+				Err(Error::OutOfGas)
+			}
+			evmc_types::StatusCode::EVMC_SUCCESS | evmc_types::StatusCode::EVMC_REVERT
+				if gas_left >= 0 =>
+			{
+				// cannot underflow, checked above
+				let gas_left = gas_left - storage_gas.low_u64() as i64;
+				into_vm_result(status_code, gas_left, &output)
+			}
+			_ => into_vm_result(status_code, gas_left, &output),
+		};
+
+		result.map(|result| match result {
+			GasLeft::Known(gas_left) => GasLeft::Known(from_wasm_gas(scale, gas_left)),
+			GasLeft::NeedsReturn {
+				gas_left,
+				data,
+				apply_state,
+			} => GasLeft::NeedsReturn {
+				gas_left: from_wasm_gas(scale, gas_left),
+				data,
+				apply_state,
+			},
 		})
 	}
 }
+
+/// Returns `(opcodes_mul, opcodes_div)`, by which gas is scaled to wasm units.
+fn wasm_gas_scale(schedule: &Schedule) -> Result<(u32, u32)> {
+	match schedule.wasm {
+		Some(ref wasm) => Ok((wasm.opcodes_mul, wasm.opcodes_div)),
+		None => Err(Error::Wasm("Wasm is not activated".to_owned())),
+	}
+}
+
+/// Converts gas to wasm units, as `WasmInterpreter` does when entering a contract
+/// and `Runtime::adjusted_charge` does for host charges.
+/// Takes `cost` out of `gas_left`, failing with `Error::OutOfGas` if it does not cover it.
+fn charge(cost: U256, gas_left: &mut i64) -> Result<()> {
+	if cost > U256::from(cmp::max(*gas_left, 0) as u64) {
+		return Err(Error::OutOfGas);
+	}
+	*gas_left -= cost.low_u64() as i64;
+	Ok(())
+}
+
+fn to_wasm_gas((opcodes_mul, opcodes_div): (u32, u32), gas: U256) -> U256 {
+	gas * U256::from(opcodes_div) / U256::from(opcodes_mul)
+}
+
+/// Converts gas in wasm units back, as `WasmInterpreter` does for the gas left.
+fn from_wasm_gas((opcodes_mul, opcodes_div): (u32, u32), gas: U256) -> U256 {
+	gas * U256::from(opcodes_mul) / U256::from(opcodes_div)
+}
+
+fn into_evmc_revision(revision: EvmcRevision) -> evmc_types::Revision {
+	use self::evmc_types::Revision::*;
+
+	match revision {
+		EvmcRevision::Frontier => EVMC_FRONTIER,
+		EvmcRevision::Homestead => EVMC_HOMESTEAD,
+		EvmcRevision::TangerineWhistle => EVMC_TANGERINE_WHISTLE,
+		EvmcRevision::SpuriousDragon => EVMC_SPURIOUS_DRAGON,
+		EvmcRevision::Byzantium => EVMC_BYZANTIUM,
+		EvmcRevision::Constantinople => EVMC_CONSTANTINOPLE,
+		EvmcRevision::Petersburg => EVMC_PETERSBURG,
+	}
+}
+
+fn wasm_trap(kind: &str) -> Error {
+	Error::WasmTrap(WasmTrap {
+		kind: kind.to_owned(),
+		backtrace: vec![],
+	})
+}
+
+/// Converts the outcome of an EVMC execution into the result expected by the executive.
+///
+/// As with the other vms, a revert is not an `Err` but a `NeedsReturn` with
+/// `apply_state: false`, so that the remaining gas and the revert data reach the caller.
+fn into_vm_result(
+	status_code: evmc_types::StatusCode,
+	gas_left: i64,
+	output: &[u8],
+) -> Result<GasLeft> {
+	use self::evmc_types::StatusCode::*;
+
+	let apply_state = match status_code {
+		EVMC_SUCCESS => true,
+		EVMC_REVERT => false,
+		EVMC_OUT_OF_GAS => return Err(Error::OutOfGas),
+		EVMC_INVALID_INSTRUCTION | EVMC_UNDEFINED_INSTRUCTION => {
+			return Err(Error::BadInstruction {
+				instruction: INVALID_INSTRUCTION,
+			});
+		}
+		EVMC_STATIC_MODE_VIOLATION => return Err(Error::MutableCallInStaticContext),
+		EVMC_PRECOMPILE_FAILURE => return Err(Error::BuiltIn("evmc precompile")),
+		// named like the `TrapKind`s of wasmi; SSVM reports no backtrace
+		EVMC_WASM_UNREACHABLE_INSTRUCTION => return Err(wasm_trap("Unreachable")),
+		EVMC_INVALID_MEMORY_ACCESS => return Err(wasm_trap("MemoryAccessOutOfBounds")),
+		EVMC_STACK_OVERFLOW => return Err(wasm_trap("StackOverflow")),
+		EVMC_WASM_TRAP => return Err(wasm_trap("Trap")),
+		// the runtime itself failed, rather than the contract
+		EVMC_INTERNAL_ERROR | EVMC_REJECTED => {
+			return Err(Error::Internal(format!(
+				"EVMC execution failed: {:?}",
+				status_code
+			)));
+		}
+		// failures EVMC reports no details of, such as a bad jump destination or a
+		// stack underflow, are named by their status
+		_ => {
+			return Err(Error::Wasm(format!(
+				"EVMC execution failed: {:?}",
+				status_code
+			)));
+		}
+	};
+
+	if gas_left < 0 {
+		return Err(Error::Internal(format!(
+			"EVMC returned negative gas left: {}",
+			gas_left
+		)));
+	}
+
+	Ok(GasLeft::NeedsReturn {
+		gas_left: U256::from(gas_left as u64),
+		data: ReturnData::new(output.to_vec(), 0, output.len()),
+		apply_state,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::tests::{FakeCallType, FakeExt};
+	use hash::{keccak, KECCAK_EMPTY};
+
+	#[test]
+	fn missing_library_is_an_error() {
+		let path = "/nonexistent/libevmc-missing.so";
+		let mut ext = FakeExt::new();
+		let instances = Arc::new(EvmcInstances::new());
+		let mut vm = Ssvm::with_library(instances.clone(), path);
+
+		match vm.exec(ActionParams::default(), &mut ext) {
+			Err(Error::Internal(msg)) => assert!(msg.contains(path)),
+			other => panic!("expected an internal error, got {:?}", other),
+		}
+		// failures are not cached, so there is nothing to unload
+		assert!(instances.load(path).is_err());
+		assert!(!instances.unload(path));
+	}
+
+	#[test]
+	fn revert_keeps_gas_and_data() {
+		match into_vm_result(evmc_types::StatusCode::EVMC_REVERT, 42, b"nope") {
+			Ok(GasLeft::NeedsReturn {
+				gas_left,
+				data,
+				apply_state,
+			}) => {
+				assert_eq!(gas_left, U256::from(42));
+				assert_eq!(&*data, b"nope");
+				assert!(!apply_state);
+			}
+			other => panic!("expected a revert, got {:?}", other),
+		}
+	}
+
+	#[test]
+	fn failures_map_to_vm_errors() {
+		use super::evmc_types::StatusCode::*;
+
+		assert_eq!(
+			into_vm_result(EVMC_OUT_OF_GAS, 0, &[]).unwrap_err(),
+			Error::OutOfGas
+		);
+		assert_eq!(
+			into_vm_result(EVMC_UNDEFINED_INSTRUCTION, 0, &[]).unwrap_err(),
+			Error::BadInstruction {
+				instruction: INVALID_INSTRUCTION
+			}
+		);
+		assert_eq!(
+			into_vm_result(EVMC_STATIC_MODE_VIOLATION, 0, &[]).unwrap_err(),
+			Error::MutableCallInStaticContext
+		);
+		match into_vm_result(EVMC_WASM_UNREACHABLE_INSTRUCTION, 0, &[]) {
+			Err(Error::WasmTrap(trap)) => {
+				assert_eq!(trap.kind, "Unreachable");
+				assert!(trap.backtrace.is_empty());
+			}
+			other => panic!("expected a wasm trap, got {:?}", other),
+		}
+		match into_vm_result(EVMC_INVALID_MEMORY_ACCESS, 0, &[]) {
+			Err(Error::WasmTrap(trap)) => assert_eq!(trap.kind, "MemoryAccessOutOfBounds"),
+			other => panic!("expected a wasm trap, got {:?}", other),
+		}
+		match into_vm_result(EVMC_BAD_JUMP_DESTINATION, 0, &[]) {
+			Err(Error::Wasm(msg)) => assert!(msg.contains("EVMC_BAD_JUMP_DESTINATION")),
+			other => panic!("expected a wasm error, got {:?}", other),
+		}
+		match into_vm_result(EVMC_REJECTED, 0, &[]) {
+			Err(Error::Internal(msg)) => assert!(msg.contains("EVMC_REJECTED")),
+			other => panic!("expected an internal error, got {:?}", other),
+		}
+	}
+
+	fn host_context(ext: &mut FakeExt) -> HostContext {
+		host_context_with_gas(ext, U256::from(1_000_000))
+	}
+
+	fn host_context_with_gas(ext: &mut FakeExt, gas_limit: U256) -> HostContext {
+		HostContext::new(
+			RuntimeContext {
+				address: Address::zero(),
+				origin: Address::zero(),
+				gas_price: U256::zero(),
+			},
+			ext,
+			gas_limit,
+		)
+	}
+
+	#[test]
+	fn code_hash() {
+		let with_code = Address::from(1);
+		let without_code = Address::from(2);
+		let missing = Address::from(3);
+		let mut ext = FakeExt::new();
+		ext.codes.insert(with_code, Arc::new(b"code".to_vec()));
+		ext.balances.insert(without_code, U256::from(1));
+
+		let mut host = host_context(&mut ext);
+		assert_eq!(
+			H256::from(host.get_code_hash(&with_code.into())),
+			keccak(b"code")
+		);
+		assert_eq!(
+			H256::from(host.get_code_hash(&without_code.into())),
+			KECCAK_EMPTY
+		);
+		assert!(H256::from(host.get_code_hash(&missing.into())).is_zero());
+	}
+
+	#[test]
+	fn code_size() {
+		let address = Address::from(1);
+		let mut ext = FakeExt::new();
+		ext.codes.insert(address, Arc::new(vec![0u8; 7]));
+
+		let mut host = host_context(&mut ext);
+		assert_eq!(host.get_code_size(&address.into()), 7);
+		assert_eq!(host.get_code_size(&Address::from(2).into()), 0);
+	}
+
+	#[test]
+	fn block_hash_of_negative_height_is_zero() {
+		let mut ext = FakeExt::new();
+		ext.blockhashes.insert(U256::from(1), H256::from(7));
+
+		let mut host = host_context(&mut ext);
+		assert_eq!(H256::from(host.get_block_hash(1)), H256::from(7));
+		assert!(H256::from(host.get_block_hash(-1)).is_zero());
+		assert!(H256::from(host.get_block_hash(i64::min_value())).is_zero());
+	}
+
+	#[test]
+	fn copy_code_is_clamped() {
+		let address = Address::from(1);
+		let mut ext = FakeExt::new();
+		ext.codes.insert(address, Arc::new(b"0123456789".to_vec()));
+		let mut host = host_context(&mut ext);
+
+		let copy = |host: &mut HostContext, offset: usize, size: usize| {
+			let mut buffer = vec![0xffu8; size];
+			let copied = host.copy_code(&address.into(), &offset, &buffer.as_mut_ptr(), &size);
+			(copied, buffer)
+		};
+
+		// whole code into a larger buffer leaves the tail untouched
+		let (copied, buffer) = copy(&mut host, 0, 12);
+		assert_eq!(copied, 10);
+		assert_eq!(&buffer[..10], b"0123456789");
+		assert_eq!(&buffer[10..], &[0xff, 0xff]);
+
+		// copy from the middle, limited by the buffer
+		let (copied, buffer) = copy(&mut host, 3, 4);
+		assert_eq!(copied, 4);
+		assert_eq!(&buffer[..], b"3456");
+
+		// copy running past the end of the code
+		let (copied, buffer) = copy(&mut host, 8, 4);
+		assert_eq!(copied, 2);
+		assert_eq!(&buffer[..2], b"89");
+
+		// offset at or past the end of the code copies nothing
+		assert_eq!(copy(&mut host, 10, 4).0, 0);
+		assert_eq!(copy(&mut host, usize::max_value(), 4).0, 0);
+	}
+
+	#[test]
+	fn create_forwards_init_code_and_value() {
+		let sender = Address::from(1);
+		let mut ext = FakeExt::new();
+		ext.balances.insert(sender, U256::from(100));
+
+		for kind in &[
+			evmc_types::CallKind::EVMC_CREATE,
+			evmc_types::CallKind::EVMC_CREATE2,
+		] {
+			let (output, gas_left, _, status) = host_context(&mut ext).call(
+				*kind,
+				&[0u8; evmc_types::ADDRESS_LENGTH],
+				&sender.into(),
+				&U256::from(10).into(),
+				b"init code",
+				1000,
+				0,
+				false,
+				&[7u8; 32],
+			);
+			// `FakeExt` fails every create, which consumes all of the gas
+			assert_eq!(status, evmc_types::StatusCode::EVMC_FAILURE);
+			assert_eq!(gas_left, 0);
+			assert!(output.is_empty());
+		}
+
+		let created = ext.calls.iter().next().unwrap();
+		assert_eq!(created.call_type, FakeCallType::Create);
+		assert_eq!(created.data, b"init code".to_vec());
+		assert_eq!(created.value, Some(U256::from(10)));
+		assert_eq!(created.gas, U256::from(1000));
+	}
+
+	#[test]
+	fn create_requires_endowment() {
+		let sender = Address::from(1);
+		let mut ext = FakeExt::new();
+		ext.balances.insert(sender, U256::from(5));
+
+		let (_, gas_left, _, status) = host_context(&mut ext).call(
+			evmc_types::CallKind::EVMC_CREATE,
+			&[0u8; evmc_types::ADDRESS_LENGTH],
+			&sender.into(),
+			&U256::from(10).into(),
+			b"init code",
+			1000,
+			0,
+			false,
+			&[0u8; 32],
+		);
+		assert_eq!(status, evmc_types::StatusCode::EVMC_FAILURE);
+		assert_eq!(gas_left, 1000);
+		assert!(ext.calls.is_empty());
+	}
+
+	fn host_extension(
+		ext: &mut FakeExt,
+		operation: HostExtension,
+		args: &[u8],
+	) -> (Vec<u8>, i64, evmc_types::StatusCode) {
+		let mut input = vec![operation as u8];
+		input.extend_from_slice(args);
+		let (output, gas_left, _, status) = host_context(ext).call(
+			evmc_types::CallKind::EVMC_CALL,
+			&HOST_EXTENSION_ADDRESS,
+			&Address::zero().into(),
+			&U256::zero().into(),
+			&input,
+			1_000_000_000,
+			1,
+			false,
+			&[0u8; 32],
+		);
+		(output, gas_left, status)
+	}
+
+	#[test]
+	fn host_extension_storage_bytes() {
+		let key = H256::from(7);
+		let mut ext = FakeExt::new().with_wasm();
+
+		let mut args = key.to_vec();
+		args.extend_from_slice(b"a value longer than a storage word");
+		let (output, gas_left, status) =
+			host_extension(&mut ext, HostExtension::SetStorageBytes, &args);
+		assert_eq!(status, evmc_types::StatusCode::EVMC_SUCCESS);
+		assert!(output.is_empty());
+		assert!(gas_left < 1_000_000_000);
+
+		let (output, _, _) = host_extension(&mut ext, HostExtension::StorageBytesAt, &key);
+		assert_eq!(&output[..], &b"a value longer than a storage word"[..]);
+		let (output, _, _) = host_extension(&mut ext, HostExtension::StorageBytesLen, &key);
+		assert_eq!(U256::from(&output[..]), U256::from(34));
+
+		// writes are rejected in a static context
+		ext.is_static = true;
+		let (_, _, status) = host_extension(&mut ext, HostExtension::SetStorageBytes, &args);
+		assert_eq!(status, evmc_types::StatusCode::EVMC_STATIC_MODE_VIOLATION);
+	}
+
+	#[test]
+	fn host_extension_clears_accrue_refunds() {
+		let key = H256::from(7);
+		let mut ext = FakeExt::new().with_wasm();
+		ext.store.insert(key.to_vec(), b"value".to_vec());
+
+		let (_, _, status) = host_extension(&mut ext, HostExtension::SetStorageBytes, &key);
+		assert_eq!(status, evmc_types::StatusCode::EVMC_SUCCESS);
+		assert_eq!(ext.sstore_clears, 1);
+		assert_eq!(ext.storage_bytes_len(&key).unwrap(), 0);
+	}
+
+	#[test]
+	fn set_storage_is_priced_by_size_and_expiry() {
+		let address = [0u8; evmc_types::ADDRESS_LENGTH];
+		let key = [1u8; 32];
+		let mut ext = FakeExt::new().with_wasm();
+		// storage kept for twice the default duration costs twice the EVM price, half
+		// of which the runtime charges itself
+		ext.schedule.default_storage_duration = 21;
+		let scale = wasm_gas_scale(&ext.schedule).unwrap();
+		let set_gas = to_wasm_gas(scale, ext.schedule.prorated_sstore_set_gas(42, 32))
+			- U256::from(EVMC_SSTORE_SET_GAS);
+		let reset_gas = to_wasm_gas(scale, ext.schedule.prorated_sstore_reset_gas(42, 32))
+			- U256::from(EVMC_SSTORE_RESET_GAS);
+		assert_eq!(set_gas, U256::from(ext.schedule.sstore_set_gas));
+
+		let mut host = host_context(&mut ext);
+		assert_eq!(
+			host.set_storage(&address, &key, &H256::from(1).into()),
+			evmc_types::StorageStatus::EVMC_STORAGE_ADDED
+		);
+		assert_eq!(host.storage_gas, set_gas);
+		assert_eq!(
+			host.set_storage(&address, &key, &H256::from(1).into()),
+			evmc_types::StorageStatus::EVMC_STORAGE_UNCHANGED
+		);
+		assert_eq!(host.storage_gas, set_gas);
+		assert_eq!(
+			host.set_storage(&address, &key, &H256::zero().into()),
+			evmc_types::StorageStatus::EVMC_STORAGE_DELETED
+		);
+		assert_eq!(host.storage_gas, set_gas + reset_gas);
+		assert_eq!(
+			host.spent_gas,
+			U256::from(EVMC_SSTORE_SET_GAS + EVMC_SSTORE_RESET_GAS)
+		);
+		assert!(host.error.is_none());
+		assert_eq!(ext.sstore_clears, 1);
+	}
+
+	#[test]
+	fn set_storage_counts_what_the_runtime_charged() {
+		let address = [0u8; evmc_types::ADDRESS_LENGTH];
+		let mut ext = FakeExt::new().with_wasm();
+		ext.schedule.default_storage_duration = 21;
+		let set_gas = ext.schedule.sstore_set_gas as u64 + EVMC_SSTORE_SET_GAS;
+
+		// enough for the extra charge alone, but not once the runtime's is counted
+		let mut host = host_context_with_gas(&mut ext, U256::from(set_gas - 1));
+		host.set_storage(&address, &[1u8; 32], &H256::from(1).into());
+		assert_eq!(host.error, Some(Error::OutOfGas));
+
+		let mut host = host_context_with_gas(&mut ext, U256::from(set_gas));
+		host.set_storage(&address, &[1u8; 32], &H256::from(1).into());
+		assert!(host.error.is_none());
+	}
+
+	#[test]
+	fn set_storage_fails_once_out_of_gas() {
+		let address = [0u8; evmc_types::ADDRESS_LENGTH];
+		let key = [1u8; 32];
+		let mut ext = FakeExt::new().with_wasm();
+		ext.schedule.default_storage_duration = 21;
+
+		let mut host = host_context_with_gas(&mut ext, U256::from(100));
+		host.set_storage(&address, &key, &H256::from(1).into());
+		assert_eq!(host.error, Some(Error::OutOfGas));
+		let (_, gas_left, _, status) = host.call(
+			evmc_types::CallKind::EVMC_CALL,
+			&Address::from(0xc0de).into(),
+			&Address::zero().into(),
+			&U256::zero().into(),
+			b"input",
+			1000,
+			1,
+			false,
+			&[0u8; 32],
+		);
+		assert_eq!(status, evmc_types::StatusCode::EVMC_FAILURE);
+		assert_eq!(gas_left, 0);
+		assert!(ext.store.is_empty());
+		assert!(ext.calls.is_empty());
+	}
+
+	#[test]
+	fn storage_gas_is_paid_before_nested_calls() {
+		let address = [0u8; evmc_types::ADDRESS_LENGTH];
+		let key = [1u8; 32];
+		let mut ext = FakeExt::new().with_wasm();
+		ext.schedule.default_storage_duration = 21;
+		let storage_gas = ext.schedule.sstore_set_gas as i64;
+
+		let mut host = host_context(&mut ext);
+		host.set_storage(&address, &key, &H256::from(1).into());
+		host.call(
+			evmc_types::CallKind::EVMC_CALL,
+			&Address::from(0xc0de).into(),
+			&Address::zero().into(),
+			&U256::zero().into(),
+			b"input",
+			storage_gas + 1000,
+			1,
+			false,
+			&[0u8; 32],
+		);
+		assert_eq!(host.storage_gas, U256::zero());
+		assert!(host.error.is_none());
+		assert_eq!(ext.calls.iter().next().unwrap().gas, U256::from(1000));
+	}
+
+	#[test]
+	fn gas_is_scaled_like_wasmi() {
+		let mut schedule = Schedule::default();
+		assert!(wasm_gas_scale(&schedule).is_err());
+
+		schedule.wasm = Some(Default::default());
+		schedule.wasm.as_mut().unwrap().opcodes_mul = 3;
+		schedule.wasm.as_mut().unwrap().opcodes_div = 8;
+		let scale = wasm_gas_scale(&schedule).unwrap();
+
+		assert_eq!(to_wasm_gas(scale, U256::from(300)), U256::from(800));
+		assert_eq!(from_wasm_gas(scale, U256::from(800)), U256::from(300));
+	}
+
+	#[test]
+	fn host_extension_reads_are_charged() {
+		let key = H256::from(7);
+		let mut ext = FakeExt::new().with_wasm();
+		ext.set_storage_bytes(key, vec![1u8; 33]).unwrap();
+		let sload_gas = ext.schedule().sload_gas as i64;
+
+		// a lookup, then two words of data
+		let (_, gas_left, status) = host_extension(&mut ext, HostExtension::StorageBytesAt, &key);
+		assert_eq!(status, evmc_types::StatusCode::EVMC_SUCCESS);
+		assert_eq!(
+			gas_left,
+			1_000_000_000 - sload_gas - (sload_gas * 33 + 31) / 32
+		);
+
+		for &(operation, args) in &[
+			(HostExtension::StorageBytesLen, &key[..]),
+			(HostExtension::StorageExpiry, &Address::from(1)[..]),
+			(HostExtension::IsConfidentialContract, &Address::from(1)[..]),
+			(HostExtension::SecondsUntilExpiry, &[][..]),
+		] {
+			let (_, gas_left, status) = host_extension(&mut ext, operation, args);
+			assert_eq!(status, evmc_types::StatusCode::EVMC_SUCCESS);
+			assert_eq!(gas_left, 1_000_000_000 - sload_gas);
+		}
+
+		// the read is charged before the value is read
+		let mut input = vec![HostExtension::StorageBytesAt as u8];
+		input.extend_from_slice(&key);
+		let (output, gas_left, _, status) = host_context(&mut ext).call(
+			evmc_types::CallKind::EVMC_CALL,
+			&HOST_EXTENSION_ADDRESS,
+			&Address::zero().into(),
+			&U256::zero().into(),
+			&input,
+			sload_gas,
+			1,
+			false,
+			&[0u8; 32],
+		);
+		assert_eq!(status, evmc_types::StatusCode::EVMC_OUT_OF_GAS);
+		assert!(output.is_empty());
+		assert_eq!(gas_left, 0);
+	}
+
+	#[test]
+	fn host_extension_expiry() {
+		let mut ext = FakeExt::new().with_wasm();
+
+		let (output, _, status) = host_extension(&mut ext, HostExtension::SecondsUntilExpiry, &[]);
+		assert_eq!(status, evmc_types::StatusCode::EVMC_SUCCESS);
+		assert_eq!(U256::from(&output[..]), U256::from(42));
+
+		let (output, _, _) =
+			host_extension(&mut ext, HostExtension::StorageExpiry, &Address::from(1));
+		assert_eq!(U256::from(&output[..]), U256::from(u64::max_value()));
+	}
+
+	#[test]
+	fn host_extension_rejects_malformed_input() {
+		let mut ext = FakeExt::new();
+
+		for &(operation, args) in &[
+			(HostExtension::StorageBytesAt, &[0u8; 31][..]),
+			(HostExtension::SetStorageBytes, &[][..]),
+			(HostExtension::StorageExpiry, &[0u8; 32][..]),
+			(HostExtension::SecondsUntilExpiry, &[0u8][..]),
+		] {
+			let (_, gas_left, status) = host_extension(&mut ext, operation, args);
+			assert_eq!(status, evmc_types::StatusCode::EVMC_FAILURE);
+			assert_eq!(gas_left, 0);
+		}
+	}
+
+	#[test]
+	fn host_extension_rejects_value() {
+		let mut ext = FakeExt::new();
+
+		let (_, gas_left, _, status) = host_context(&mut ext).call(
+			evmc_types::CallKind::EVMC_CALL,
+			&HOST_EXTENSION_ADDRESS,
+			&Address::zero().into(),
+			&U256::from(1).into(),
+			&[HostExtension::SecondsUntilExpiry as u8],
+			1000,
+			1,
+			false,
+			&[0u8; 32],
+		);
+		assert_eq!(status, evmc_types::StatusCode::EVMC_FAILURE);
+		assert_eq!(gas_left, 0);
+	}
+
+	fn call_from(
+		ext: &mut FakeExt,
+		address: Address,
+		kind: evmc_types::CallKind,
+		is_static: bool,
+	) -> evmc_types::StatusCode {
+		let mut host = host_context(ext);
+		host.context.address = address;
+		let (_, _, _, status) = host.call(
+			kind,
+			&Address::from(0xc0de).into(),
+			&Address::from(0xca11).into(),
+			&U256::from(5).into(),
+			b"input",
+			1000,
+			1,
+			is_static,
+			&[0u8; 32],
+		);
+		status
+	}
+
+	#[test]
+	fn call_kinds_set_code_and_storage_addresses() {
+		let current = Address::from(0xa);
+		let code = Address::from(0xc0de);
+		let expectations = vec![
+			(
+				evmc_types::CallKind::EVMC_CALL,
+				false,
+				code,
+				Some(U256::from(5)),
+			),
+			(evmc_types::CallKind::EVMC_CALL, true, code, None),
+			(
+				evmc_types::CallKind::EVMC_CALLCODE,
+				false,
+				current,
+				Some(U256::from(5)),
+			),
+			(
+				evmc_types::CallKind::EVMC_DELEGATECALL,
+				false,
+				current,
+				None,
+			),
+		];
+
+		for (kind, is_static, receive_address, value) in expectations {
+			let mut ext = FakeExt::new();
+			ext.balances.insert(current, U256::from(100));
+			assert_eq!(
+				call_from(&mut ext, current, kind, is_static),
+				evmc_types::StatusCode::EVMC_SUCCESS
+			);
+
+			let call = ext.calls.iter().next().unwrap();
+			assert_eq!(call.sender_address, Some(Address::from(0xca11)));
+			assert_eq!(call.receive_address, Some(receive_address));
+			assert_eq!(call.code_address, Some(code));
+			assert_eq!(call.value, value);
+		}
+	}
+
+	#[test]
+	fn call_value_requires_balance() {
+		let current = Address::from(0xa);
+		let mut ext = FakeExt::new();
+		ext.balances.insert(current, U256::from(1));
+
+		assert_eq!(
+			call_from(&mut ext, current, evmc_types::CallKind::EVMC_CALL, false),
+			evmc_types::StatusCode::EVMC_FAILURE
+		);
+		assert!(ext.calls.is_empty());
+	}
+
+	#[test]
+	fn call_depth_is_limited() {
+		let mut ext = FakeExt::new();
+		ext.depth = ext.schedule.max_depth;
+
+		assert_eq!(
+			call_from(
+				&mut ext,
+				Address::zero(),
+				evmc_types::CallKind::EVMC_CALL,
+				true
+			),
+			evmc_types::StatusCode::EVMC_CALL_DEPTH_EXCEEDED
+		);
+		assert!(ext.calls.is_empty());
+	}
+
+	#[test]
+	fn tx_context_reports_block_author() {
+		let author = Address::from(0xb10c);
+		let mut ext = FakeExt::new();
+		ext.info.author = author;
+
+		let (_, _, coinbase, _, _, _, _) = host_context(&mut ext).get_tx_context();
+		assert_eq!(Address::from(coinbase), author);
+	}
+}
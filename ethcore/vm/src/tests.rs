@@ -19,17 +19,13 @@ use std::sync::Arc;
 
 use bytes::Bytes;
 use ethereum_types::{Address, H256, U256};
+use hash::{keccak, KECCAK_EMPTY};
+use types::log_entry::LogEntry;
 use {
-	CallType, ContractCreateResult, CreateContractAddress, EnvInfo, Ext, GasLeft,
-	MessageCallResult, Result, ReturnData, Schedule,
+	CallType, Comparison, ContractCreateResult, CreateContractAddress, EnvInfo, Error, Ext,
+	GasLeft, MessageCallResult, OasisContract, Result, ReturnData, Schedule,
 };
 
-#[derive(Debug)]
-pub struct FakeLogEntry {
-	pub topics: Vec<H256>,
-	pub data: Bytes,
-}
-
 #[derive(PartialEq, Eq, Hash, Debug)]
 pub enum FakeCallType {
 	Call,
@@ -49,7 +45,8 @@ pub struct FakeCall {
 
 /// Fake externalities test structure.
 ///
-/// Can't do recursive calls.
+/// Can't do recursive calls. A call to an address in `callee_logs` emits the
+/// logs found there, as if the callee had created them.
 #[derive(Default)]
 pub struct FakeExt {
 	pub store: HashMap<Vec<u8>, Vec<u8>>,
@@ -59,13 +56,16 @@ pub struct FakeExt {
 	pub depth: usize,
 	pub blockhashes: HashMap<U256, H256>,
 	pub codes: HashMap<Address, Arc<Bytes>>,
-	pub logs: Vec<FakeLogEntry>,
+	pub logs: Vec<LogEntry>,
+	pub callee_logs: HashMap<Address, Vec<LogEntry>>,
 	pub info: EnvInfo,
 	pub schedule: Schedule,
 	pub balances: HashMap<Address, U256>,
 	pub tracing: bool,
 	pub is_static: bool,
 	pub is_create: bool,
+	pub wasm_output: Vec<(u32, Bytes)>,
+	pub comparisons: Vec<Comparison>,
 }
 
 // similar to the normal `finalize` function, but ignoring NeedsReturn.
@@ -141,14 +141,17 @@ impl Ext for FakeExt {
 	}
 
 	fn storage_bytes_at(&self, key: &H256) -> Result<Vec<u8>> {
-		Ok(Vec::new())
+		let key: &[u8] = key.as_ref();
+		Ok(self.store.get(key).cloned().unwrap_or_default())
 	}
 
 	fn storage_bytes_len(&self, key: &H256) -> Result<u64> {
-		Ok(0)
+		let key: &[u8] = key.as_ref();
+		Ok(self.store.get(key).map_or(0, |value| value.len() as u64))
 	}
 
 	fn set_storage_bytes(&mut self, key: H256, value: Vec<u8>) -> Result<()> {
+		self.store.insert(key.to_vec(), value);
 		Ok(())
 	}
 
@@ -177,7 +180,7 @@ impl Ext for FakeExt {
 	}
 
 	fn balance(&self, address: &Address) -> Result<U256> {
-		Ok(self.balances[address])
+		Ok(self.balances.get(address).cloned().unwrap_or_default())
 	}
 
 	fn blockhash(&mut self, number: &U256) -> H256 {
@@ -223,6 +226,9 @@ impl Ext for FakeExt {
 			data: data.to_vec(),
 			code_address: Some(code_address.clone()),
 		});
+		if let Some(logs) = self.callee_logs.get(receive_address) {
+			self.logs.extend(logs.iter().cloned());
+		}
 		MessageCallResult::Success(*gas, ReturnData::empty())
 	}
 
@@ -238,14 +244,27 @@ impl Ext for FakeExt {
 		Ok(self.codes.get(address).map_or(0, |c| c.len()))
 	}
 
+	fn extcodehash(&self, address: &Address) -> Result<Option<H256>> {
+		Ok(match self.codes.get(address) {
+			Some(code) => Some(keccak(code.as_slice())),
+			None if self.balances.contains_key(address) => Some(KECCAK_EMPTY),
+			None => None,
+		})
+	}
+
 	fn log(&mut self, topics: Vec<H256>, data: &[u8]) -> Result<()> {
-		self.logs.push(FakeLogEntry {
+		self.logs.push(LogEntry {
+			address: Address::default(),
 			topics: topics,
 			data: data.to_vec(),
 		});
 		Ok(())
 	}
 
+	fn logs(&self) -> &[LogEntry] {
+		&self.logs
+	}
+
 	fn ret(self, _gas: &U256, _data: &ReturnData, _apply_state: bool) -> Result<U256> {
 		unimplemented!();
 	}
@@ -267,6 +286,18 @@ impl Ext for FakeExt {
 		self.depth
 	}
 
+	fn trace_wasm_output(&mut self, fd: u32, data: &[u8]) {
+		self.wasm_output.push((fd, data.to_vec()));
+	}
+
+	fn traces_wasm_output(&self) -> bool {
+		true
+	}
+
+	fn trace_comparison(&mut self, comparison: &Comparison) {
+		self.comparisons.push(comparison.clone());
+	}
+
 	fn is_static(&self) -> bool {
 		self.is_static
 	}
@@ -285,7 +316,16 @@ impl Ext for FakeExt {
 	}
 
 	fn is_confidential_contract(&self, contract: &Address) -> Result<bool> {
-		Ok(false)
+		Ok(self
+			.oasis_contract(contract)?
+			.map_or(false, |contract| contract.confidential))
+	}
+
+	fn oasis_contract(&self, contract: &Address) -> Result<Option<OasisContract>> {
+		match self.codes.get(contract) {
+			Some(code) => OasisContract::from_code(code).map_err(Error::Confidential),
+			None => Ok(None),
+		}
 	}
 
 	fn as_kvstore(&self) -> &dyn blockchain_traits::KVStore {
@@ -68,8 +68,8 @@ pub fn wasm_exports(
 					WasmType::I32 => quote!(I32),
 					WasmType::I64 => quote!(I64),
 				});
-			// these are special cased since `gas` returns nothing and `proc_exit` traps
-			let ret_ty = if sig.ident == "gas" || sig.ident == "proc_exit" {
+			// these are special cased since they return nothing, or trap in the case of `proc_exit`
+			let ret_ty = if returns_nothing(&sig.ident) {
 				quote! { None }
 			} else {
 				quote! { Some(I32) } // otherwise, WASI returns errno_t (u32)
@@ -93,10 +93,11 @@ pub fn wasm_exports(
 				quote! { args.nth_checked(#i)? }
 			});
 			// special cased for the same reason as described above
-			if f_ident == "gas" || f_ident == "proc_exit" {
+			if returns_nothing(f_ident) {
 				quote! {
 					ids::#id_ident => {
 						self.#f_ident(#(#args),*)?;
+						self.check_value_overflow()?;
 						Ok(None)
 					}
 				}
@@ -105,20 +106,48 @@ pub fn wasm_exports(
 				ids::#id_ident => {
 					debug!(target: "wasm",
 						   "runtime: call {}", stringify!(#f_ident));
-					Ok(Some((self.#f_ident(#(#args),*)? as u16).into()))
+					let gas_before = self.gas_counter;
+					let errno = self.#f_ident(#(#args),*);
+					if let Some(profile) = &mut self.profile {
+						profile.host_call(stringify!(#f_ident), self.gas_counter - gas_before);
+					}
+					// a value read through `PendingTransaction` may not have fit
+					self.check_value_overflow()?;
+					Ok(Some((errno? as u16).into()))
 				}
 				}
 			}
 		});
 
+	let legacy_names = export_fn_sigs
+		.iter()
+		.filter(|sig| is_legacy(&sig.ident))
+		.map(|sig| import_name(&sig.ident))
+		.collect::<Vec<_>>();
+
 	let resolver_arms = export_fn_sigs
 		.iter()
 		.zip(id_idents.iter().zip(wsig_idents.iter()))
 		.map(|(f, (id_ident, sig_ident))| {
 			let f_ident = &f.ident;
+			let module = import_module(f_ident);
+			let name = import_name(f_ident);
+			let condition = if f_ident == "oasis_enter" || f_ident == "oasis_leave" {
+				// the functions of the call tracer are only resolved for modules it instrumented
+				quote! { && self.resolver.call_tracer }
+			} else if is_legacy(f_ident) {
+				// legacy versions are resolved until a schedule flag replaces them
+				let flag = replacing_flag(&name);
+				quote! { && !self.resolver.#flag }
+			} else if legacy_names.contains(&name) {
+				let flag = replacing_flag(&name);
+				quote! { && self.resolver.#flag }
+			} else {
+				quote! {}
+			};
 			// generates https://github.com/paritytech/parity-ethereum/blob/dae5d75d/ethcore/wasm/src/env.rs#L257
 			quote! {
-				stringify!(#f_ident) => {
+				#name if (!self.resolver.module_scoped || self.name == #module) #condition => {
 					crate::env::host(signatures::#sig_ident, ids::#id_ident)
 				}
 			}
@@ -139,8 +168,8 @@ pub fn wasm_exports(
 				#(#signatures)*
 			}
 
-			const _impl_ModuleImportResolver_for_ImportResolver: () = {
-				impl wasmi::ModuleImportResolver for crate::env::ImportResolver {
+			const _impl_ModuleImportResolver_for_ModuleResolver: () = {
+				impl<'a> wasmi::ModuleImportResolver for crate::env::ModuleResolver<'a> {
 					fn resolve_func(
 						&self,
 						field_name: &str,
@@ -150,7 +179,8 @@ pub fn wasm_exports(
 							#(#resolver_arms)*
 							_ => {
 								return Err(wasmi::Error::Instantiation(format!(
-									"Export {} not found ({:?})",
+									"Export {}.{} not found ({:?})",
+									self.name,
 									field_name,
 									_signature,
 								)))
@@ -164,25 +194,26 @@ pub fn wasm_exports(
 						descriptor: &wasmi::MemoryDescriptor,
 					) -> Result<wasmi::MemoryRef, wasmi::Error> {
 						if field_name == "memory" {
+							let resolver = self.resolver;
 							let effective_max = descriptor
 								.maximum()
-								.unwrap_or(self.max_memory);
-							if descriptor.initial() > self.max_memory ||
-								effective_max > self.max_memory {
+								.unwrap_or(resolver.max_memory);
+							if descriptor.initial() > resolver.max_initial_memory ||
+								effective_max > resolver.max_memory {
 								Err(wasmi::Error::Instantiation(format!(
-									"Module requested too much memory: initial={}, effective={}, max={}",
+									"Module requested too much memory: initial={}, effective={}, max initial={}, max={}",
 									descriptor.initial(),
 									effective_max,
-									self.max_memory
+									resolver.max_initial_memory,
+									resolver.max_memory
 								)))
 							} else {
 								let mem = wasmi::MemoryInstance::alloc(
 									wasmi::memory_units::Pages(descriptor.initial() as usize),
-									descriptor
-										.maximum()
+									resolver.memory_maximum(descriptor)
 										.map(|x| wasmi::memory_units::Pages(x as usize)),
 								)?;
-								*self.memory.borrow_mut() = Some(mem.clone());
+								*resolver.memory.borrow_mut() = Some(mem.clone());
 								Ok(mem)
 							}
 						} else {
@@ -212,6 +243,49 @@ pub fn wasm_exports(
 	})
 }
 
+/// Whether the export is not a WASI function returning an errno.
+fn returns_nothing(ident: &syn::Ident) -> bool {
+	ident == "gas" || ident == "proc_exit" || ident == "oasis_enter" || ident == "oasis_leave"
+}
+
+/// Whether the export is the legacy version of another, named like it with a
+/// `_legacy` suffix, which has since changed signature.
+fn is_legacy(ident: &syn::Ident) -> bool {
+	ident.to_string().ends_with("_legacy")
+}
+
+/// The flag of the import resolver, set from the schedule flag of the same name,
+/// from which the legacy version of `name` is replaced.
+fn replacing_flag(name: &str) -> syn::Ident {
+	let flag = match name {
+		"path_readlink" => "wasi_dirs",
+		"poll_oneoff" => "wasi_clocks",
+		_ => panic!("No schedule flag replaces the legacy version of {}", name),
+	};
+	syn::Ident::new(flag, proc_macro2::Span::call_site())
+}
+
+/// The name the export is provided under, which for a legacy version is that of
+/// the function it was replaced by.
+fn import_name(ident: &syn::Ident) -> String {
+	let name = ident.to_string();
+	if is_legacy(ident) {
+		name[..name.len() - "_legacy".len()].to_owned()
+	} else {
+		name
+	}
+}
+
+/// The module the export is provided under: `env` for those which are not part
+/// of WASI, `wasi_unstable` otherwise.
+fn import_module(ident: &syn::Ident) -> &'static str {
+	if ident == "gas" || ident == "oasis_enter" || ident == "oasis_leave" {
+		"env"
+	} else {
+		"wasi_unstable"
+	}
+}
+
 enum WasmType {
 	I32,
 	I64,
@@ -26,7 +26,7 @@ macro_rules! load_sample {
 }
 
 fn wasm_interpreter() -> WasmInterpreter {
-	WasmInterpreter
+	WasmInterpreter::default()
 }
 
 /// Do everything but the contract call itself, used for testing microbenchmarks
@@ -44,8 +44,11 @@ fn prepare_module(params: ActionParams, ext: &mut vm::Ext) -> (Runtime, wasmi::M
 	let module_instance = wasmi::ModuleInstance::new(
 		&loaded_module,
 		&wasmi::ImportsBuilder::new()
-			.with_resolver("env", &instantiation_resolver)
-			.with_resolver("wasi_unstable", &instantiation_resolver),
+			.with_resolver("env", &instantiation_resolver.module("env"))
+			.with_resolver(
+				"wasi_unstable",
+				&instantiation_resolver.module("wasi_unstable"),
+			),
 	)
 	.map_err(Error::Interpreter)
 	.unwrap();
@@ -69,8 +72,9 @@ fn prepare_module(params: ActionParams, ext: &mut vm::Ext) -> (Runtime, wasmi::M
 			sender: params.sender,
 			origin: params.origin,
 			code_address: params.code_address,
+			call_type: params.call_type,
+			gas_price: params.gas_price,
 			value: params.value.value(),
-			value_str: params.value.value().as_u64().to_string(),
 			aad_str: params.aad.as_ref().map(base64::encode).unwrap_or_default(),
 		},
 	);
@@ -1,37 +1,141 @@
+//! Profiles the gas usage of a contract.
+//!
+//! Usage: gastimate <contract.wasm> [--data <bytes>] [--storage <fixture.json>] [--gas <n>] [--create]
+//!
+//! The storage fixture is a JSON object mapping keys to values with which the
+//! storage is seeded. The call data and the strings of the fixture are hex if
+//! they start with `0x`, and taken verbatim otherwise.
+//! The profile is printed to stdout as JSON, also when the execution fails.
+
+use std::collections::BTreeMap;
 use std::sync::Arc;
 
-use ethereum_types::{Address, U256};
-use vm::{tests::FakeExt, ActionParams, GasLeft, Vm as _};
-use wasm::WasmInterpreter;
+use ethereum_types::U256;
+use rustc_hex::FromHex;
+use serde::Serialize;
+use vm::{tests::FakeExt, ActionParams, GasLeft};
+use wasm::{Profile, WasmInterpreter};
 
+const USAGE: &str =
+	"usage: gastimate <contract.wasm> [--data <bytes>] [--storage <fixture.json>] [--gas <n>] [--create]";
 const INIT_GAS: u64 = 5_000_000;
-const NUM_TRIALS: usize = 10;
 
-fn main() {
-	let code = Arc::new(std::fs::read(std::env::args().nth(1).unwrap()).unwrap());
-
-	let gases_used = (0..NUM_TRIALS)
-		.map(|_| {
-			let mut ext = FakeExt::new().with_wasm();
-			let mut params = ActionParams::default();
-			params.gas = U256::from(INIT_GAS);
-			params.code = Some(Arc::clone(&code));
-
-			let gas_used = match WasmInterpreter.exec(params, &mut ext).unwrap() {
-				GasLeft::Known(gas_left) => gas_left,
-				GasLeft::NeedsReturn { gas_left, .. } => gas_left,
-			};
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Report {
+	gas_limit: u64,
+	gas_used: u64,
+	success: bool,
+	error: Option<String>,
+	profile: Profile,
+}
+
+struct Options {
+	code: Vec<u8>,
+	data: Vec<u8>,
+	storage: BTreeMap<String, String>,
+	gas: u64,
+	create: bool,
+}
+
+fn bytes(s: &str) -> Result<Vec<u8>, String> {
+	if s.starts_with("0x") {
+		s[2..]
+			.from_hex()
+			.map_err(|e| format!("invalid hex `{}`: {}", s, e))
+	} else {
+		Ok(s.as_bytes().to_vec())
+	}
+}
 
-			(INIT_GAS - gas_used.low_u64()) as f64
-		})
-		.collect::<Vec<f64>>();
+fn parse_options() -> Result<Options, String> {
+	let mut args = std::env::args().skip(1);
+	let mut options = Options {
+		code: Vec::new(),
+		data: Vec::new(),
+		storage: BTreeMap::new(),
+		gas: INIT_GAS,
+		create: false,
+	};
+	let mut code_path = None;
 
-	let avg_gas_used = gases_used.iter().sum::<f64>() / (gases_used.len() as f64);
-	let gas_used_std = gases_used
-		.into_iter()
-		.map(|g| (g - avg_gas_used).powf(2.0))
-		.sum::<f64>()
-		.sqrt();
+	while let Some(arg) = args.next() {
+		let mut value = || {
+			args.next()
+				.ok_or_else(|| format!("{} expects a value", arg))
+		};
+		match arg.as_str() {
+			"--data" => options.data = bytes(&value()?)?,
+			"--storage" => {
+				let path = value()?;
+				let fixture = std::fs::read(&path).map_err(|e| format!("{}: {}", path, e))?;
+				options.storage = serde_json::from_slice(&fixture)
+					.map_err(|e| format!("invalid storage fixture {}: {}", path, e))?;
+			}
+			"--gas" => {
+				options.gas = value()?
+					.parse()
+					.map_err(|e| format!("invalid gas: {}", e))?;
+			}
+			"--create" => options.create = true,
+			_ if code_path.is_none() && !arg.starts_with("--") => code_path = Some(arg),
+			_ => return Err(USAGE.to_owned()),
+		}
+	}
 
-	println!("{:.1} ({:.1})", avg_gas_used, gas_used_std);
+	let code_path = code_path.ok_or_else(|| USAGE.to_owned())?;
+	options.code = std::fs::read(&code_path).map_err(|e| format!("{}: {}", code_path, e))?;
+	Ok(options)
+}
+
+fn run(options: Options) -> Result<Report, String> {
+	let mut ext = FakeExt::new().with_wasm();
+	ext.is_create = options.create;
+	for (key, value) in &options.storage {
+		ext.store.insert(bytes(key)?, bytes(value)?);
+	}
+
+	let mut params = ActionParams::default();
+	params.gas = U256::from(options.gas);
+	params.code = Some(Arc::new(options.code));
+	params.data = Some(options.data);
+
+	let (result, profile) = WasmInterpreter::default().profile(params, &mut ext);
+	let mut report = Report {
+		gas_limit: options.gas,
+		gas_used: options.gas,
+		success: false,
+		error: None,
+		profile,
+	};
+	match result {
+		Ok(gas_left) => {
+			let (gas_left, success) = match gas_left {
+				GasLeft::Known(gas_left) => (gas_left, true),
+				GasLeft::NeedsReturn {
+					gas_left,
+					apply_state,
+					..
+				} => (gas_left, apply_state),
+			};
+			report.gas_used = options.gas - gas_left.low_u64();
+			report.success = success;
+		}
+		Err(e) => report.error = Some(e.to_string()),
+	}
+	Ok(report)
+}
+
+fn main() {
+	let report = match parse_options().and_then(run) {
+		Ok(report) => report,
+		Err(e) => {
+			eprintln!("{}", e);
+			std::process::exit(1);
+		}
+	};
+	println!(
+		"{}",
+		serde_json::to_string_pretty(&report).expect("report is serializable; qed")
+	);
 }
@@ -0,0 +1,110 @@
+//! Cache of compiled wasm modules
+
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+
+use ethereum_types::H256;
+use hash::keccak;
+use heapsize::HeapSizeOf;
+use memory_cache::MemoryLruCache;
+use vm::WasmCosts;
+
+const DEFAULT_CACHE_SIZE: usize = 4 * 1024 * 1024;
+
+/// A module compiled for execution by wasmi.
+struct CompiledModule {
+	module: Arc<wasmi::Module>,
+	/// Size of the contract code the module was compiled from.
+	code_size: usize,
+}
+
+// wasmi does not report the size of its modules, so this is an estimate rather than
+// a measurement: the instruction stream grows with the code a module was compiled
+// from, and doubling the code size roughly allows for the injected gas metering and
+// for wasmi's own representation. The cache may thus hold somewhat more or less
+// than its `max_size` of modules.
+impl HeapSizeOf for CompiledModule {
+	fn heap_size_of_children(&self) -> usize {
+		self.code_size * 2
+	}
+}
+
+/// Cache of validated and instrumented modules, shared between interpreters.
+///
+/// Modules are keyed by code hash and by the `fingerprint` of the `WasmCosts` they
+/// were instrumented for, since the injected gas counter and stack limiter depend
+/// on the schedule, which includes the module policy. A cache whose lock was
+/// poisoned by a panic is no longer used.
+pub struct ModuleCache {
+	modules: Mutex<MemoryLruCache<(H256, H256), CompiledModule>>,
+}
+
+impl ModuleCache {
+	/// Creates a module cache using about `max_size` bytes, see `CompiledModule`.
+	pub fn new(max_size: usize) -> Self {
+		ModuleCache {
+			modules: Mutex::new(MemoryLruCache::new(max_size)),
+		}
+	}
+
+	/// Returns the module for `code_hash` compiled with the costs of `fingerprint`,
+	/// if cached.
+	pub(crate) fn get(&self, code_hash: &H256, fingerprint: &H256) -> Option<Arc<wasmi::Module>> {
+		let mut modules = self.modules.lock().ok()?;
+		modules
+			.get_mut(&(*code_hash, *fingerprint))
+			.map(|compiled| compiled.module.clone())
+	}
+
+	/// Caches `module`, compiled from `code_size` bytes of code with the costs of
+	/// `fingerprint`.
+	pub(crate) fn insert(
+		&self,
+		code_hash: H256,
+		fingerprint: H256,
+		module: Arc<wasmi::Module>,
+		code_size: usize,
+	) {
+		if let Ok(mut modules) = self.modules.lock() {
+			modules.insert(
+				(code_hash, fingerprint),
+				CompiledModule { module, code_size },
+			);
+		}
+	}
+
+	/// Currently-used size of the cached modules in bytes.
+	pub fn current_size(&self) -> usize {
+		self.modules
+			.lock()
+			.map(|modules| modules.current_size())
+			.unwrap_or(0)
+	}
+}
+
+impl Default for ModuleCache {
+	fn default() -> Self {
+		ModuleCache::new(DEFAULT_CACHE_SIZE)
+	}
+}
+
+/// Hashes `wasm_costs` into a key for the cache. Computed once per compilation, so
+/// that lookups neither clone nor rehash the costs and their import policy.
+pub(crate) fn fingerprint(wasm_costs: &WasmCosts) -> H256 {
+	let mut hasher = Fingerprinter(Vec::new());
+	wasm_costs.hash(&mut hasher);
+	keccak(&hasher.0)
+}
+
+/// Collects what a `Hash` implementation feeds it, to be hashed with keccak.
+struct Fingerprinter(Vec<u8>);
+
+impl Hasher for Fingerprinter {
+	fn write(&mut self, bytes: &[u8]) {
+		self.0.extend_from_slice(bytes);
+	}
+
+	fn finish(&self) -> u64 {
+		unreachable!("only the collected bytes are hashed")
+	}
+}
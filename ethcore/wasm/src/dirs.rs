@@ -0,0 +1,444 @@
+//! Directories and symbolic links of a contract's BCFS namespace.
+//!
+//! BCFS stores each file of the current account under its path relative to the
+//! home directory, which is flat. On top of it, a directory is stored as the
+//! listing of its entries under its path prefixed with `DIR_PREFIX`, and a
+//! symbolic link as its target under its path prefixed with `LINK_PREFIX`.
+//! Paths here are relative to the home directory, which always exists and whose
+//! path is the empty string.
+
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::path::{Component, Path};
+
+use blockchain_traits::KVStore;
+use wasi_types::{ErrNo, Fd};
+
+/// Prefix of the keys holding the listing of each directory.
+const DIR_PREFIX: &[u8] = b"\0dir\0";
+/// Prefix of the keys holding the target of each symbolic link.
+const LINK_PREFIX: &[u8] = b"\0link\0";
+/// Heads every listing, so that the one of an empty directory is not empty.
+const LISTING_VERSION: u8 = 1;
+/// Number of symbolic links followed before giving up on resolving a path.
+const MAX_LINKS: usize = 8;
+/// Directory descriptors are numbered from here on, so as not to clash with BCFS ones.
+const FIRST_DIR_FD: u32 = 1 << 30;
+
+pub type Result<T> = std::result::Result<T, ErrNo>;
+
+/// Storage writes making up a change to the namespace, by key: the value to set,
+/// or `None` to remove it.
+pub type Writes = BTreeMap<Vec<u8>, Option<Vec<u8>>>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EntryKind {
+	File,
+	Dir,
+	Link,
+}
+
+impl EntryKind {
+	fn tag(self) -> u8 {
+		match self {
+			EntryKind::File => b'f',
+			EntryKind::Dir => b'd',
+			EntryKind::Link => b'l',
+		}
+	}
+
+	fn from_tag(tag: u8) -> Option<Self> {
+		match tag {
+			b'f' => Some(EntryKind::File),
+			b'd' => Some(EntryKind::Dir),
+			b'l' => Some(EntryKind::Link),
+			_ => None,
+		}
+	}
+
+	/// The WASI `filetype` of the entry.
+	fn file_type(self) -> u8 {
+		match self {
+			EntryKind::Dir => 3,
+			EntryKind::File => 4,
+			EntryKind::Link => 7,
+		}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+	pub name: String,
+	pub kind: EntryKind,
+}
+
+/// Directories opened through `path_open`, along with the BCFS directory they were opened in.
+#[derive(Default)]
+pub struct DirFds {
+	open: BTreeMap<u32, (Fd, String)>,
+}
+
+impl DirFds {
+	pub fn open(&mut self, preopen: Fd, path: String) -> Fd {
+		let fd = self
+			.open
+			.keys()
+			.next_back()
+			.map_or(FIRST_DIR_FD, |fd| fd + 1);
+		self.open.insert(fd, (preopen, path));
+		Fd::from(fd)
+	}
+
+	pub fn get(&self, fd: Fd) -> Option<&(Fd, String)> {
+		self.open.get(&u32::from(fd))
+	}
+
+	/// Closes `fd`, returning whether it was a directory.
+	pub fn close(&mut self, fd: Fd) -> bool {
+		self.open.remove(&u32::from(fd)).is_some()
+	}
+}
+
+/// The namespace of the current account, as found in its storage.
+///
+/// Writes are buffered rather than applied, so that they can be paid for first,
+/// and the storage lookups made and bytes of listings and link targets read are
+/// counted, so that they can be charged.
+pub struct Namespace<'a> {
+	kvstore: &'a dyn KVStore,
+	writes: Writes,
+	lookups: Cell<u64>,
+	bytes_read: u64,
+}
+
+impl<'a> Namespace<'a> {
+	pub fn new(ext: &'a dyn vm::Ext) -> Self {
+		Namespace {
+			kvstore: ext.as_kvstore(),
+			writes: Writes::new(),
+			lookups: Cell::new(0),
+			bytes_read: 0,
+		}
+	}
+
+	/// Returns the number of storage lookups made, the number of bytes read and
+	/// the writes to apply.
+	pub fn finish(self) -> (u64, u64, Writes) {
+		(self.lookups.get(), self.bytes_read, self.writes)
+	}
+
+	/// Follows the symbolic links of every component of `path`.
+	pub fn resolve(&mut self, path: &str) -> Result<String> {
+		let mut unresolved = path.rsplit('/').map(str::to_owned).collect::<Vec<_>>();
+		let mut resolved = String::new();
+		let mut links = 0;
+		while let Some(name) = unresolved.pop() {
+			if name.is_empty() {
+				continue;
+			}
+			let candidate = child(&resolved, &name);
+			if self.kind(&candidate) != Some(EntryKind::Link) {
+				resolved = candidate;
+				continue;
+			}
+			links += 1;
+			if links > MAX_LINKS {
+				return Err(ErrNo::Loop);
+			}
+			// targets are relative to the directory holding the link
+			let target = String::from_utf8(self.readlink(&candidate)?).map_err(|_| ErrNo::Inval)?;
+			let target = join(&resolved, Path::new(&target))?;
+			unresolved.extend(target.rsplit('/').map(str::to_owned));
+			resolved.clear();
+		}
+		Ok(resolved)
+	}
+
+	/// Follows the symbolic links of every component of `path` but the last one.
+	pub fn resolve_parent(&mut self, path: &str) -> Result<String> {
+		let (dir, name) = split(path);
+		Ok(child(&self.resolve(dir)?, name))
+	}
+
+	/// Returns the kind of the entry at `path`, if there is one.
+	pub fn kind(&self, path: &str) -> Option<EntryKind> {
+		if path.is_empty() || self.contains(&dir_key(path)) {
+			Some(EntryKind::Dir)
+		} else if self.contains(&link_key(path)) {
+			Some(EntryKind::Link)
+		} else if self.contains(path.as_bytes()) {
+			Some(EntryKind::File)
+		} else {
+			None
+		}
+	}
+
+	/// Returns the entries of the directory at `path`, sorted by name.
+	///
+	/// Files which were created but never written have no storage, and are skipped.
+	pub fn entries(&mut self, path: &str) -> Result<Vec<Entry>> {
+		self.expect_dir(path)?;
+		let entries = self.listing(path);
+		Ok(entries
+			.into_iter()
+			.filter(|entry| {
+				entry.kind != EntryKind::File || self.contains(child(path, &entry.name).as_bytes())
+			})
+			.collect())
+	}
+
+	/// Records the entry at `path` in the listing of its directory, which must exist.
+	pub fn add_entry(&mut self, path: &str, kind: EntryKind) -> Result<()> {
+		let (dir, name) = split(path);
+		self.expect_dir(dir)?;
+		let mut entries = self.listing(dir);
+		entries.retain(|entry| entry.name != name);
+		entries.push(Entry {
+			name: name.to_owned(),
+			kind,
+		});
+		entries.sort_by(|a, b| a.name.cmp(&b.name));
+		self.set_listing(dir, &entries);
+		Ok(())
+	}
+
+	/// Drops the entry at `path` from the listing of its directory, if it is listed.
+	pub fn remove_entry(&mut self, path: &str) {
+		let (dir, name) = split(path);
+		let mut entries = self.listing(dir);
+		let len = entries.len();
+		entries.retain(|entry| entry.name != name);
+		if entries.len() != len {
+			self.set_listing(dir, &entries);
+		}
+	}
+
+	pub fn create_dir(&mut self, path: &str) -> Result<()> {
+		if self.kind(path).is_some() {
+			return Err(ErrNo::Exist);
+		}
+		self.add_entry(path, EntryKind::Dir)?;
+		self.set_listing(path, &[]);
+		Ok(())
+	}
+
+	pub fn remove_dir(&mut self, path: &str) -> Result<()> {
+		if path.is_empty() {
+			return Err(ErrNo::Busy);
+		}
+		if !self.entries(path)?.is_empty() {
+			return Err(ErrNo::NotEmpty);
+		}
+		self.remove(&dir_key(path));
+		self.remove_entry(path);
+		Ok(())
+	}
+
+	/// Creates a symbolic link at `path` pointing to `target`.
+	pub fn symlink(&mut self, target: &str, path: &str) -> Result<()> {
+		if target.is_empty() {
+			return Err(ErrNo::NoEnt);
+		}
+		if self.kind(path).is_some() {
+			return Err(ErrNo::Exist);
+		}
+		self.add_entry(path, EntryKind::Link)?;
+		self.set(&link_key(path), target.as_bytes().to_vec());
+		Ok(())
+	}
+
+	/// Returns the target of the symbolic link at `path`.
+	pub fn readlink(&mut self, path: &str) -> Result<Vec<u8>> {
+		match self.kind(path) {
+			Some(EntryKind::Link) => Ok(self.get(&link_key(path)).unwrap_or_default()),
+			Some(_) => Err(ErrNo::Inval),
+			None => Err(ErrNo::NoEnt),
+		}
+	}
+
+	pub fn remove_link(&mut self, path: &str) {
+		self.remove(&link_key(path));
+		self.remove_entry(path);
+	}
+
+	/// Moves the entry at `from` to `to`, replacing the file, link or empty directory there.
+	pub fn rename(&mut self, from: &str, to: &str) -> Result<()> {
+		let from_kind = self.kind(from).ok_or(ErrNo::NoEnt)?;
+		if from.is_empty() || to.is_empty() {
+			return Err(ErrNo::Busy);
+		}
+		if from == to {
+			return Ok(());
+		}
+		if to.starts_with(&format!("{}/", from)) {
+			return Err(ErrNo::Inval);
+		}
+		self.expect_dir(split(to).0)?;
+
+		match (from_kind, self.kind(to)) {
+			(_, None) => (),
+			(EntryKind::Dir, Some(EntryKind::Dir)) => self.remove_dir(to)?,
+			(EntryKind::Dir, Some(_)) => return Err(ErrNo::NotDir),
+			(_, Some(EntryKind::Dir)) => return Err(ErrNo::IsDir),
+			(_, Some(EntryKind::File)) => self.remove(to.as_bytes()),
+			(_, Some(EntryKind::Link)) => self.remove_link(to),
+		}
+
+		self.move_entry(from, to, from_kind);
+		self.remove_entry(from);
+		self.add_entry(to, from_kind)
+	}
+
+	fn move_entry(&mut self, from: &str, to: &str, kind: EntryKind) {
+		let (from_key, to_key) = match kind {
+			EntryKind::File => (from.as_bytes().to_vec(), to.as_bytes().to_vec()),
+			EntryKind::Link => (link_key(from), link_key(to)),
+			EntryKind::Dir => {
+				for entry in self.listing(from) {
+					self.move_entry(
+						&child(from, &entry.name),
+						&child(to, &entry.name),
+						entry.kind,
+					);
+				}
+				(dir_key(from), dir_key(to))
+			}
+		};
+		// files which were created but never written have nothing to move
+		if let Some(value) = self.get(&from_key) {
+			self.set(&to_key, value);
+			self.remove(&from_key);
+		}
+	}
+
+	fn expect_dir(&self, path: &str) -> Result<()> {
+		match self.kind(path) {
+			Some(EntryKind::Dir) => Ok(()),
+			Some(_) => Err(ErrNo::NotDir),
+			None => Err(ErrNo::NoEnt),
+		}
+	}
+
+	fn listing(&mut self, path: &str) -> Vec<Entry> {
+		let listing = self.get(&dir_key(path)).unwrap_or_default();
+		listing
+			.get(1..)
+			.unwrap_or_default()
+			.split(|b| *b == 0)
+			.filter_map(|entry| {
+				let (tag, name) = entry.split_first()?;
+				Some(Entry {
+					name: String::from_utf8(name.to_vec()).ok()?,
+					kind: EntryKind::from_tag(*tag)?,
+				})
+			})
+			.collect()
+	}
+
+	fn set_listing(&mut self, path: &str, entries: &[Entry]) {
+		let mut listing = vec![LISTING_VERSION];
+		for entry in entries {
+			listing.push(entry.kind.tag());
+			listing.extend_from_slice(entry.name.as_bytes());
+			listing.push(0);
+		}
+		self.set(&dir_key(path), listing);
+	}
+
+	fn contains(&self, key: &[u8]) -> bool {
+		match self.writes.get(key) {
+			Some(value) => value.is_some(),
+			None => {
+				self.lookups.set(self.lookups.get() + 1);
+				self.kvstore.contains(key)
+			}
+		}
+	}
+
+	fn get(&mut self, key: &[u8]) -> Option<Vec<u8>> {
+		let value = match self.writes.get(key) {
+			Some(value) => value.clone(),
+			None => {
+				self.lookups.set(self.lookups.get() + 1);
+				self.kvstore.get(key)
+			}
+		};
+		self.bytes_read += value.as_ref().map_or(0, |value| value.len() as u64);
+		value
+	}
+
+	fn set(&mut self, key: &[u8], value: Vec<u8>) {
+		self.writes.insert(key.to_vec(), Some(value));
+	}
+
+	fn remove(&mut self, key: &[u8]) {
+		self.writes.insert(key.to_vec(), None);
+	}
+}
+
+/// Joins `path` onto the directory `dir`, without leaving the home directory.
+pub fn join(dir: &str, path: &Path) -> Result<String> {
+	let mut components = dir
+		.split('/')
+		.filter(|name| !name.is_empty())
+		.collect::<Vec<_>>();
+	for component in path.components() {
+		match component {
+			Component::Normal(name) => components.push(name.to_str().ok_or(ErrNo::Inval)?),
+			Component::CurDir => (),
+			Component::ParentDir => {
+				components.pop().ok_or(ErrNo::NotCapable)?;
+			}
+			Component::RootDir | Component::Prefix(_) => return Err(ErrNo::NotCapable),
+		}
+	}
+	Ok(components.join("/"))
+}
+
+/// Applies the `writes` of a change to the namespace.
+pub fn apply(ext: &mut dyn vm::Ext, writes: Writes) {
+	let kvstore = ext.as_kvstore_mut();
+	for (key, value) in writes {
+		match value {
+			Some(value) => kvstore.set(&key, &value),
+			None => kvstore.remove(&key),
+		}
+	}
+}
+
+/// Serializes the WASI `dirent`s of `entries`, starting from the `cookie`th one.
+pub fn dirents(entries: &[Entry], cookie: u64) -> Vec<u8> {
+	let mut buf = Vec::new();
+	for (i, entry) in entries.iter().enumerate().skip(cookie as usize) {
+		let next = i as u64 + 1;
+		buf.extend_from_slice(&next.to_le_bytes()); // d_next
+		buf.extend_from_slice(&next.to_le_bytes()); // d_ino
+		buf.extend_from_slice(&(entry.name.len() as u32).to_le_bytes()); // d_namlen
+		buf.extend_from_slice(&[entry.kind.file_type(), 0, 0, 0]); // d_type and padding
+		buf.extend_from_slice(entry.name.as_bytes());
+	}
+	buf
+}
+
+fn split(path: &str) -> (&str, &str) {
+	match path.rfind('/') {
+		Some(i) => (&path[..i], &path[i + 1..]),
+		None => ("", path),
+	}
+}
+
+fn child(dir: &str, name: &str) -> String {
+	if dir.is_empty() {
+		name.to_owned()
+	} else {
+		format!("{}/{}", dir, name)
+	}
+}
+
+fn dir_key(path: &str) -> Vec<u8> {
+	[DIR_PREFIX, path.as_bytes()].concat()
+}
+
+fn link_key(path: &str) -> Vec<u8> {
+	[LINK_PREFIX, path.as_bytes()].concat()
+}
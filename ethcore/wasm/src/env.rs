@@ -17,6 +17,7 @@
 //! Env module glue for wasmi interpreter
 
 use std::cell::RefCell;
+use vm;
 use wasmi::{self, memory_units, Error, FuncInstance, FuncRef, MemoryInstance, MemoryRef};
 
 pub struct StaticSignature(
@@ -40,19 +41,82 @@ pub(crate) fn host(signature: StaticSignature, idx: usize) -> FuncRef {
 /// Also manages initial memory request from the runtime.
 #[derive(Default)]
 pub struct ImportResolver {
+	pub(crate) max_initial_memory: u32,
 	pub(crate) max_memory: u32,
 	pub(crate) memory: RefCell<Option<MemoryRef>>,
+	pub(crate) call_tracer: bool,
+	pub(crate) module_scoped: bool,
+	pub(crate) wasi_dirs: bool,
+	pub(crate) wasi_clocks: bool,
 }
 
+/// Resolver of the imports of a contract from the module `name`, under which
+/// the runtime provides either its WASI functions or its own. Before
+/// `schedule.wasm_validation`, every function is resolved under any module.
+pub struct ModuleResolver<'a> {
+	pub(crate) resolver: &'a ImportResolver,
+	pub(crate) name: &'a str,
+}
+
+/// Largest amount of memory a wasm module can address, in pages (64kb)
+pub(crate) const MAX_MEMORY_PAGES: u32 = 65536;
+
 impl ImportResolver {
 	/// New import resolver with specifed maximum amount of inital memory (in wasm pages = 64kb)
 	pub fn with_limit(max_memory: u32) -> ImportResolver {
 		ImportResolver {
+			max_initial_memory: max_memory,
 			max_memory: max_memory,
 			memory: RefCell::new(None),
+			call_tracer: false,
+			module_scoped: true,
+			wasi_dirs: true,
+			wasi_clocks: true,
+		}
+	}
+
+	/// New import resolver providing the functions of `schedule` and enforcing the
+	/// memory limits of its wasm policy
+	pub fn with_schedule(schedule: &vm::Schedule) -> ImportResolver {
+		let policy = &schedule.wasm().policy;
+		ImportResolver {
+			max_initial_memory: policy.max_initial_pages,
+			max_memory: policy.max_pages,
+			memory: RefCell::new(None),
+			call_tracer: false,
+			module_scoped: schedule.wasm_validation,
+			wasi_dirs: schedule.wasi_dirs,
+			wasi_clocks: schedule.wasi_clocks,
 		}
 	}
 
+	/// Also resolves the functions called by the instrumentation of
+	/// `parser::inject_call_tracer`, which only modules it went through may import
+	pub fn with_call_tracer(mut self) -> ImportResolver {
+		self.call_tracer = true;
+		self
+	}
+
+	/// Resolver of the imports from the module `name`, which share the memory of this one
+	pub fn module<'a>(&'a self, name: &'a str) -> ModuleResolver<'a> {
+		ModuleResolver {
+			resolver: self,
+			name,
+		}
+	}
+
+	/// Returns the maximum size of a memory requested with `descriptor`, capping
+	/// memories without one to the resolver limit.
+	pub(crate) fn memory_maximum(&self, descriptor: &wasmi::MemoryDescriptor) -> Option<u32> {
+		descriptor.maximum().or_else(|| {
+			if self.max_memory < MAX_MEMORY_PAGES {
+				Some(self.max_memory)
+			} else {
+				None
+			}
+		})
+	}
+
 	/// Returns memory that was instantiated during the contract module
 	/// start. If contract does not use memory at all, the dummy memory of length (0, 0)
 	/// will be created instead. So this method always returns memory instance
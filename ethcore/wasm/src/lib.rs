@@ -23,9 +23,11 @@ extern crate bcfs;
 extern crate byteorder;
 extern crate common_types;
 extern crate ethereum_types;
+extern crate heapsize;
 extern crate keccak_hash as hash;
 #[macro_use]
 extern crate log;
+extern crate memory_cache;
 extern crate parity_wasm;
 extern crate pwasm_utils as wasm_utils;
 extern crate vm;
@@ -33,9 +35,13 @@ extern crate wasi_types;
 extern crate wasm_macros;
 extern crate wasmi;
 
+mod cache;
+mod dirs;
 mod env;
 mod parser;
+mod profile;
 mod runtime;
+mod validation;
 
 #[cfg(test)]
 mod tests;
@@ -44,12 +50,19 @@ mod wasi;
 #[cfg(test)]
 mod benches;
 
+use std::sync::Arc;
+
+use hash::KECCAK_EMPTY;
 use parity_wasm::elements;
 use vm::{ActionParams, GasLeft, ReturnData};
 use wasmi::{Error as InterpreterError, Trap};
 
 use runtime::{Result, Runtime, RuntimeContext};
 
+pub use cache::ModuleCache;
+pub use profile::{FunctionGas, HostCallGas, Profile};
+pub use validation::{check_policy, validate};
+
 use ethereum_types::U256;
 
 /// Wrapped interpreter error
@@ -81,7 +94,78 @@ impl From<Error> for vm::Error {
 }
 
 /// Wasm interpreter instance
-pub struct WasmInterpreter;
+#[derive(Default)]
+pub struct WasmInterpreter {
+	/// Compiled modules shared with other interpreters, if caching is enabled.
+	cache: Option<Arc<ModuleCache>>,
+	/// Outcome of compiling the contract in `prepare`, and whether it was compiled
+	/// with the call tracer, used by the next `exec`.
+	prepared: Option<(bool, vm::Result<Option<Arc<wasmi::Module>>>)>,
+}
+
+impl WasmInterpreter {
+	/// Creates an interpreter reusing the modules compiled into `cache`.
+	pub fn new(cache: Arc<ModuleCache>) -> Self {
+		WasmInterpreter {
+			cache: Some(cache),
+			prepared: None,
+		}
+	}
+
+	/// Parses and instruments the contract code, returning `None` for a deployment
+	/// without a constructor. Modules of deployed contracts are looked up in and
+	/// added to the cache, unless they are `traced` by `parser::inject_call_tracer`.
+	///
+	/// The code of a deployment is expected to have gone through `validate`.
+	fn compile(
+		&self,
+		params: &ActionParams,
+		ext: &mut dyn vm::Ext,
+		traced: bool,
+	) -> vm::Result<Option<Arc<wasmi::Module>>> {
+		let is_create = ext.is_create();
+		let wasm_costs = ext.schedule().wasm();
+		let cache = match (&self.cache, params.code_hash) {
+			(Some(cache), Some(code_hash))
+				if !is_create && !traced && code_hash != KECCAK_EMPTY =>
+			{
+				let fingerprint = cache::fingerprint(wasm_costs);
+				if let Some(module) = cache.get(&code_hash, &fingerprint) {
+					return Ok(Some(module));
+				}
+				Some((cache, code_hash, fingerprint))
+			}
+			_ => None,
+		};
+
+		let parser::ParsedModule {
+			mut module, code, ..
+		} = parser::payload(&params)?;
+
+		let has_constructor = !is_create || subst_main_call(&mut module);
+
+		let module = parser::inject_gas_counter_and_stack_limiter(module, wasm_costs)?;
+
+		if !has_constructor {
+			return Ok(None);
+		}
+
+		let module = if traced {
+			parser::inject_call_tracer(module)?
+		} else {
+			module
+		};
+
+		let loaded_module =
+			Arc::new(wasmi::Module::from_parity_wasm_module(module).map_err(Error::Interpreter)?);
+
+		if let Some((cache, code_hash, fingerprint)) = cache {
+			cache.insert(code_hash, fingerprint, loaded_module.clone(), code.len());
+		}
+
+		Ok(Some(loaded_module))
+	}
+}
 
 impl From<runtime::Error> for vm::Error {
 	fn from(e: runtime::Error) -> Self {
@@ -96,22 +180,57 @@ enum ExecutionOutcome {
 }
 
 impl vm::Vm for WasmInterpreter {
-	fn prepare(&mut self, _params: &ActionParams, _ext: &mut dyn vm::Ext) -> vm::Result<()> {
+	fn prepare(&mut self, params: &ActionParams, ext: &mut dyn vm::Ext) -> vm::Result<()> {
+		// Invalid code is reported by `exec`, so that it fails the call rather than
+		// the caller of `prepare`, and is not compiled a second time.
+		let traced = ext.traces_wasm_output();
+		self.prepared = Some((traced, self.compile(params, ext, traced)));
 		Ok(())
 	}
 
 	fn exec(&mut self, params: ActionParams, ext: &mut dyn vm::Ext) -> vm::Result<GasLeft> {
+		self.run(params, ext, None)
+	}
+}
+
+impl WasmInterpreter {
+	/// Executes the contract like `exec`, also returning where its gas went.
+	///
+	/// If the execution fails, e.g. by running out of gas, the profile covers it
+	/// up to the failure.
+	pub fn profile(
+		&mut self,
+		params: ActionParams,
+		ext: &mut dyn vm::Ext,
+	) -> (vm::Result<GasLeft>, Profile) {
+		let mut profile = Profile::default();
+		let result = self.run(params, ext, Some(&mut profile));
+		(result, profile)
+	}
+
+	/// Executes the contract, filling in `profile` if the gas usage is to be profiled.
+	///
+	/// The functions the contract is executing are only kept track of, for the
+	/// profile and the backtrace of traps, when it is profiled or its execution
+	/// is traced: the instrumentation this takes costs no gas, but slows it down.
+	fn run(
+		&mut self,
+		params: ActionParams,
+		ext: &mut dyn vm::Ext,
+		mut profile: Option<&mut Profile>,
+	) -> vm::Result<GasLeft> {
 		let is_create = ext.is_create();
 
-		let parser::ParsedModule {
-			mut module,
-			code,
-			data,
-		} = parser::payload(&params)?;
+		let (code, data) = parser::split(&params)?;
 
-		if is_create {
-			let did_subst_main = subst_main_call(&mut module);
-			if !did_subst_main {
+		let traced = profile.is_some() || ext.traces_wasm_output();
+		let compiled = match self.prepared.take() {
+			Some((prepared_traced, compiled)) if prepared_traced == traced => compiled,
+			_ => self.compile(&params, ext, traced),
+		};
+		let loaded_module = match compiled? {
+			Some(module) => module,
+			None => {
 				// Early return when it's a deploy but there's no constructor function.
 				return Ok(GasLeft::NeedsReturn {
 					gas_left: params.gas,
@@ -121,18 +240,19 @@ impl vm::Vm for WasmInterpreter {
 			}
 		};
 
-		let module = parser::inject_gas_counter_and_stack_limiter(module, ext.schedule().wasm())?;
-
-		let loaded_module =
-			wasmi::Module::from_parity_wasm_module(module).map_err(Error::Interpreter)?;
-
-		let instantiation_resolver = env::ImportResolver::with_limit(<u32>::max_value());
+		let mut instantiation_resolver = env::ImportResolver::with_schedule(ext.schedule());
+		if traced {
+			instantiation_resolver = instantiation_resolver.with_call_tracer();
+		}
 
 		let module_instance = wasmi::ModuleInstance::new(
 			&loaded_module,
 			&wasmi::ImportsBuilder::new()
-				.with_resolver("env", &instantiation_resolver)
-				.with_resolver("wasi_unstable", &instantiation_resolver),
+				.with_resolver("env", &instantiation_resolver.module("env"))
+				.with_resolver(
+					"wasi_unstable",
+					&instantiation_resolver.module("wasi_unstable"),
+				),
 		)
 		.map_err(Error::Interpreter)?;
 
@@ -162,8 +282,9 @@ impl vm::Vm for WasmInterpreter {
 					sender: params.sender,
 					origin: params.origin,
 					code_address: params.code_address,
+					call_type: params.call_type,
+					gas_price: params.gas_price,
 					value: params.value.value(),
-					value_str: params.value.value().as_u64().to_string(),
 					aad_str: params.aad.as_ref().map(base64::encode).unwrap_or_default(),
 				},
 			);
@@ -174,11 +295,23 @@ impl vm::Vm for WasmInterpreter {
 			// total_charge ∈ [0..2^64) if static_region ∈ [0..2^16)
 			// qed
 			assert!(runtime.schedule().wasm().initial_mem_cost < 1 << 16);
-			runtime.charge(|s| Some(initial_memory as u64 * s.wasm().initial_mem_cost as u64))?;
+			if profile.is_some() {
+				runtime.profile = Some(Default::default());
+			}
+			if let Err(err) =
+				runtime.charge(|s| Some(initial_memory as u64 * s.wasm().initial_mem_cost as u64))
+			{
+				fill_profile(&mut profile, &mut runtime, code);
+				return Err(err.into());
+			}
 
-			let module_instance = module_instance
-				.run_start(&mut runtime)
-				.map_err(Error::Trap)?;
+			let module_instance = match module_instance.run_start(&mut runtime) {
+				Ok(module_instance) => module_instance,
+				Err(trap) => {
+					fill_profile(&mut profile, &mut runtime, code);
+					return Err(trap_error(&trap, &runtime.call_stack, code));
+				}
+			};
 
 			let invoke_result = module_instance.invoke_export("_start", &[], &mut runtime);
 
@@ -214,9 +347,14 @@ impl vm::Vm for WasmInterpreter {
 				_ => (),
 			}
 
+			fill_profile(&mut profile, &mut runtime, code);
+
 			if let (ExecutionOutcome::NotSpecial, Err(e)) = (execution_outcome, invoke_result) {
 				trace!(target: "wasm", "Error executing contract: {:?}", e);
-				return Err(vm::Error::from(Error::from(e)));
+				return Err(match e {
+					InterpreterError::Trap(trap) => trap_error(&trap, &runtime.call_stack, code),
+					e => vm::Error::from(Error::from(e)),
+				});
 			}
 
 			(
@@ -245,6 +383,59 @@ impl vm::Vm for WasmInterpreter {
 	}
 }
 
+/// Returns a function resolving the code section indices of an instrumented module
+/// into the functions of the contract `code` they were instrumented from.
+///
+/// Bodies added by the instrumentation, such as stack limiter thunks, resolve to `None`.
+fn function_resolver(code: &[u8]) -> impl Fn(u32) -> Option<vm::WasmFrame> {
+	let (imported_fns, defined_fns, names) =
+		match elements::deserialize_buffer::<elements::Module>(code) {
+			Ok(module) => (
+				module.import_count(elements::ImportCountType::Function) as u32,
+				module.code_section().map_or(0, |s| s.bodies().len()) as u32,
+				parser::function_names(module),
+			),
+			Err(_) => (0, 0, Default::default()),
+		};
+
+	move |body| {
+		if body >= defined_fns {
+			return None;
+		}
+		let index = imported_fns + body;
+		Some(vm::WasmFrame {
+			index,
+			name: names.get(&index).cloned(),
+		})
+	}
+}
+
+/// Fills in `profile` with the counters of `runtime`, if its gas usage is profiled.
+fn fill_profile(profile: &mut Option<&mut Profile>, runtime: &mut Runtime, code: &[u8]) {
+	if let (Some(profile), Some(counters)) = (profile.as_mut(), runtime.profile.take()) {
+		**profile = counters.into_profile(
+			function_resolver(code),
+			runtime.gas_counter,
+			runtime.memory.current_size().0 as u32,
+		);
+	}
+}
+
+/// Describes a trap of the contract `code`, with the functions on the `call_stack`
+/// maintained by the instrumentation of `parser::inject_call_tracer`, which is
+/// empty unless the contract was traced.
+fn trap_error(trap: &Trap, call_stack: &[u32], code: &[u8]) -> vm::Error {
+	let resolve = function_resolver(code);
+	vm::Error::WasmTrap(vm::WasmTrap {
+		kind: format!("{:?}", trap.kind()),
+		backtrace: call_stack
+			.iter()
+			.rev()
+			.filter_map(|&body| resolve(body))
+			.collect(),
+	})
+}
+
 /// Returns whether the call to `main` in `_start` was replaced with a call to `_oasis_deploy`.
 fn subst_main_call(module: &mut elements::Module) -> bool {
 	let start_fn_idx = match func_index(module, "_start") {
@@ -18,6 +18,7 @@
 
 use parity_wasm::elements::{self, Deserialize};
 use parity_wasm::peek_size;
+use std::collections::BTreeMap;
 use vm;
 use wasm_utils::{self, rules};
 
@@ -43,8 +44,80 @@ fn gas_rules(wasm_costs: &vm::WasmCosts) -> rules::Set {
 		vals
 	})
 	.with_grow_cost(wasm_costs.grow_mem)
-	// enabling the following line disables floats
-	//.with_forbidden_floats()
+}
+
+/// Whether `instruction` is one of those forbidden by `rules::Set::with_forbidden_floats`.
+fn is_float(instruction: &elements::Instruction) -> bool {
+	match rules::InstructionType::op(instruction) {
+		rules::InstructionType::Float
+		| rules::InstructionType::FloatConst
+		| rules::InstructionType::FloatComparsion
+		| rules::InstructionType::FloatConversion => true,
+		_ => false,
+	}
+}
+
+/// Checks that the module of a contract being deployed complies with `policy`.
+pub fn check_policy(module: &elements::Module, policy: &vm::WasmPolicy) -> Result<(), vm::Error> {
+	let violation = |reason: String| {
+		Err(vm::Error::Wasm(format!(
+			"Wasm policy violation: {}",
+			reason
+		)))
+	};
+
+	let imports = module
+		.import_section()
+		.map_or(&[][..], |section| section.entries());
+	for entry in imports {
+		match entry.external() {
+			elements::External::Function(_) => {
+				if !policy.allows_import(entry.module(), entry.field()) {
+					return violation(format!(
+						"import {}.{} is not allowed",
+						entry.module(),
+						entry.field()
+					));
+				}
+			}
+			elements::External::Memory(memory) => {
+				let limits = memory.limits();
+				if limits.initial() > policy.max_initial_pages {
+					return violation(format!(
+						"initial memory of {} pages exceeds the limit of {}",
+						limits.initial(),
+						policy.max_initial_pages
+					));
+				}
+				match limits.maximum() {
+					Some(maximum) if maximum > policy.max_pages => {
+						return violation(format!(
+							"maximum memory of {} pages exceeds the limit of {}",
+							maximum, policy.max_pages
+						));
+					}
+					_ => (),
+				}
+			}
+			_ => (),
+		}
+	}
+
+	if policy.forbid_floats {
+		let bodies = module
+			.code_section()
+			.map_or(&[][..], |section| section.bodies());
+		for (idx, body) in bodies.iter().enumerate() {
+			if let Some(instruction) = body.code().elements().iter().find(|i| is_float(i)) {
+				return violation(format!(
+					"function body {} uses floating point instruction {:?}",
+					idx, instruction
+				));
+			}
+		}
+	}
+
+	Ok(())
 }
 
 pub struct ParsedModule<'a> {
@@ -73,6 +146,32 @@ const WASM_SEPARATOR: &[u8] = b"\x00\x19\x18==OasisEndOfWasmMarker==";
 /// loads the module instance from payload and injects gas counter according
 /// to schedule.
 pub fn payload<'a>(params: &'a vm::ActionParams) -> Result<ParsedModule<'a>, vm::Error> {
+	let (code, data) = split(params)?;
+
+	let deserialized_module = elements::Module::deserialize(&mut ::std::io::Cursor::new(code))
+		.map_err(|err| vm::Error::Wasm(format!("Error deserializing contract code ({:?})", err)))?;
+
+	if deserialized_module
+		.memory_section()
+		.map_or(false, |ms| ms.entries().len() > 0)
+	{
+		// According to WebAssembly spec, internal memory is hidden from embedder and should not
+		// be interacted with. So we disable this kind of modules at decoding level.
+		return Err(vm::Error::Wasm(format!(
+			"Malformed wasm module: internal memory"
+		)));
+	}
+
+	Ok(ParsedModule {
+		module: deserialized_module,
+		code,
+		data,
+	})
+}
+
+/// Splits payload to code and data according to params.params_type, without
+/// deserializing the code.
+pub fn split<'a>(params: &'a vm::ActionParams) -> Result<(&'a [u8], &'a [u8]), vm::Error> {
 	let payload = match params.code {
 		Some(ref code) => &code[..],
 		None => {
@@ -82,52 +181,24 @@ pub fn payload<'a>(params: &'a vm::ActionParams) -> Result<ParsedModule<'a>, vm:
 		}
 	};
 
-	let (mut code, embedded_data) = match params.params_type {
+	Ok(match params.params_type {
 		vm::ParamsType::Embedded => match index_of(payload, WASM_SEPARATOR) {
 			Some(separator_idx) => (
-				::std::io::Cursor::new(&payload[..separator_idx]),
+				&payload[..separator_idx],
 				&payload[separator_idx + WASM_SEPARATOR.len()..],
 			),
 			None => {
 				let module_size = peek_size(&*payload);
-				(
-					::std::io::Cursor::new(&payload[..module_size]),
-					&payload[module_size..],
-				)
+				(&payload[..module_size], &payload[module_size..])
 			}
 		},
-		vm::ParamsType::Separate => (::std::io::Cursor::new(&payload[..]), &[] as &[u8]),
-	};
-
-	let mut deserialized_module = elements::Module::deserialize(&mut code)
-		.map_err(|err| vm::Error::Wasm(format!("Error deserializing contract code ({:?})", err)))?;
-
-	if deserialized_module
-		.memory_section()
-		.map_or(false, |ms| ms.entries().len() > 0)
-	{
-		// According to WebAssembly spec, internal memory is hidden from embedder and should not
-		// be interacted with. So we disable this kind of modules at decoding level.
-		return Err(vm::Error::Wasm(format!(
-			"Malformed wasm module: internal memory"
-		)));
-	}
-
-	let (code, data): (&[u8], &[u8]) = match params.params_type {
-		vm::ParamsType::Embedded => (code.into_inner(), embedded_data),
 		vm::ParamsType::Separate => (
-			code.into_inner(),
+			payload,
 			match params.data {
 				Some(ref s) => &s[..],
 				None => &[],
 			},
 		),
-	};
-
-	Ok(ParsedModule {
-		module: deserialized_module,
-		code,
-		data,
 	})
 }
 
@@ -142,9 +213,166 @@ pub fn inject_gas_counter_and_stack_limiter<'a>(
 	Ok(module)
 }
 
+/// Name of the host function called on entry of every function body, with the index of the body.
+pub const ENTER_FN: &str = "oasis_enter";
+/// Name of the host function called after every call of a function of the module.
+pub const LEAVE_FN: &str = "oasis_leave";
+/// Body index passed to `ENTER_FN` ahead of an indirect call, which may call an
+/// imported function that enters nothing itself.
+pub const INDIRECT_CALL: u32 = u32::max_value();
+
+/// Makes functions of an instrumented module report when they are entered and
+/// left, so that the runtime can keep a backtrace of the contract.
+///
+/// Indirect calls are entered as `INDIRECT_CALL` before their callee is known,
+/// so that leaving them also leaves that mark, whether the callee entered
+/// itself or was imported.
+///
+/// This must be done after gas metering, so that it does not affect gas usage.
+pub fn inject_call_tracer(mut module: elements::Module) -> Result<elements::Module, vm::Error> {
+	// the gas counter is always imported, so the imports of the tracer can be added to its section
+	let imports = match module.import_section() {
+		Some(imports) => imports.entries(),
+		None => {
+			return Err(vm::Error::Wasm(format!(
+				"Wasm contract error: instrumented module has no imports"
+			)))
+		}
+	};
+	// only the imports added here may resolve to the functions of the tracer
+	if imports
+		.iter()
+		.any(|entry| entry.field() == ENTER_FN || entry.field() == LEAVE_FN)
+	{
+		return Err(vm::Error::Wasm(format!(
+			"Wasm contract error: {} and {} are reserved for the runtime",
+			ENTER_FN, LEAVE_FN
+		)));
+	}
+
+	let imported_fns = module.import_count(elements::ImportCountType::Function) as u32;
+	let enter_fn = imported_fns;
+	let leave_fn = imported_fns + 1;
+	let shift = |idx: &mut u32| {
+		if *idx >= imported_fns {
+			*idx += 2
+		}
+	};
+
+	let types = module.type_section_mut().ok_or_else(|| {
+		vm::Error::Wasm(format!(
+			"Wasm contract error: instrumented module has no types"
+		))
+	})?;
+	let enter_ty = types.types().len() as u32;
+	types
+		.types_mut()
+		.push(elements::Type::Function(elements::FunctionType::new(
+			vec![elements::ValueType::I32],
+			None,
+		)));
+	types
+		.types_mut()
+		.push(elements::Type::Function(elements::FunctionType::new(
+			vec![],
+			None,
+		)));
+
+	for section in module.sections_mut() {
+		match section {
+			elements::Section::Import(imports) => {
+				// after the other function imports, so that they get the indices computed above
+				let entries = imports.entries_mut();
+				let pos = entries
+					.iter()
+					.rposition(|entry| match entry.external() {
+						elements::External::Function(_) => true,
+						_ => false,
+					})
+					.map_or(0, |pos| pos + 1);
+				entries.insert(
+					pos,
+					elements::ImportEntry::new(
+						"env".to_owned(),
+						ENTER_FN.to_owned(),
+						elements::External::Function(enter_ty),
+					),
+				);
+				entries.insert(
+					pos + 1,
+					elements::ImportEntry::new(
+						"env".to_owned(),
+						LEAVE_FN.to_owned(),
+						elements::External::Function(enter_ty + 1),
+					),
+				);
+			}
+			elements::Section::Code(code) => {
+				for (body_idx, body) in code.bodies_mut().iter_mut().enumerate() {
+					let instructions = body.code_mut().elements_mut();
+					let mut traced = Vec::with_capacity(instructions.len() + 2);
+					traced.push(elements::Instruction::I32Const(body_idx as i32));
+					traced.push(elements::Instruction::Call(enter_fn));
+					for mut instruction in instructions.drain(..) {
+						if let elements::Instruction::CallIndirect(..) = instruction {
+							traced.push(elements::Instruction::I32Const(INDIRECT_CALL as i32));
+							traced.push(elements::Instruction::Call(enter_fn));
+						}
+						let leaves = match instruction {
+							elements::Instruction::Call(ref mut idx) => {
+								let internal = *idx >= imported_fns;
+								shift(idx);
+								internal
+							}
+							elements::Instruction::CallIndirect(..) => true,
+							_ => false,
+						};
+						traced.push(instruction);
+						if leaves {
+							traced.push(elements::Instruction::Call(leave_fn));
+						}
+					}
+					*instructions = traced;
+				}
+			}
+			elements::Section::Export(exports) => {
+				for entry in exports.entries_mut() {
+					if let elements::Internal::Function(idx) = entry.internal_mut() {
+						shift(idx);
+					}
+				}
+			}
+			elements::Section::Element(elements) => {
+				for segment in elements.entries_mut() {
+					segment.members_mut().iter_mut().for_each(shift);
+				}
+			}
+			elements::Section::Start(idx) => shift(idx),
+			_ => (),
+		}
+	}
+
+	Ok(module)
+}
+
+/// Returns the functions of a module with a name section, by index.
+pub fn function_names(module: elements::Module) -> BTreeMap<u32, String> {
+	let module = module.parse_names().unwrap_or_else(|(_, module)| module);
+	match module.names_section() {
+		Some(elements::NameSection::Function(names)) => names
+			.names()
+			.iter()
+			.map(|(idx, name)| (idx, name.clone()))
+			.collect(),
+		_ => BTreeMap::new(),
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use parity_wasm::builder;
+	use std::collections::BTreeSet;
 
 	static simple_wasm: &[u8] = include_bytes!("../../res/wasi-tests/target/service/empty.wasm");
 
@@ -217,4 +445,184 @@ mod tests {
 		// WASMs are not allowed to have a memory section. Expect parser to error out.
 		assert_eq!(payload(&params).is_err(), true);
 	}
+
+	fn simple_module() -> elements::Module {
+		elements::Module::deserialize(&mut ::std::io::Cursor::new(simple_wasm)).unwrap()
+	}
+
+	fn policy_violation(module: &elements::Module, policy: &vm::WasmPolicy) -> String {
+		match check_policy(module, policy) {
+			Err(vm::Error::Wasm(reason)) => reason,
+			res => panic!("expected a policy violation, got {:?}", res),
+		}
+	}
+
+	#[test]
+	fn policy_restricts_imports() {
+		let module = simple_module();
+		let imports: BTreeSet<String> = module
+			.import_section()
+			.unwrap()
+			.entries()
+			.iter()
+			.filter(|entry| match entry.external() {
+				elements::External::Function(_) => true,
+				_ => false,
+			})
+			.map(|entry| format!("{}.{}", entry.module(), entry.field()))
+			.collect();
+
+		let mut policy = vm::WasmPolicy::default();
+		policy.allowed_imports = Some(imports.clone());
+		check_policy(&module, &policy).unwrap();
+
+		let forbidden = imports.iter().next().unwrap();
+		policy.allowed_imports.as_mut().unwrap().remove(forbidden);
+		assert!(policy_violation(&module, &policy).contains(forbidden.as_str()));
+	}
+
+	#[test]
+	fn policy_restricts_memory() {
+		let module = simple_module();
+		let initial = module
+			.import_section()
+			.unwrap()
+			.entries()
+			.iter()
+			.filter_map(|entry| match entry.external() {
+				elements::External::Memory(memory) => Some(memory.limits().initial()),
+				_ => None,
+			})
+			.next()
+			.unwrap();
+
+		let mut policy = vm::WasmPolicy::default();
+		policy.max_initial_pages = initial;
+		check_policy(&module, &policy).unwrap();
+
+		policy.max_initial_pages = initial - 1;
+		assert!(policy_violation(&module, &policy).contains("initial memory"));
+	}
+
+	#[test]
+	fn policy_forbids_floats() {
+		let module = builder::module()
+			.function()
+			.signature()
+			.build()
+			.body()
+			.with_instructions(elements::Instructions::new(vec![
+				elements::Instruction::F32Const(0),
+				elements::Instruction::Drop,
+				elements::Instruction::End,
+			]))
+			.build()
+			.build()
+			.build();
+
+		let mut policy = vm::WasmPolicy::default();
+		check_policy(&module, &policy).unwrap();
+
+		policy.forbid_floats = true;
+		assert!(policy_violation(&module, &policy).contains("F32Const"));
+	}
+
+	#[test]
+	fn traces_calls() {
+		use elements::Instruction::{Call, CallIndirect, End, I32Const};
+		use elements::*;
+
+		let module = Module::new(vec![
+			Section::Type(TypeSection::with_types(vec![
+				Type::Function(FunctionType::new(vec![ValueType::I32], None)),
+				Type::Function(FunctionType::new(vec![], None)),
+			])),
+			Section::Import(ImportSection::with_entries(vec![ImportEntry::new(
+				"env".to_owned(),
+				"gas".to_owned(),
+				External::Function(0),
+			)])),
+			Section::Function(FunctionSection::with_entries(vec![
+				Func::new(1),
+				Func::new(1),
+			])),
+			Section::Export(ExportSection::with_entries(vec![ExportEntry::new(
+				"_start".to_owned(),
+				Internal::Function(1),
+			)])),
+			Section::Code(CodeSection::with_bodies(vec![
+				FuncBody::new(
+					vec![],
+					Instructions::new(vec![I32Const(1), Call(0), Call(2), End]),
+				),
+				FuncBody::new(
+					vec![],
+					Instructions::new(vec![I32Const(0), CallIndirect(1, 0), End]),
+				),
+			])),
+		]);
+
+		let module = inject_call_tracer(module).unwrap();
+		let imports: Vec<_> = module
+			.import_section()
+			.unwrap()
+			.entries()
+			.iter()
+			.map(|entry| entry.field())
+			.collect();
+		assert_eq!(imports, vec!["gas", ENTER_FN, LEAVE_FN]);
+		assert_eq!(
+			module.export_section().unwrap().entries()[0].internal(),
+			&Internal::Function(3)
+		);
+
+		let bodies = module.code_section().unwrap().bodies();
+		// calls to imported functions do not enter a new frame, so they are not left
+		assert_eq!(
+			bodies[0].code().elements(),
+			&[
+				I32Const(0),
+				Call(1),
+				I32Const(1),
+				Call(0),
+				Call(4),
+				Call(2),
+				End
+			][..]
+		);
+		// the callee of an indirect call may be imported, so it is entered by the caller
+		assert_eq!(
+			bodies[1].code().elements(),
+			&[
+				I32Const(1),
+				Call(1),
+				I32Const(0),
+				I32Const(INDIRECT_CALL as i32),
+				Call(1),
+				CallIndirect(1, 0),
+				Call(2),
+				End
+			][..]
+		);
+	}
+
+	#[test]
+	fn call_tracer_imports_are_reserved() {
+		let module = builder::module()
+			.function()
+			.signature()
+			.with_params(vec![elements::ValueType::I32])
+			.build()
+			.body()
+			.build()
+			.build()
+			.import()
+			.module("env")
+			.field(ENTER_FN)
+			.external()
+			.func(0)
+			.build()
+			.build();
+		assert!(inject_call_tracer(module).is_err());
+	}
 }
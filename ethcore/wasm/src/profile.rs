@@ -0,0 +1,104 @@
+//! Gas profiling of contract executions
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::Serialize;
+
+/// Where the gas of a contract execution went, in wasm gas units
+/// (before the `opcodes_mul / opcodes_div` adjustment).
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Profile {
+	/// Total gas used, including the initial memory charge.
+	pub gas_used: u64,
+	/// Size of the linear memory, in pages, when the execution ended.
+	/// Memory never shrinks, so this is also its peak size.
+	pub peak_memory_pages: u32,
+	/// Gas of the instructions of each contract function, most expensive first.
+	pub functions: Vec<FunctionGas>,
+	/// Gas charged by each runtime function, most expensive first.
+	pub host_calls: Vec<HostCallGas>,
+}
+
+/// Gas of the instructions executed in a contract function, excluding its callees.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FunctionGas {
+	/// Index of the function in the contract module.
+	pub index: u32,
+	/// Name of the function, if the module has a names section.
+	pub name: Option<String>,
+	/// Gas used.
+	pub gas: u64,
+}
+
+/// Gas charged by the calls to a runtime function.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HostCallGas {
+	/// Name of the runtime function.
+	pub name: String,
+	/// Number of calls.
+	pub calls: u64,
+	/// Gas charged, including that of nested calls and creates.
+	pub gas: u64,
+}
+
+/// Gas counted by the runtime while profiling.
+#[derive(Default)]
+pub(crate) struct Counters {
+	/// Gas of instructions, by code section index of the instrumented module.
+	functions: HashMap<u32, u64>,
+	/// Number of calls and gas charged, by runtime function.
+	host_calls: BTreeMap<&'static str, (u64, u64)>,
+}
+
+impl Counters {
+	pub fn function(&mut self, body: u32, gas: u64) {
+		*self.functions.entry(body).or_insert(0) += gas;
+	}
+
+	pub fn host_call(&mut self, name: &'static str, gas: u64) {
+		let counter = self.host_calls.entry(name).or_insert((0, 0));
+		counter.0 += 1;
+		counter.1 += gas;
+	}
+
+	/// Builds the profile, resolving code section indices with `resolve`.
+	/// Gas of bodies added by the instrumentation is not reported.
+	pub fn into_profile(
+		self,
+		resolve: impl Fn(u32) -> Option<vm::WasmFrame>,
+		gas_used: u64,
+		peak_memory_pages: u32,
+	) -> Profile {
+		let mut functions = self
+			.functions
+			.into_iter()
+			.filter_map(|(body, gas)| {
+				resolve(body).map(|frame| FunctionGas {
+					index: frame.index,
+					name: frame.name,
+					gas,
+				})
+			})
+			.collect::<Vec<_>>();
+		functions.sort_by(|a, b| b.gas.cmp(&a.gas).then(a.index.cmp(&b.index)));
+
+		let mut host_calls = self
+			.host_calls
+			.into_iter()
+			.map(|(name, (calls, gas))| HostCallGas {
+				name: name.to_owned(),
+				calls,
+				gas,
+			})
+			.collect::<Vec<_>>();
+		host_calls.sort_by(|a, b| b.gas.cmp(&a.gas));
+
+		Profile {
+			gas_used,
+			peak_memory_pages,
+			functions,
+			host_calls,
+		}
+	}
+}
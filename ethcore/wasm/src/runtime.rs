@@ -14,26 +14,29 @@
 // You should have received a copy of the GNU General Public License
 // along with Parity.  If not, see <http://www.gnu.org/licenses/>.
 use std::{
-	cell::{RefCell, UnsafeCell},
+	cell::{Cell, RefCell, UnsafeCell},
 	sync::Arc,
 };
 
 use bcfs::BCFS;
 use blockchain_traits::{KVStore, KVStoreMut, PendingTransaction, TransactionOutcome};
-use common_types::{u128_from_u256, u256_from_u128};
+use common_types::{log_entry::LogEntry, u128_from_u256, u256_from_u128};
 use ethereum_types::{Address, H256, U256};
 use vm::{
 	self, CallType, ContractCreateResult, CreateContractAddress, MessageCallResult, ReturnData,
 };
 use wasmi::{self, Error as InterpreterError, MemoryRef, Trap, TrapKind};
 
+use crate::dirs::DirFds;
+
 pub struct RuntimeContext {
 	pub address: Address,
 	pub sender: Address,
 	pub origin: Address,
 	pub code_address: Address,
+	pub call_type: CallType,
+	pub gas_price: U256,
 	pub value: U256,
-	pub value_str: String,
 	pub aad_str: String,
 }
 
@@ -50,9 +53,22 @@ pub struct Runtime<'a> {
 	pub err_output: Vec<u8>,
 	// unsafety is needed because runtime contains BCFS but is used by bcfs as PendingTransaction
 	pub bcfs: UnsafeCell<BCFS>,
+	pub dir_fds: DirFds,
 	pub should_revert: bool,
 	pub bytes_cache: RefCell<Vec<Arc<Vec<u8>>>>,
 	pub rng: hmac_drbg::HmacDRBG<sha2::Sha256>,
+	/// Code section indices of the functions being executed, outermost first.
+	pub call_stack: Vec<u32>,
+	/// Bytes of stdout and stderr passed on to the tracer so far.
+	pub output_captured: usize,
+	/// Nanoseconds the clocks were advanced by `poll_oneoff`, on top of the gas consumed.
+	pub(crate) slept: u64,
+	/// Environment variables, once `environ_sizes_get` or `environ_get` built them.
+	pub(crate) environ_strings: Option<Vec<String>>,
+	/// Gas counters, when the execution is being profiled.
+	pub(crate) profile: Option<crate::profile::Counters>,
+	/// Whether a value had to be narrowed to 128 bits but did not fit.
+	pub(crate) value_overflow: Cell<bool>,
 }
 
 pub struct Receipt {
@@ -61,6 +77,7 @@ pub struct Receipt {
 	gas_used: u64,
 	outcome: TransactionOutcome,
 	output: ReturnData,
+	events: Vec<oasis_types::Event>,
 }
 
 impl blockchain_traits::Receipt for Receipt {
@@ -77,7 +94,7 @@ impl blockchain_traits::Receipt for Receipt {
 	}
 
 	fn events(&self) -> Vec<&oasis_types::Event> {
-		unimplemented!()
+		self.events.iter().collect()
 	}
 
 	/// Returns the outcome of this transaction.
@@ -101,7 +118,7 @@ impl<'a> PendingTransaction for Runtime<'a> {
 	}
 
 	fn value(&self) -> u128 {
-		u128_from_u256(self.context.value)
+		self.narrow_value(self.context.value).unwrap_or_default()
 	}
 
 	fn input(&self) -> &[u8] {
@@ -117,6 +134,7 @@ impl<'a> PendingTransaction for Runtime<'a> {
 			gas_used: 0,
 			outcome: TransactionOutcome::Fatal,
 			output: ReturnData::empty(),
+			events: Vec::new(),
 		});
 
 		if let Err(err) = self.adjusted_charge(|schedule| Some(schedule.create_gas as u64)) {
@@ -136,6 +154,7 @@ impl<'a> PendingTransaction for Runtime<'a> {
 		let mut salt = H256::new();
 		self.rng.generate_to_slice(salt.as_mut(), None /* aad */);
 
+		let first_log = self.ext.logs().len();
 		let create_result = self.ext.create(
 			&pre_gas_left.into(),
 			&u256_from_u128(value),
@@ -154,6 +173,7 @@ impl<'a> PendingTransaction for Runtime<'a> {
 					receipt.gas_used = self.gas_limit;
 					return receipt;
 				}
+				receipt.events = self.events_since(first_log);
 			}
 			ContractCreateResult::Failed => {
 				receipt.outcome = TransactionOutcome::Fatal;
@@ -190,6 +210,7 @@ impl<'a> PendingTransaction for Runtime<'a> {
 			gas_used: 0,
 			outcome: TransactionOutcome::Fatal,
 			output: ReturnData::empty(),
+			events: Vec::new(),
 		});
 
 		if let Err(err) = self.adjusted_charge(|schedule| Some(schedule.call_gas as u64)) {
@@ -207,6 +228,7 @@ impl<'a> PendingTransaction for Runtime<'a> {
 		};
 
 		let callee = maddr2eaddr(&callee);
+		let first_log = self.ext.logs().len();
 		let call_result = self.ext.call(
 			&pre_gas_left.into(),
 			&self.context.address, /* sender */
@@ -241,6 +263,8 @@ impl<'a> PendingTransaction for Runtime<'a> {
 					return receipt;
 				}
 				receipt.output = return_data;
+				// a reverted callee leaves no logs behind
+				receipt.events = self.events_since(first_log);
 			}
 			MessageCallResult::Failed => {
 				receipt.gas_used = self.gas_limit;
@@ -302,8 +326,8 @@ impl<'a> PendingTransaction for Runtime<'a> {
 	fn account_meta_at(&self, addr: &oasis_types::Address) -> Option<oasis_types::AccountMeta> {
 		Some(oasis_types::AccountMeta {
 			balance: match self.ext.balance(maddr2eaddr(addr)) {
-				Ok(bal) if bal.bits() <= 128 => u128_from_u256(bal),
-				_ => return None,
+				Ok(bal) => self.narrow_value(bal)?,
+				Err(_) => return None,
 			},
 			expiry: self
 				.ext
@@ -314,6 +338,14 @@ impl<'a> PendingTransaction for Runtime<'a> {
 	}
 }
 
+pub fn log2event(log: &LogEntry) -> oasis_types::Event {
+	oasis_types::Event {
+		emitter: *eaddr2maddr(&log.address),
+		topics: log.topics.iter().map(|topic| topic.0).collect(),
+		data: log.data.clone(),
+	}
+}
+
 pub fn maddr2eaddr(addr: &oasis_types::Address) -> &ethereum_types::Address {
 	// this is safe because both `Address` types are newtypes containing [u8; 32]
 	unsafe { std::mem::transmute(addr) }
@@ -369,6 +401,8 @@ pub enum Error {
 	StackOverflow,
 	/// Panic with message
 	Panic(String),
+	/// Value does not fit in 128 bits
+	ValueOverflow,
 }
 
 impl wasmi::HostError for Error {}
@@ -424,6 +458,7 @@ impl ::std::fmt::Display for Error {
 			Error::StackOverflow => write!(f, "Stack overflow"),
 			Error::InvalidConversionToInt => write!(f, "Invalid conversion to integer"),
 			Error::Panic(ref msg) => write!(f, "Panic: {}", msg),
+			Error::ValueOverflow => write!(f, "Value does not fit in 128 bits"),
 		}
 	}
 }
@@ -460,6 +495,7 @@ impl<'a> Runtime<'a> {
 
 		Runtime {
 			bcfs: UnsafeCell::new(BCFS::new(*eaddr2maddr(&context.address), "oasis")),
+			dir_fds: DirFds::default(),
 			gas_counter: 0,
 			gas_limit,
 			memory,
@@ -471,6 +507,12 @@ impl<'a> Runtime<'a> {
 			err_output: Vec::new(),
 			should_revert: false,
 			bytes_cache: RefCell::new(Vec::new()),
+			call_stack: Vec::new(),
+			output_captured: 0,
+			slept: 0,
+			environ_strings: None,
+			profile: None,
+			value_overflow: Cell::new(false),
 		}
 	}
 
@@ -524,6 +566,28 @@ impl<'a> Runtime<'a> {
 		})
 	}
 
+	/// Gas charge for reading `bytes_len` bytes of storage: `sload_gas` per 32 bytes.
+	pub fn storage_read_charge(&mut self, bytes_len: u64) -> Result<()> {
+		let sload_gas = U256::from(self.ext.schedule().sload_gas);
+		let bytes_len = U256::from(bytes_len);
+
+		// gas <- ceiling(sload_gas * bytes_len / 32)
+		// Cannot overflow as gas and len are converted from u64s.
+		let mut gas = sload_gas * bytes_len / U256::from(32);
+		if sload_gas * bytes_len % U256::from(32) != U256::from(0) {
+			gas = gas + U256::from(1);
+		}
+
+		// Charge gas after checking for u64 overflow.
+		if gas > U256::from(std::u64::MAX) {
+			return Err(Error::GasLimit);
+		} else {
+			self.adjusted_charge(|_| Some(gas.as_u64()))?;
+		}
+
+		Ok(())
+	}
+
 	/// Gas charge prorated based on time until expiry and the number of bytes we're storing.
 	pub fn storage_bytes_charge(&mut self, bytes_len: u64, reset: bool) -> Result<()> {
 		let duration_secs = self
@@ -551,6 +615,28 @@ impl<'a> Runtime<'a> {
 		Ok(())
 	}
 
+	/// Narrows `value` to the 128 bits used by `blockchain_traits`.
+	///
+	/// `PendingTransaction` methods cannot fail, so a wider value is recorded in
+	/// `value_overflow`. Every host function checks it before returning to the
+	/// module, so whichever one reached a `PendingTransaction` method fails with
+	/// `Error::ValueOverflow` instead of using the 0 or `None` returned here.
+	pub(crate) fn narrow_value(&self, value: U256) -> Option<u128> {
+		if value.bits() > 128 {
+			self.value_overflow.set(true);
+			return None;
+		}
+		Some(u128_from_u256(value))
+	}
+
+	/// Fails with `Error::ValueOverflow` if a value could not be narrowed since the last check.
+	pub(crate) fn check_value_overflow(&self) -> Result<()> {
+		if self.value_overflow.replace(false) {
+			return Err(Error::ValueOverflow);
+		}
+		Ok(())
+	}
+
 	/// Return currently used schedule
 	pub fn schedule(&self) -> &vm::Schedule {
 		self.ext.schedule()
@@ -575,4 +661,9 @@ impl<'a> Runtime<'a> {
 		}
 		Ok(self.gas_limit - self.gas_counter)
 	}
+
+	/// Events of the logs created since the first `first_log` logs, i.e. by a nested call.
+	fn events_since(&self, first_log: usize) -> Vec<oasis_types::Event> {
+		self.ext.logs()[first_log..].iter().map(log2event).collect()
+	}
 }
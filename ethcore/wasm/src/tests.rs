@@ -14,12 +14,23 @@
 // You should have received a copy of the GNU General Public License
 // along with Parity.  If not, see <http://www.gnu.org/licenses/>.
 
+use blockchain_traits::{PendingTransaction as _, TransactionOutcome};
+use common_types::log_entry::LogEntry;
 use ethereum_types::{Address, U256};
+use hash::keccak;
+use std::io::IoSlice;
 use std::sync::Arc;
+use wasi_types::ErrNo;
 
-use vm::{self, tests::FakeExt, ActionParams, ActionValue, GasLeft, ReturnData, Vm};
+use vm::{
+	self, tests::FakeExt, ActionParams, ActionValue, CallType, GasLeft, ReturnData, Vm, WasiEnviron,
+};
 
-use crate::WasmInterpreter;
+use crate::{
+	dirs::{self, EntryKind},
+	runtime::{eaddr2maddr, Runtime, RuntimeContext},
+	ModuleCache, WasmInterpreter,
+};
 
 macro_rules! load_sample {
 	($name: expr) => {{
@@ -46,7 +57,41 @@ fn test_finalize(res: Result<GasLeft, vm::Error>) -> Result<(u64, ReturnData, bo
 }
 
 fn wasm_interpreter() -> WasmInterpreter {
-	WasmInterpreter
+	WasmInterpreter::default()
+}
+
+/// Logs emitted by the `event` sample when running at `address`.
+fn sample_event_logs(address: Address) -> Vec<LogEntry> {
+	let mut params = ActionParams::default();
+	params.gas = U256::from(1_000_000);
+	params.code = Some(Arc::new(load_sample!("event")));
+	let mut ext = FakeExt::new().with_wasm();
+	test_finalize(wasm_interpreter().exec(params, &mut ext)).unwrap();
+
+	ext.logs
+		.into_iter()
+		.map(|log| LogEntry { address, ..log })
+		.collect()
+}
+
+fn runtime(ext: &mut FakeExt, address: Address) -> Runtime {
+	let memory = wasmi::MemoryInstance::alloc(wasmi::memory_units::Pages(1), None).unwrap();
+	Runtime::with_params(
+		ext,
+		memory,
+		1_000_000,
+		Vec::new(),
+		RuntimeContext {
+			address,
+			sender: Address::zero(),
+			origin: Address::zero(),
+			code_address: address,
+			call_type: CallType::Call,
+			gas_price: U256::zero(),
+			value: U256::zero(),
+			aad_str: String::new(),
+		},
+	)
 }
 
 #[test]
@@ -96,6 +141,183 @@ fn gas_limit() {
 	test_finalize(interpreter.exec(params, &mut ext)).unwrap_err();
 }
 
+#[test]
+fn wasm_policy() {
+	let code = load_sample!("empty");
+
+	let mut params = ActionParams::default();
+	params.gas = U256::from(100_000);
+	params.code = Some(Arc::new(code));
+	let mut ext = FakeExt::new().with_wasm();
+	ext.is_create = true;
+	ext.schedule.wasm.as_mut().unwrap().policy.max_initial_pages = 0;
+
+	// enforced ahead of execution, whichever runtime is used
+	match crate::check_policy(&params, &ext.schedule) {
+		Err(vm::Error::Wasm(reason)) => assert!(reason.starts_with("Wasm policy violation")),
+		res => panic!("expected a policy violation, got {:?}", res),
+	}
+	// and by wasmi when instantiating the module
+	test_finalize(wasm_interpreter().exec(params, &mut ext)).unwrap_err();
+}
+
+#[test]
+fn validate_deploy() {
+	let code = load_sample!("empty");
+
+	let mut params = ActionParams::default();
+	params.code = Some(Arc::new(code.clone()));
+	let mut schedule = FakeExt::new().with_wasm().schedule;
+	schedule.wasm_validation = true;
+	crate::validate(&params, &schedule).unwrap();
+
+	// instrumentation only ever grows the module
+	schedule.create_data_limit = code.len();
+	match crate::validate(&params, &schedule) {
+		Err(vm::Error::Wasm(reason)) => assert!(reason.contains("instrumented module")),
+		res => panic!("expected the module to be too large, got {:?}", res),
+	}
+}
+
+#[test]
+fn trap_backtrace() {
+	let code = load_sample!("empty");
+	let module =
+		parity_wasm::elements::deserialize_buffer::<parity_wasm::elements::Module>(&code).unwrap();
+	let imported_fns = module.import_count(parity_wasm::elements::ImportCountType::Function) as u32;
+	let defined_fns = module.code_section().unwrap().bodies().len() as u32;
+
+	// the last body is beyond the module, as stack limiter thunks are
+	let trap = wasmi::Trap::new(wasmi::TrapKind::DivisionByZero);
+	match crate::trap_error(&trap, &[0, 2, defined_fns], &code) {
+		vm::Error::WasmTrap(trap) => {
+			assert_eq!(trap.kind, "DivisionByZero");
+			let indices: Vec<_> = trap.backtrace.iter().map(|frame| frame.index).collect();
+			assert_eq!(indices, vec![imported_fns + 2, imported_fns]);
+		}
+		err => panic!("expected a wasm trap, got {:?}", err),
+	}
+}
+
+#[test]
+fn indirect_call_stack() {
+	let mut ext = FakeExt::new().with_wasm();
+	let mut runtime = runtime(&mut ext, Address::zero());
+	runtime.oasis_enter(0).unwrap();
+
+	// to an imported function, which enters nothing
+	runtime.oasis_enter(crate::parser::INDIRECT_CALL).unwrap();
+	runtime.oasis_leave().unwrap();
+	assert_eq!(runtime.call_stack, vec![0]);
+
+	// to a function of the module
+	runtime.oasis_enter(crate::parser::INDIRECT_CALL).unwrap();
+	runtime.oasis_enter(1).unwrap();
+	runtime.oasis_enter(2).unwrap();
+	runtime.oasis_leave().unwrap();
+	assert_eq!(runtime.call_stack, vec![0, crate::parser::INDIRECT_CALL, 1]);
+	runtime.oasis_leave().unwrap();
+	assert_eq!(runtime.call_stack, vec![0]);
+}
+
+#[test]
+fn profile() {
+	let mut params = ActionParams::default();
+	params.gas = U256::from(1_000_000);
+	params.value = ActionValue::Transfer(0.into());
+	params.code = Some(Arc::new(load_sample!("event")));
+	let mut ext = FakeExt::new().with_wasm();
+
+	let (result, profile) = wasm_interpreter().profile(params.clone(), &mut ext);
+	let (gas_left, _, _) = test_finalize(result).unwrap();
+
+	assert_eq!(profile.gas_used, 1_000_000 - gas_left);
+	assert!(!profile.functions.is_empty());
+	let function_gas: u64 = profile.functions.iter().map(|f| f.gas).sum();
+	let host_call_gas: u64 = profile.host_calls.iter().map(|c| c.gas).sum();
+	assert!(function_gas + host_call_gas <= profile.gas_used);
+	assert!(profile.peak_memory_pages > 0);
+	assert!(profile
+		.functions
+		.windows(2)
+		.all(|pair| pair[0].gas >= pair[1].gas));
+}
+
+#[test]
+fn profile_out_of_gas() {
+	let mut params = ActionParams::default();
+	params.gas = U256::from(1_000_000);
+	params.value = ActionValue::Transfer(0.into());
+	params.code = Some(Arc::new(load_sample!("event")));
+
+	let (result, full_profile) =
+		wasm_interpreter().profile(params.clone(), &mut FakeExt::new().with_wasm());
+	let (gas_left, _, _) = test_finalize(result).unwrap();
+
+	// the profile of a failed execution covers it up to the failure
+	params.gas = U256::from((1_000_000 - gas_left) * 9 / 10);
+	let (result, profile) = wasm_interpreter().profile(params, &mut FakeExt::new().with_wasm());
+	assert!(result.is_err());
+	assert!(profile.gas_used > 0);
+	assert!(profile.gas_used < full_profile.gas_used);
+}
+
+#[test]
+fn module_cache() {
+	let code = load_sample!("empty");
+
+	let mut params = ActionParams::default();
+	params.gas = U256::from(100_000);
+	params.code_hash = Some(keccak(&code));
+	params.code = Some(Arc::new(code));
+	let mut ext = FakeExt::new().with_wasm();
+	let cache = Arc::new(ModuleCache::default());
+
+	let mut interpreter = WasmInterpreter::new(cache.clone());
+	interpreter.prepare(&params, &mut ext).unwrap();
+	assert!(cache.current_size() > 0);
+	let (gas_left, _, apply_state) =
+		test_finalize(interpreter.exec(params.clone(), &mut ext)).unwrap();
+	assert!(apply_state);
+
+	// a fresh interpreter runs the cached module, charging as much gas as compiling it
+	let (uncached_gas_left, _, _) =
+		test_finalize(wasm_interpreter().exec(params.clone(), &mut ext)).unwrap();
+	let (cached_gas_left, _, _) =
+		test_finalize(WasmInterpreter::new(cache).exec(params, &mut ext)).unwrap();
+	assert_eq!(gas_left, uncached_gas_left);
+	assert_eq!(cached_gas_left, uncached_gas_left);
+}
+
+#[test]
+fn module_cache_fingerprints_every_cost() {
+	let costs = vm::WasmCosts::default();
+	let fingerprint = crate::cache::fingerprint(&costs);
+	assert_eq!(fingerprint, crate::cache::fingerprint(&costs.clone()));
+
+	let mut imports = costs.clone();
+	imports.policy.allowed_imports = Some(Some("env.gas".to_owned()).into_iter().collect());
+	let mut no_imports = costs.clone();
+	no_imports.policy.allowed_imports = Some(Default::default());
+	let mut regular = costs.clone();
+	regular.regular += 1;
+	for other in &[imports, no_imports, regular] {
+		assert_ne!(fingerprint, crate::cache::fingerprint(other));
+	}
+}
+
+#[test]
+fn prepared_compile_errors_fail_exec() {
+	let mut params = ActionParams::default();
+	params.gas = U256::from(100_000);
+	params.code = Some(Arc::new(b"not wasm".to_vec()));
+	let mut ext = FakeExt::new().with_wasm();
+
+	let mut interpreter = wasm_interpreter();
+	interpreter.prepare(&params, &mut ext).unwrap();
+	assert!(interpreter.exec(params, &mut ext).is_err());
+}
+
 #[test]
 fn envs() {
 	let code = load_sample!("envs");
@@ -132,6 +354,241 @@ fn envs() {
 	);
 }
 
+#[test]
+fn envs_v2() {
+	let code = load_sample!("envs");
+	let address_str = "0f572e5295c57f15886f9b263e2f6d2d6c7b5ec6";
+	let address: Address = address_str.parse().unwrap();
+
+	let mut params = ActionParams::default();
+	params.address = address.clone();
+	params.gas = U256::from(1_000_000);
+	params.code = Some(Arc::new(code));
+	let mut ext = FakeExt::new().with_wasm();
+	ext.schedule.wasi_environ = WasiEnviron::V2;
+	ext.info.number = 42;
+	ext.info.timestamp = 1_500_000_000;
+
+	// contracts reading the first version of the environment keep working
+	let mut interpreter = wasm_interpreter();
+	test_finalize(interpreter.exec(params, &mut ext)).unwrap();
+	assert_eq!(
+		ext.store.get(b"address".as_ref()).unwrap(),
+		&address_str.as_bytes()
+	);
+
+	let sload_gas = ext.schedule.sload_gas as u64;
+	let mut v2_runtime = runtime(&mut ext, address);
+	let environ = v2_runtime.environ().unwrap();
+	// the expiry and confidentiality lookups are each charged as a storage read, once
+	assert_eq!(v2_runtime.gas_counter, 2 * sload_gas);
+	assert_eq!(v2_runtime.environ().unwrap(), environ);
+	assert_eq!(v2_runtime.gas_counter, 2 * sload_gas);
+	assert_eq!(
+		&environ[4..],
+		&[
+			"ORIGIN=0000000000000000000000000000000000000000".to_owned(),
+			format!("CODE_ADDRESS={}", address_str),
+			"BLOCK_NUMBER=42".to_owned(),
+			"TIMESTAMP=1500000000".to_owned(),
+			"GAS_PRICE=0".to_owned(),
+			format!("EXPIRY={}", u64::max_value()),
+			"CONFIDENTIAL=false".to_owned(),
+		]
+	);
+
+	ext.schedule.wasi_environ = WasiEnviron::V1;
+	let mut v1_runtime = runtime(&mut ext, address);
+	let environ = v1_runtime.environ().unwrap();
+	assert_eq!(environ.len(), 4);
+	assert_eq!(v1_runtime.gas_counter, 0);
+}
+
+#[test]
+fn wide_values() {
+	let address: Address = "0f572e5295c57f15886f9b263e2f6d2d6c7b5ec6".parse().unwrap();
+	let narrow = common_types::u256_from_u128(u128::max_value());
+	let wide = narrow + U256::one();
+	let (rich, richer, callee) = (
+		Address::from([1u8; 20]),
+		Address::from([2u8; 20]),
+		Address::from([3u8; 20]),
+	);
+
+	// values above 64 bits used to panic when building the environment
+	let mut params = ActionParams::default();
+	params.gas = U256::from(1_000_000);
+	params.value = ActionValue::Transfer(wide);
+	params.code = Some(Arc::new(load_sample!("empty")));
+	let mut ext = FakeExt::new().with_wasm();
+	test_finalize(wasm_interpreter().exec(params, &mut ext)).unwrap();
+
+	let mut ext = FakeExt::new().with_wasm();
+	ext.balances.insert(rich, narrow);
+	ext.balances.insert(richer, wide);
+	let mut runtime = runtime(&mut ext, address);
+	runtime.context.value = wide;
+
+	assert!(runtime
+		.environ()
+		.unwrap()
+		.contains(&format!("VALUE={}", wide)));
+
+	assert_eq!(runtime.value(), 0);
+	assert_eq!(
+		runtime.check_value_overflow(),
+		Err(crate::runtime::Error::ValueOverflow)
+	);
+	assert_eq!(runtime.check_value_overflow(), Ok(()));
+
+	let meta = runtime.account_meta_at(eaddr2maddr(&rich));
+	assert_eq!(meta.map(|meta| meta.balance), Some(u128::max_value()));
+	assert_eq!(runtime.check_value_overflow(), Ok(()));
+
+	assert!(runtime.account_meta_at(eaddr2maddr(&richer)).is_none());
+	assert_eq!(
+		runtime.check_value_overflow(),
+		Err(crate::runtime::Error::ValueOverflow)
+	);
+
+	// values passed to nested transactions keep all of their 128 bits
+	runtime.transact(*eaddr2maddr(&callee), u128::max_value(), &[]);
+	let call = ext.calls.iter().next().unwrap();
+	assert_eq!(call.value, Some(narrow));
+}
+
+#[test]
+fn argv() {
+	let address_str = "0f572e5295c57f15886f9b263e2f6d2d6c7b5ec6";
+	let address: Address = address_str.parse().unwrap();
+	let mut ext = FakeExt::new().with_wasm();
+	ext.schedule.wasi_args = true;
+	ext.depth = 2;
+	ext.info.number = 42;
+
+	let mut v2_runtime = runtime(&mut ext, address);
+	v2_runtime.args = vec![0xde, 0xad, 0xbe, 0xef, 0x01];
+	assert_eq!(
+		v2_runtime.argv(),
+		[address_str, "deadbeef", "call", "2", "42"]
+	);
+
+	v2_runtime.args = vec![0xde];
+	v2_runtime.context.call_type = CallType::StaticCall;
+	assert_eq!(
+		v2_runtime.argv(),
+		[address_str, "", "staticcall", "2", "42"]
+	);
+
+	// historical blocks keep an empty argv, whatever their environment
+	ext.schedule.wasi_args = false;
+	ext.schedule.wasi_environ = WasiEnviron::V2;
+	let mut v1_runtime = runtime(&mut ext, address);
+	v1_runtime.args = vec![0xde, 0xad, 0xbe, 0xef];
+	assert!(v1_runtime.argv().is_empty());
+}
+
+fn clock_subscription(userdata: u64, clock_id: u32, timeout: u64, abstime: bool) -> Vec<u8> {
+	let mut subscription = vec![0u8; 56];
+	subscription[0..8].copy_from_slice(&userdata.to_le_bytes());
+	subscription[24..28].copy_from_slice(&clock_id.to_le_bytes());
+	subscription[32..40].copy_from_slice(&timeout.to_le_bytes());
+	subscription[48] = abstime as u8;
+	subscription
+}
+
+#[test]
+fn clocks_advance_with_gas() {
+	let mut ext = FakeExt::new().with_wasm();
+	ext.schedule.wasi_clocks = true;
+	ext.info.timestamp = 10;
+	let mut runtime = runtime(&mut ext, Address::zero());
+
+	assert_eq!(runtime.clock_now(0), Some(10_000_000_000));
+	assert_eq!(runtime.clock_now(2), Some(0));
+	assert_eq!(runtime.clock_now(4), None);
+
+	assert!(runtime.charge_gas(100));
+	assert_eq!(runtime.clock_now(1), Some(10_000_000_100));
+	assert_eq!(runtime.clock_now(3), Some(100));
+
+	// the rate is part of the schedule
+	ext.schedule.wasm.as_mut().unwrap().clock_nanos_per_gas = 1000;
+	let mut slower = self::runtime(&mut ext, Address::zero());
+	assert!(slower.charge_gas(100));
+	assert_eq!(slower.clock_now(2), Some(100_000));
+}
+
+#[test]
+fn clocks_stand_still_before_the_transition() {
+	let mut ext = FakeExt::new().with_wasm();
+	ext.info.timestamp = 10;
+	let mut runtime = runtime(&mut ext, Address::zero());
+
+	assert!(runtime.charge_gas(100));
+	assert_eq!(runtime.clock_now(0), Some(10_000_000_000));
+	assert_eq!(runtime.clock_now(1), Some(10_000_000_000));
+	assert_eq!(runtime.clock_now(2), None);
+	assert_eq!(runtime.clock_now(3), None);
+}
+
+#[test]
+fn poll_oneoff() {
+	let mut ext = FakeExt::new().with_wasm();
+	ext.schedule.wasi_clocks = true;
+	let mut runtime = runtime(&mut ext, Address::zero());
+
+	// the process clock subscription is the earliest one
+	let subscriptions = [
+		clock_subscription(1, 1, 500, false),
+		clock_subscription(2, 2, 200, true),
+		clock_subscription(3, 1, 10_000, false),
+	]
+	.concat();
+	let events = runtime.poll(&subscriptions).unwrap().unwrap();
+	assert_eq!(events.len(), 32);
+	assert_eq!(&events[0..8], &2u64.to_le_bytes());
+	assert_eq!(runtime.clock_now(2), Some(200));
+
+	// fd subscriptions occur at once
+	let mut fd_read = vec![0u8; 56];
+	fd_read[0..8].copy_from_slice(&7u64.to_le_bytes());
+	fd_read[8] = 1;
+	let subscriptions = [fd_read, clock_subscription(1, 1, 500, false)].concat();
+	let events = runtime.poll(&subscriptions).unwrap().unwrap();
+	assert_eq!(events.len(), 32);
+	assert_eq!(&events[0..8], &7u64.to_le_bytes());
+	assert_eq!(events[10], 1);
+	assert_eq!(runtime.clock_now(2), Some(200));
+
+	assert_eq!(runtime.poll(&[]).unwrap(), Err(ErrNo::Inval));
+	// only the lowest bit of the u16 flags is `abstime`
+	let mut subscription = clock_subscription(1, 2, 300, false);
+	subscription[49] = 1;
+	runtime.poll(&subscription).unwrap().unwrap();
+	assert_eq!(runtime.clock_now(2), Some(500));
+	// the clocks saturate rather than overflow
+	let subscription = clock_subscription(1, 1, u64::max_value(), false);
+	assert!(runtime.poll(&subscription).unwrap().is_ok());
+	assert_eq!(runtime.clock_now(3), Some(u64::max_value()));
+	assert_eq!(runtime.gas_counter, 0);
+}
+
+#[test]
+fn poll_oneoff_sleeps_without_charging_the_wait() {
+	let mut ext = FakeExt::new().with_wasm();
+	ext.schedule.wasi_clocks = true;
+	ext.info.timestamp = 10;
+	let mut runtime = runtime(&mut ext, Address::zero());
+
+	// a one second timeout, like a contract retrying after a delay
+	let subscription = clock_subscription(1, 1, 1_000_000_000, false);
+	let events = runtime.poll(&subscription).unwrap().unwrap();
+	assert_eq!(&events[0..8], &1u64.to_le_bytes());
+	assert_eq!(runtime.clock_now(1), Some(11_000_000_000));
+	assert_eq!(runtime.gas_counter, 0);
+}
+
 #[test]
 fn io() {
 	let code = load_sample!("io");
@@ -149,6 +606,32 @@ fn io() {
 		output,
 		"the input was: hello, world!\nthe aad was: hello, gateway!\n"
 	);
+
+	let stdout: Vec<u8> = ext
+		.wasm_output
+		.iter()
+		.filter(|(fd, _)| *fd == 1)
+		.flat_map(|(_, data)| data.iter().copied())
+		.collect();
+	assert_eq!(stdout, output.as_bytes());
+}
+
+#[test]
+fn output_capture_limit() {
+	let mut ext = FakeExt::new().with_wasm();
+	{
+		let mut runtime = runtime(&mut ext, Address::zero());
+		let stderr = vec![b'!'; crate::wasi::OUTPUT_CAPTURE_LIMIT - 1];
+		runtime.capture_output(2, &[IoSlice::new(&stderr), IoSlice::new(b"ab")]);
+		runtime.capture_output(1, &[IoSlice::new(b"c")]);
+	}
+	assert_eq!(ext.wasm_output.len(), 1);
+	assert_eq!(ext.wasm_output[0].0, 2);
+	assert_eq!(
+		ext.wasm_output[0].1.len(),
+		crate::wasi::OUTPUT_CAPTURE_LIMIT
+	);
+	assert_eq!(ext.wasm_output[0].1.last(), Some(&b'a'));
 }
 
 #[test]
@@ -187,6 +670,160 @@ fn event() {
 	assert_eq!(&log_entry.data, b"hello, world!");
 }
 
+/// Makes a change to the namespace of `ext`, applying its writes even if it fails.
+fn change_dirs<T>(
+	ext: &mut FakeExt,
+	change: impl FnOnce(&mut dirs::Namespace) -> dirs::Result<T>,
+) -> dirs::Result<T> {
+	let mut namespace = dirs::Namespace::new(&*ext);
+	let result = change(&mut namespace);
+	let (_, _, writes) = namespace.finish();
+	dirs::apply(ext, writes);
+	result
+}
+
+fn dir_names(ext: &mut FakeExt, path: &str) -> Vec<String> {
+	change_dirs(ext, |namespace| namespace.entries(path))
+		.unwrap()
+		.into_iter()
+		.map(|entry| entry.name)
+		.collect()
+}
+
+#[test]
+fn dirs_create_list_remove() {
+	let mut ext = FakeExt::new().with_wasm();
+	change_dirs(&mut ext, |namespace| namespace.create_dir("a")).unwrap();
+	change_dirs(&mut ext, |namespace| namespace.create_dir("a/b")).unwrap();
+	assert_eq!(
+		change_dirs(&mut ext, |namespace| namespace.create_dir("a")),
+		Err(ErrNo::Exist)
+	);
+	assert_eq!(
+		change_dirs(&mut ext, |namespace| namespace.create_dir("c/d")),
+		Err(ErrNo::NoEnt)
+	);
+
+	ext.store.insert(b"a/file".to_vec(), b"contents".to_vec());
+	change_dirs(&mut ext, |namespace| {
+		namespace.add_entry("a/file", EntryKind::File)?;
+		namespace.add_entry("a/unwritten", EntryKind::File)
+	})
+	.unwrap();
+	assert_eq!(dir_names(&mut ext, ""), ["a"]);
+	assert_eq!(dir_names(&mut ext, "a"), ["b", "file"]);
+	assert_eq!(
+		change_dirs(&mut ext, |namespace| namespace.entries("a/file")),
+		Err(ErrNo::NotDir)
+	);
+
+	assert_eq!(
+		change_dirs(&mut ext, |namespace| namespace.remove_dir("a")),
+		Err(ErrNo::NotEmpty)
+	);
+	change_dirs(&mut ext, |namespace| namespace.remove_dir("a/b")).unwrap();
+	assert_eq!(dirs::Namespace::new(&ext).kind("a/b"), None);
+	assert_eq!(dir_names(&mut ext, "a"), ["file"]);
+}
+
+#[test]
+fn dirs_rename() {
+	let mut ext = FakeExt::new().with_wasm();
+	ext.store.insert(b"a/b/file".to_vec(), b"contents".to_vec());
+	change_dirs(&mut ext, |namespace| {
+		namespace.create_dir("a")?;
+		namespace.create_dir("a/b")?;
+		namespace.add_entry("a/b/file", EntryKind::File)
+	})
+	.unwrap();
+
+	assert_eq!(
+		change_dirs(&mut ext, |namespace| namespace.rename("a", "a/b/c")),
+		Err(ErrNo::Inval)
+	);
+	assert_eq!(
+		change_dirs(&mut ext, |namespace| namespace.rename("a/b/file", "a")),
+		Err(ErrNo::IsDir)
+	);
+	change_dirs(&mut ext, |namespace| namespace.rename("a", "z")).unwrap();
+
+	assert_eq!(dir_names(&mut ext, ""), ["z"]);
+	assert_eq!(dir_names(&mut ext, "z/b"), ["file"]);
+	assert_eq!(
+		ext.store.get(b"z/b/file".as_ref()).map(Vec::as_slice),
+		Some(b"contents".as_ref())
+	);
+	assert!(!ext.store.contains_key(b"a/b/file".as_ref()));
+}
+
+#[test]
+fn dirs_symlinks() {
+	let mut ext = FakeExt::new().with_wasm();
+	change_dirs(&mut ext, |namespace| {
+		namespace.create_dir("a")?;
+		namespace.symlink("a", "link")?;
+		namespace.symlink("../link/file", "a/up")?;
+		namespace.symlink("loop", "loop")
+	})
+	.unwrap();
+
+	let mut namespace = dirs::Namespace::new(&ext);
+	assert_eq!(namespace.readlink("link"), Ok(b"a".to_vec()));
+	assert_eq!(namespace.resolve("link/file"), Ok("a/file".to_owned()));
+	assert_eq!(namespace.resolve("a/up"), Ok("a/file".to_owned()));
+	assert_eq!(namespace.resolve_parent("link/up"), Ok("a/up".to_owned()));
+	assert_eq!(namespace.resolve("loop"), Err(ErrNo::Loop));
+	assert_eq!(dir_names(&mut ext, ""), ["a", "link", "loop"]);
+
+	change_dirs(&mut ext, |namespace| Ok(namespace.remove_link("link"))).unwrap();
+	assert_eq!(
+		dirs::Namespace::new(&ext).resolve("link/file"),
+		Ok("link/file".to_owned())
+	);
+	assert_eq!(dir_names(&mut ext, ""), ["a", "loop"]);
+}
+
+#[test]
+fn dirs_writes_are_buffered_and_reads_counted() {
+	let mut ext = FakeExt::new().with_wasm();
+	change_dirs(&mut ext, |namespace| namespace.symlink("target", "link")).unwrap();
+
+	let mut namespace = dirs::Namespace::new(&ext);
+	namespace.create_dir("a").unwrap();
+	assert_eq!(namespace.kind("a"), Some(EntryKind::Dir));
+	assert_eq!(namespace.readlink("link"), Ok(b"target".to_vec()));
+	let (lookups, bytes_read, writes) = namespace.finish();
+	// `create_dir` finds nothing at `a` and reads the home listing, `readlink`
+	// finds the link and reads its target
+	assert_eq!(lookups, 3 + 1 + 2 + 1);
+	assert_eq!(
+		bytes_read,
+		"\x01llink\0".len() as u64 + "target".len() as u64
+	);
+	assert_eq!(writes.len(), 2);
+	assert!(!ext.store.keys().any(|key| key.ends_with(b"a")));
+}
+
+#[test]
+fn dirs_dirents() {
+	let entries = [
+		dirs::Entry {
+			name: "dir".to_owned(),
+			kind: EntryKind::Dir,
+		},
+		dirs::Entry {
+			name: "file".to_owned(),
+			kind: EntryKind::File,
+		},
+	];
+	let dirents = dirs::dirents(&entries, 1);
+	assert_eq!(dirents.len(), 24 + "file".len());
+	assert_eq!(&dirents[..8], &2u64.to_le_bytes());
+	assert_eq!(&dirents[16..21], &[4, 0, 0, 0, 4]);
+	assert_eq!(&dirents[24..], b"file");
+	assert!(dirs::dirents(&entries, 2).is_empty());
+}
+
 #[test]
 fn revert_panic() {
 	let code = load_sample!("revert_panic");
@@ -325,6 +962,65 @@ fn xcc() {
 	);
 }
 
+#[test]
+fn xcc_events() {
+	let code = load_sample!("xcc_a");
+	let callee_address: Address = Address::from([1u8; std::mem::size_of::<Address>()]);
+
+	let mut params = ActionParams::default();
+	params.data = Some(callee_address.to_vec());
+	params.gas = U256::from(1_000_000);
+	params.code = Some(Arc::new(code));
+	let mut ext = FakeExt::new().with_wasm();
+	ext.callee_logs
+		.insert(callee_address, sample_event_logs(callee_address));
+
+	let mut interpreter = wasm_interpreter();
+	test_finalize(interpreter.exec(params, &mut ext)).unwrap();
+
+	assert_eq!(ext.logs, sample_event_logs(callee_address));
+}
+
+#[test]
+fn receipt_events() {
+	let caller_address: Address = "0f572e5295c57f15886f9b263e2f6d2d6c7b5ec6".parse().unwrap();
+	let callee_address: Address = Address::from([1u8; std::mem::size_of::<Address>()]);
+
+	let mut ext = FakeExt::new().with_wasm();
+	ext.callee_logs
+		.insert(callee_address, sample_event_logs(callee_address));
+	let mut runtime = runtime(&mut ext, caller_address);
+
+	// events of the caller are not part of the receipt
+	runtime.emit(&[b"caller"], b"before the call");
+	let receipt = runtime.transact(*eaddr2maddr(&callee_address), 0, b"");
+	assert_eq!(receipt.outcome(), TransactionOutcome::Success);
+
+	let events = receipt.events();
+	assert_eq!(events.len(), 1);
+	assert_eq!(&events[0].emitter, eaddr2maddr(&callee_address));
+	let topics = events[0]
+		.topics
+		.iter()
+		.map(|topic| ethereum_types::H256::from(*topic))
+		.collect::<Vec<_>>();
+	assert_eq!(
+		topics,
+		vec![
+			ethereum_types::H256::from(
+				"0404040400000000000000000000000000000000000000000000000000000000"
+			),
+			ethereum_types::H256::from(
+				"0505050505000000000000000000000000000000000000000000000000000000"
+			),
+			ethereum_types::H256::from(
+				"0606060606060000000000000000000000000000000000000000000000000000"
+			),
+		]
+	);
+	assert_eq!(&events[0].data, b"hello, world!");
+}
+
 #[test]
 fn math_factorial() {
 	let code = load_sample!("factorial");
@@ -0,0 +1,318 @@
+//! Static validation of contracts being deployed
+
+use parity_wasm::elements;
+use vm::{self, ActionParams};
+use wasmi::ModuleImportResolver;
+
+use crate::{env, func_index, parser, subst_main_call};
+
+/// Module names under which the runtime provides its functions.
+const IMPORT_MODULES: &[&str] = &["env", "wasi_unstable"];
+
+fn invalid(reason: String) -> vm::Error {
+	vm::Error::Wasm(format!("Invalid wasm contract: {}", reason))
+}
+
+/// Checks that the code of a contract being deployed with `params` can be run
+/// by wasmi under `schedule`, without executing any of it.
+///
+/// The module must be well formed, export `_start`, import only functions the
+/// runtime provides, comply with the wasm policy, and still fit in
+/// `create_data_limit` once instrumented. Imports are resolved as from
+/// `schedule.wasm_validation` on, under the module they name.
+pub fn validate(params: &ActionParams, schedule: &vm::Schedule) -> vm::Result<()> {
+	let wasm_costs = schedule
+		.wasm
+		.as_ref()
+		.ok_or_else(|| invalid("wasm is not activated".to_owned()))?;
+
+	let parser::ParsedModule { mut module, .. } = parser::payload(params)?;
+	check_module(&module, schedule)?;
+	subst_main_call(&mut module);
+
+	let module = parser::inject_gas_counter_and_stack_limiter(module, wasm_costs)?;
+	check_instrumented(&module, schedule)?;
+
+	wasmi::Module::from_parity_wasm_module(module).map_err(|e| invalid(format!("{:?}", e)))?;
+	Ok(())
+}
+
+/// Checks that the code of a contract being deployed with `params` complies with
+/// the wasm policy of `schedule`, whichever runtime is to execute it.
+pub fn check_policy(params: &ActionParams, schedule: &vm::Schedule) -> vm::Result<()> {
+	let wasm_costs = schedule
+		.wasm
+		.as_ref()
+		.ok_or_else(|| invalid("wasm is not activated".to_owned()))?;
+	let parser::ParsedModule { module, .. } = parser::payload(params)?;
+	parser::check_policy(&module, &wasm_costs.policy)
+}
+
+/// Checks the exports and imports of a module being deployed, before it is instrumented.
+fn check_module(module: &elements::Module, schedule: &vm::Schedule) -> vm::Result<()> {
+	if func_index(module, "_start").is_none() {
+		return Err(invalid("function `_start` is not exported".to_owned()));
+	}
+	if exports(module, "_oasis_deploy") && func_index(module, "_oasis_deploy").is_none() {
+		return Err(invalid(
+			"`_oasis_deploy` is exported, but is not a function".to_owned(),
+		));
+	}
+
+	let resolver = env::ImportResolver::with_schedule(schedule);
+	let imports = module
+		.import_section()
+		.map_or(&[][..], |section| section.entries());
+	for entry in imports {
+		if !IMPORT_MODULES.contains(&entry.module()) {
+			return Err(invalid(format!(
+				"import {}.{} is from an unknown module",
+				entry.module(),
+				entry.field()
+			)));
+		}
+		if entry.field() == parser::ENTER_FN || entry.field() == parser::LEAVE_FN {
+			return Err(invalid(format!(
+				"import {}.{} is reserved for the runtime",
+				entry.module(),
+				entry.field()
+			)));
+		}
+		match entry.external() {
+			elements::External::Function(type_idx) => {
+				let signature = signature(module, *type_idx).ok_or_else(|| {
+					invalid(format!(
+						"import {}.{} has an undefined type",
+						entry.module(),
+						entry.field()
+					))
+				})?;
+				let host = resolver
+					.module(entry.module())
+					.resolve_func(entry.field(), &signature)
+					.map_err(|_| {
+						invalid(format!(
+							"import {}.{} is not provided by the runtime",
+							entry.module(),
+							entry.field()
+						))
+					})?;
+				if host.signature() != &signature {
+					return Err(invalid(format!(
+						"import {}.{} has signature {:?}, expected {:?}",
+						entry.module(),
+						entry.field(),
+						signature,
+						host.signature()
+					)));
+				}
+			}
+			elements::External::Memory(_) if entry.field() == "memory" => (),
+			_ => {
+				return Err(invalid(format!(
+					"import {}.{} is not a function or the memory",
+					entry.module(),
+					entry.field()
+				)));
+			}
+		}
+	}
+
+	parser::check_policy(module, &schedule.wasm().policy)
+}
+
+/// Checks that an instrumented module being deployed does not exceed `create_data_limit`.
+fn check_instrumented(module: &elements::Module, schedule: &vm::Schedule) -> vm::Result<()> {
+	let size = elements::serialize(module.clone())
+		.map_err(|e| invalid(format!("cannot serialize instrumented module ({:?})", e)))?
+		.len();
+	if size > schedule.create_data_limit {
+		return Err(invalid(format!(
+			"instrumented module of {} bytes exceeds the limit of {}",
+			size, schedule.create_data_limit
+		)));
+	}
+	Ok(())
+}
+
+/// Returns whether the module exports anything under `name`.
+fn exports(module: &elements::Module, name: &str) -> bool {
+	module
+		.export_section()
+		.map_or(false, |s| s.entries().iter().any(|e| e.field() == name))
+}
+
+/// Returns the signature of the function type at `type_idx`.
+fn signature(module: &elements::Module, type_idx: u32) -> Option<wasmi::Signature> {
+	let elements::Type::Function(ty) = module
+		.type_section()
+		.and_then(|s| s.types().get(type_idx as usize))?;
+	Some(wasmi::Signature::new(
+		ty.params()
+			.iter()
+			.cloned()
+			.map(value_type)
+			.collect::<Vec<_>>(),
+		ty.return_type().map(value_type),
+	))
+}
+
+fn value_type(ty: elements::ValueType) -> wasmi::ValueType {
+	match ty {
+		elements::ValueType::I32 => wasmi::ValueType::I32,
+		elements::ValueType::I64 => wasmi::ValueType::I64,
+		elements::ValueType::F32 => wasmi::ValueType::F32,
+		elements::ValueType::F64 => wasmi::ValueType::F64,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use parity_wasm::builder;
+
+	/// A module exporting `_start` and importing `module.field` with the given params.
+	fn importing(module: &str, field: &str, params: Vec<elements::ValueType>) -> elements::Module {
+		importing_errno(module, field, params, None)
+	}
+
+	/// Like `importing`, for a function returning `ret`.
+	fn importing_errno(
+		module: &str,
+		field: &str,
+		params: Vec<elements::ValueType>,
+		ret: Option<elements::ValueType>,
+	) -> elements::Module {
+		builder::module()
+			.function()
+			.signature()
+			.with_params(params)
+			.with_return_type(ret)
+			.build()
+			.body()
+			.build()
+			.build()
+			.import()
+			.module(module)
+			.field(field)
+			.external()
+			.func(0)
+			.build()
+			.export()
+			.field("_start")
+			.internal()
+			.func(1)
+			.build()
+			.build()
+	}
+
+	fn schedule() -> vm::Schedule {
+		let mut schedule = vm::Schedule::default();
+		schedule.wasm = Some(Default::default());
+		schedule.wasm_validation = true;
+		schedule.wasi_dirs = true;
+		schedule.wasi_clocks = true;
+		schedule
+	}
+
+	fn diagnosis(module: &elements::Module) -> String {
+		match check_module(module, &schedule()) {
+			Err(vm::Error::Wasm(reason)) => reason,
+			res => panic!("expected the module to be invalid, got {:?}", res),
+		}
+	}
+
+	#[test]
+	fn accepts_runtime_imports() {
+		let module = importing("wasi_unstable", "proc_exit", vec![elements::ValueType::I32]);
+		check_module(&module, &schedule()).unwrap();
+	}
+
+	#[test]
+	fn resolves_legacy_imports_before_wasi_dirs() {
+		let readlink = |params: usize| {
+			importing_errno(
+				"wasi_unstable",
+				"path_readlink",
+				vec![elements::ValueType::I32; params],
+				Some(elements::ValueType::I32),
+			)
+		};
+		check_module(&readlink(6), &schedule()).unwrap();
+		assert!(diagnosis(&readlink(5)).contains("signature"));
+
+		let mut schedule = schedule();
+		schedule.wasi_dirs = false;
+		check_module(&readlink(5), &schedule).unwrap();
+		check_module(&readlink(6), &schedule).unwrap_err();
+	}
+
+	#[test]
+	fn requires_start() {
+		let module = builder::module()
+			.function()
+			.signature()
+			.build()
+			.body()
+			.build()
+			.build()
+			.build();
+		assert!(diagnosis(&module).contains("`_start`"));
+	}
+
+	#[test]
+	fn rejects_unsupported_imports() {
+		let module = importing("wasi_unstable", "proc_fork", vec![]);
+		assert!(diagnosis(&module).contains("not provided by the runtime"));
+
+		let module = importing("oasis", "proc_exit", vec![elements::ValueType::I32]);
+		assert!(diagnosis(&module).contains("unknown module"));
+
+		let module = importing("wasi_unstable", "proc_exit", vec![elements::ValueType::I64]);
+		assert!(diagnosis(&module).contains("signature"));
+
+		let module = importing("env", "proc_exit", vec![elements::ValueType::I32]);
+		assert!(diagnosis(&module).contains("not provided by the runtime"));
+	}
+
+	#[test]
+	fn resolves_legacy_imports_before_wasi_clocks() {
+		let poll = |params: usize| {
+			importing_errno(
+				"wasi_unstable",
+				"poll_oneoff",
+				vec![elements::ValueType::I32; params],
+				Some(elements::ValueType::I32),
+			)
+		};
+		check_module(&poll(4), &schedule()).unwrap();
+
+		let mut schedule = schedule();
+		schedule.wasi_clocks = false;
+		check_module(&poll(3), &schedule).unwrap();
+		check_module(&poll(4), &schedule).unwrap_err();
+	}
+
+	#[test]
+	fn resolves_imports_by_module_from_wasm_validation() {
+		let signature = wasmi::Signature::new(&[wasmi::ValueType::I32][..], None);
+		let mut schedule = schedule();
+		schedule.wasm_validation = false;
+		let resolver = env::ImportResolver::with_schedule(&schedule);
+		resolver
+			.module("env")
+			.resolve_func("proc_exit", &signature)
+			.unwrap();
+
+		schedule.wasm_validation = true;
+		let resolver = env::ImportResolver::with_schedule(&schedule);
+		resolver
+			.module("env")
+			.resolve_func("proc_exit", &signature)
+			.unwrap_err();
+		resolver
+			.module("wasi_unstable")
+			.resolve_func("proc_exit", &signature)
+			.unwrap();
+	}
+}
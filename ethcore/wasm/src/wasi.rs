@@ -1,3 +1,4 @@
+use std::cmp;
 use std::convert::TryInto;
 use std::io::{IoSlice, IoSliceMut};
 use std::path::Path;
@@ -6,24 +7,51 @@ use blockchain_traits::PendingTransaction as _;
 use wasi_types::*;
 use wasmi::P;
 
+use vm::{CallType, WasiEnviron};
+
+use crate::dirs::{self, EntryKind};
 use crate::runtime::RNG_HASH_BYTES;
-use crate::U256;
 
-const ADDR_LEN_BYTES: usize = std::mem::size_of::<oasis_types::Address>();
-const ADDR_CHARS: usize = ADDR_LEN_BYTES * 2; // two hex digits per byte
+/// Resolution reported for the clocks before `schedule.wasi_clocks`.
+const LEGACY_CLOCK_RESOLUTION_NANOS: u64 = 250 * 1_000_000;
+/// Size of a WASI `subscription`.
+const SUBSCRIPTION_SIZE: usize = 56;
+/// Size of a WASI `event`.
+const EVENT_SIZE: usize = 32;
+const EVENTTYPE_CLOCK: u8 = 0;
+const EVENTTYPE_FD_READ: u8 = 1;
+const EVENTTYPE_FD_WRITE: u8 = 2;
+const SUBSCRIPTION_CLOCK_ABSTIME: u16 = 1;
+/// Bytes of stdout and stderr captured for the tracer, per call frame. Every nested
+/// call has its own runtime and so its own limit, which bounds the output traced for
+/// a transaction by this times the number of calls it makes, themselves bounded by
+/// the gas of the transaction.
+pub(crate) const OUTPUT_CAPTURE_LIMIT: usize = 64 * 1024;
 
 macro_rules! bcfs {
 	( $self:ident . bcfs . $fn:ident ( $($args:expr),* )  ) => {
 		// Unsafety is required because BCFS is mutably borrowed with `self`
 		// but also takes a `PendingTransaction` which also happens to be `self.
 		// This is okay because BCFS doesn't modify itself through `PendingTransaction`.
-		match unsafe { &mut *$self.bcfs.get() }.$fn($self, $( $args ),* ) {
+		let result = unsafe { &mut *$self.bcfs.get() }.$fn($self, $( $args ),* );
+		// values BCFS got from the `PendingTransaction` may not have fit
+		$self.check_value_overflow()?;
+		match result {
 			Ok(result) => result,
 			Err(errno) => return Ok(errno)
 		}
 	}
 }
 
+macro_rules! try_errno {
+	($result:expr) => {
+		match $result {
+			Ok(result) => result,
+			Err(errno) => return Ok(errno),
+			}
+	};
+}
+
 macro_rules! read_path {
 	($self:ident, $path_ptr:expr, $path_len: expr) => {{
 		let path = std::path::Path::new(
@@ -54,18 +82,39 @@ impl<'a> crate::Runtime<'a> {
 	// not part of wasi, but required by parity
 	pub fn gas(&mut self, amount: u32) -> crate::Result<()> {
 		if self.charge_gas(amount as u64) {
+			if let (Some(profile), Some(&body)) = (&mut self.profile, self.call_stack.last()) {
+				profile.function(body, amount as u64);
+			}
 			Ok(())
 		} else {
 			Err(crate::runtime::Error::GasLimit.into())
 		}
 	}
 
-	pub fn args_get(&mut self, _argv: P<P<u8>>, _argv_buf: P<u8>) -> crate::Result<ErrNo> {
+	// not part of wasi, called by the instrumentation of `parser::inject_call_tracer`
+	pub fn oasis_enter(&mut self, body: u32) -> crate::Result<()> {
+		self.call_stack.push(body);
+		Ok(())
+	}
+
+	pub fn oasis_leave(&mut self) -> crate::Result<()> {
+		// the callee of an indirect call is left along with the mark entered before it
+		if self.call_stack.pop() != Some(crate::parser::INDIRECT_CALL)
+			&& self.call_stack.last() == Some(&crate::parser::INDIRECT_CALL)
+		{
+			self.call_stack.pop();
+		}
+		Ok(())
+	}
+
+	/// Writes the call metadata described by `Runtime::argv` as NUL-terminated arguments.
+	pub fn args_get(&mut self, argv: P<P<u8>>, argv_buf: P<u8>) -> crate::Result<ErrNo> {
+		self.write_strings(&self.argv(), argv, argv_buf)?;
 		Ok(ErrNo::Success)
 	}
 
-	pub fn args_sizes_get(&mut self, argc: P<u32>, _argv_buf_size: P<u32>) -> crate::Result<ErrNo> {
-		self.memory.set_value(argc, 0)?;
+	pub fn args_sizes_get(&mut self, argc: P<u32>, argv_buf_size: P<u32>) -> crate::Result<ErrNo> {
+		self.write_strings_sizes(&self.argv(), argc, argv_buf_size)?;
 		Ok(ErrNo::Success)
 	}
 
@@ -74,13 +123,16 @@ impl<'a> crate::Runtime<'a> {
 		clock_id: ClockId,
 		resolution: P<Timestamp>,
 	) -> crate::Result<ErrNo> {
-		self.memory.set_value(
-			resolution,
-			match clock_id {
-				ClockId::RealTime | ClockId::Monotonic => Timestamp::from_nanos(250 * 1_000_000),
-				_ => return Ok(ErrNo::Inval),
-			},
-		)?;
+		if self.clock_now(clock_id as u32).is_none() {
+			return Ok(ErrNo::Inval);
+		}
+		let nanos = if self.schedule().wasi_clocks {
+			cmp::max(self.schedule().wasm().clock_nanos_per_gas, 1) as u64
+		} else {
+			LEGACY_CLOCK_RESOLUTION_NANOS
+		};
+		self.memory
+			.set_value(resolution, Timestamp::from_nanos(nanos))?;
 		Ok(ErrNo::Success)
 	}
 
@@ -90,41 +142,16 @@ impl<'a> crate::Runtime<'a> {
 		_precision: Timestamp,
 		time: P<Timestamp>,
 	) -> crate::Result<ErrNo> {
-		match clock_id {
-			ClockId::RealTime | ClockId::Monotonic => self
-				.memory
-				.set_value(time, Timestamp::from_sec(self.ext.env_info().timestamp))?,
-			_ => return Ok(ErrNo::Inval),
+		match self.clock_now(clock_id as u32) {
+			Some(now) => self.memory.set_value(time, Timestamp::from_nanos(now))?,
+			None => return Ok(ErrNo::Inval),
 		}
 		Ok(ErrNo::Success)
 	}
 
-	pub fn environ_get(
-		&mut self,
-		environ: P<P<u8>>,
-		mut environ_buf: P<u8>,
-	) -> crate::Result<ErrNo> {
-		let environs = self.memory.get_mut(environ, 4)?;
-		environs[0] = environ_buf;
-		environ_buf = self.memory.set(
-			environ_buf,
-			format!("ADDRESS={:x}\0", self.context.address).as_bytes(),
-		)?;
-		environs[1] = environ_buf;
-		environ_buf = self.memory.set(
-			environ_buf,
-			format!("SENDER={:x}\0", self.context.sender).as_bytes(),
-		)?;
-		environs[2] = environ_buf;
-		environ_buf = self.memory.set(
-			environ_buf,
-			format!("AAD={}\0", self.context.aad_str).as_bytes(),
-		)?;
-		environs[3] = environ_buf;
-		self.memory.set(
-			environ_buf,
-			format!("VALUE={}\0", self.context.value_str).as_bytes(),
-		)?;
+	pub fn environ_get(&mut self, environ: P<P<u8>>, environ_buf: P<u8>) -> crate::Result<ErrNo> {
+		let environ_strings = self.environ()?;
+		self.write_strings(&environ_strings, environ, environ_buf)?;
 		Ok(ErrNo::Success)
 	}
 
@@ -133,17 +160,8 @@ impl<'a> crate::Runtime<'a> {
 		environ_count: P<u32>,
 		environ_buf_size: P<u32>,
 	) -> crate::Result<ErrNo> {
-		self.memory.set_value(environ_count, 4u32)?; // sender, address, aad, value
-		self.memory.set_value(
-			environ_buf_size,
-			#[rustfmt::skip] (
-				"SENDER=".len() + ADDR_CHARS +
-				"\0ADDRESS=".len() + ADDR_CHARS +
-				"\0AAD=".len() + self.context.aad_str.len() +
-				"\0VALUE=".len() + self.context.value_str.len() +
-				"\0".len()
-			) as u32,
-		)?;
+		let environ = self.environ()?;
+		self.write_strings_sizes(&environ, environ_count, environ_buf_size)?;
 		Ok(ErrNo::Success)
 	}
 
@@ -169,6 +187,9 @@ impl<'a> crate::Runtime<'a> {
 	}
 
 	pub fn fd_close(&mut self, fd: Fd) -> crate::Result<ErrNo> {
+		if self.dir_fds.close(fd) {
+			return Ok(ErrNo::Success);
+		}
 		bcfs!(self.bcfs.close(fd));
 		Ok(ErrNo::Success)
 	}
@@ -198,6 +219,10 @@ impl<'a> crate::Runtime<'a> {
 	}
 
 	pub fn fd_filestat_get(&mut self, fd: Fd, buf: P<FileStat>) -> crate::Result<ErrNo> {
+		if self.dir_fds.get(fd).is_some() {
+			self.memory.set_value(buf, self.dir_filestat())?;
+			return Ok(ErrNo::Success);
+		}
 		self.memory.set_value(buf, bcfs!(self.bcfs.filestat(fd)))?;
 		Ok(ErrNo::Success)
 	}
@@ -280,13 +305,36 @@ impl<'a> crate::Runtime<'a> {
 
 	pub fn fd_readdir(
 		&mut self,
-		_fd: Fd,
-		_buf: P<u8>,
-		_buf_len: u32,
-		_dircookie: DirCookie,
-		_buf_used: P<u32>,
+		fd: Fd,
+		buf: P<u8>,
+		buf_len: u32,
+		dircookie: DirCookie,
+		buf_used: P<u32>,
 	) -> crate::Result<ErrNo> {
-		Ok(ErrNo::NoSys) // unimplemented(todo): vfs doesn't have directories
+		if !self.schedule().wasi_dirs {
+			return Ok(ErrNo::NoSys);
+		}
+		let dir = match self.dir_fds.get(fd) {
+			Some((_, dir)) => dir.clone(),
+			None => {
+				// preopened directories are the home directory
+				bcfs!(self.bcfs.prestat(fd));
+				String::new()
+			}
+		};
+		let mut namespace = dirs::Namespace::new(&*self.ext);
+		let entries = namespace.entries(&dir);
+		let changes = namespace.finish();
+		let entries = try_errno!(self.update_dirs(changes, entries)?);
+
+		// a full buffer tells the caller to come back for the remaining entries
+		let dirents = dirs::dirents(&entries, u64::from(dircookie));
+		let used = std::cmp::min(dirents.len(), buf_len as usize);
+		self.memory
+			.get_mut::<_, u8>(buf, used)?
+			.copy_from_slice(&dirents[..used]);
+		self.memory.set_value(buf_used, used as u32)?;
+		Ok(ErrNo::Success)
 	}
 
 	pub fn fd_renumber(&mut self, from: Fd, to: Fd) -> crate::Result<ErrNo> {
@@ -328,11 +376,22 @@ impl<'a> crate::Runtime<'a> {
 
 	pub fn path_create_directory(
 		&mut self,
-		_fd: Fd,
-		_path: P<u8>,
-		_path_len: u32,
+		fd: Fd,
+		path: P<u8>,
+		path_len: u32,
 	) -> crate::Result<ErrNo> {
-		Ok(ErrNo::NoSys) // unimplemented(todo): vfs doesn't have directories
+		if !self.schedule().wasi_dirs {
+			return Ok(ErrNo::NoSys);
+		}
+		let path = read_path!(self, path, path_len);
+		let (_, path) = try_errno!(self.home_path(fd, path));
+		let mut namespace = dirs::Namespace::new(&*self.ext);
+		let created = namespace
+			.resolve_parent(&path)
+			.and_then(|path| namespace.create_dir(&path));
+		let changes = namespace.finish();
+		try_errno!(self.update_dirs(changes, created)?);
+		Ok(ErrNo::Success)
 	}
 
 	pub fn path_filestat_get(
@@ -344,9 +403,31 @@ impl<'a> crate::Runtime<'a> {
 		buf: P<FileStat>,
 	) -> crate::Result<ErrNo> {
 		let path = read_path!(self, path, path_len);
-		let fd = bcfs!(self
-			.bcfs
-			.open(dir_fd, path, OpenFlags::default(), FdFlags::APPEND));
+		if path.is_absolute() || !self.schedule().wasi_dirs {
+			let fd = bcfs!(self
+				.bcfs
+				.open(dir_fd, path, OpenFlags::default(), FdFlags::APPEND));
+			self.memory.set_value(buf, bcfs!(self.bcfs.filestat(fd)))?;
+			bcfs!(self.bcfs.close(fd));
+			return Ok(ErrNo::Success);
+		}
+		let (dir_fd, path) = try_errno!(self.home_path(dir_fd, path));
+		let mut namespace = dirs::Namespace::new(&*self.ext);
+		let resolved = namespace
+			.resolve(&path)
+			.map(|path| (namespace.kind(&path), path));
+		let changes = namespace.finish();
+		let (kind, path) = try_errno!(self.update_dirs(changes, resolved)?);
+		if kind == Some(EntryKind::Dir) {
+			self.memory.set_value(buf, self.dir_filestat())?;
+			return Ok(ErrNo::Success);
+		}
+		let fd = bcfs!(self.bcfs.open(
+			dir_fd,
+			Path::new(&path),
+			OpenFlags::default(),
+			FdFlags::APPEND
+		));
 		self.memory.set_value(buf, bcfs!(self.bcfs.filestat(fd)))?;
 		bcfs!(self.bcfs.close(fd));
 		Ok(ErrNo::Success)
@@ -375,7 +456,10 @@ impl<'a> crate::Runtime<'a> {
 		_new_path: P<u8>,
 		_new_path_len: u32,
 	) -> crate::Result<ErrNo> {
-		Ok(ErrNo::NoSys) // unimplemented(todo): vfs doesn't support links
+		if !self.schedule().wasi_dirs {
+			return Ok(ErrNo::NoSys);
+		}
+		Ok(ErrNo::NotSup) // unimplemented(dontneed): storage keys can't alias, use symlinks
 	}
 
 	pub fn path_open(
@@ -391,14 +475,50 @@ impl<'a> crate::Runtime<'a> {
 		p_fd: P<Fd>,
 	) -> crate::Result<ErrNo> {
 		let path = read_path!(self, path, path_len);
-		let fd = bcfs!(self // NB: mutable borrow of `self as PendingTransaction``
-			.bcfs
-			.open(dir_fd, path, open_flags, fd_flags));
+		if path.is_absolute() || !self.schedule().wasi_dirs {
+			let fd = bcfs!(self // NB: mutable borrow of `self as PendingTransaction``
+				.bcfs
+				.open(dir_fd, path, open_flags, fd_flags));
+			self.memory.set_value(p_fd, fd)?;
+			return Ok(ErrNo::Success);
+		}
+
+		let (dir_fd, path) = try_errno!(self.home_path(dir_fd, path));
+		let mut namespace = dirs::Namespace::new(&*self.ext);
+		let resolved = namespace
+			.resolve(&path)
+			.map(|path| (namespace.kind(&path), path));
+		let changes = namespace.finish();
+		let (kind, path) = try_errno!(self.update_dirs(changes, resolved)?);
+		let fd = if open_flags.contains(OpenFlags::DIRECTORY) || kind == Some(EntryKind::Dir) {
+			match kind {
+				Some(EntryKind::Dir) => self.dir_fds.open(dir_fd, path),
+				Some(_) => return Ok(ErrNo::NotDir),
+				None => return Ok(ErrNo::NoEnt),
+			}
+		} else {
+			let fd = bcfs!(self
+				.bcfs
+				.open(dir_fd, Path::new(&path), open_flags, fd_flags));
+			if kind.is_none() {
+				// the file is new, so list it in its directory
+				let mut namespace = dirs::Namespace::new(&*self.ext);
+				let added = namespace.add_entry(&path, EntryKind::File);
+				let changes = namespace.finish();
+				if let Err(errno) = self.update_dirs(changes, added)? {
+					bcfs!(self.bcfs.close(fd));
+					return Ok(errno);
+				}
+			}
+			fd
+		};
 		self.memory.set_value(p_fd, fd)?;
 		Ok(ErrNo::Success)
 	}
 
-	pub fn path_readlink(
+	/// `path_readlink` as provided before `schedule.wasi_dirs`, without the directory
+	/// descriptor of WASI, and when there were no links to read.
+	pub fn path_readlink_legacy(
 		&mut self,
 		_path: P<u8>,
 		_path_len: u32,
@@ -406,40 +526,107 @@ impl<'a> crate::Runtime<'a> {
 		_buf_len: u32,
 		_buf_used: P<u32>,
 	) -> crate::Result<ErrNo> {
-		Ok(ErrNo::NoSys) // unimplemented(todo): vfs doesn't support links
+		Ok(ErrNo::NoSys)
+	}
+
+	pub fn path_readlink(
+		&mut self,
+		fd: Fd,
+		path: P<u8>,
+		path_len: u32,
+		buf: P<u8>,
+		buf_len: u32,
+		buf_used: P<u32>,
+	) -> crate::Result<ErrNo> {
+		if !self.schedule().wasi_dirs {
+			return Ok(ErrNo::NoSys);
+		}
+		let path = read_path!(self, path, path_len);
+		let (_, path) = try_errno!(self.home_path(fd, path));
+		let mut namespace = dirs::Namespace::new(&*self.ext);
+		let target = namespace
+			.resolve_parent(&path)
+			.and_then(|path| namespace.readlink(&path));
+		let changes = namespace.finish();
+		let target = try_errno!(self.update_dirs(changes, target)?);
+		let used = std::cmp::min(target.len(), buf_len as usize);
+		self.memory.set(buf, &target[..used])?;
+		self.memory.set_value(buf_used, used as u32)?;
+		Ok(ErrNo::Success)
 	}
 
 	pub fn path_remove_directory(
 		&mut self,
-		_fd: Fd,
-		_path: P<u8>,
-		_path_len: u32,
+		fd: Fd,
+		path: P<u8>,
+		path_len: u32,
 	) -> crate::Result<ErrNo> {
-		Ok(ErrNo::NoSys) // unimplemented(todo): vfs doesn't have directories
+		if !self.schedule().wasi_dirs {
+			return Ok(ErrNo::NoSys);
+		}
+		let path = read_path!(self, path, path_len);
+		let (_, path) = try_errno!(self.home_path(fd, path));
+		let mut namespace = dirs::Namespace::new(&*self.ext);
+		let removed = namespace
+			.resolve_parent(&path)
+			.and_then(|path| namespace.remove_dir(&path));
+		let changes = namespace.finish();
+		try_errno!(self.update_dirs(changes, removed)?);
+		Ok(ErrNo::Success)
 	}
 
 	pub fn path_rename(
 		&mut self,
-		_old_fd: Fd,
+		old_fd: Fd,
 		_old_lookup_flags: LookupFlags,
-		_old_path: P<u8>,
-		_old_path_len: u32,
-		_new_fd: Fd,
-		_new_path: P<u8>,
-		_new_path_len: u32,
+		old_path: P<u8>,
+		old_path_len: u32,
+		new_fd: Fd,
+		new_path: P<u8>,
+		new_path_len: u32,
 	) -> crate::Result<ErrNo> {
-		Ok(ErrNo::NoSys) // unimplemented(todo): vfs doesn't support rename
+		if !self.schedule().wasi_dirs {
+			return Ok(ErrNo::NoSys);
+		}
+		let old_path = read_path!(self, old_path, old_path_len);
+		let new_path = read_path!(self, new_path, new_path_len);
+		let (_, old_path) = try_errno!(self.home_path(old_fd, old_path));
+		let (_, new_path) = try_errno!(self.home_path(new_fd, new_path));
+		let mut namespace = dirs::Namespace::new(&*self.ext);
+		let renamed = namespace.resolve_parent(&old_path).and_then(|old_path| {
+			let new_path = namespace.resolve_parent(&new_path)?;
+			namespace.rename(&old_path, &new_path)
+		});
+		let changes = namespace.finish();
+		try_errno!(self.update_dirs(changes, renamed)?);
+		Ok(ErrNo::Success)
 	}
 
 	pub fn path_symlink(
 		&mut self,
-		_from_path: P<u8>,
-		_from_path_len: u32,
-		_rel_fd: Fd,
-		_to_path: P<u8>,
-		_to_path_len: u32,
+		from_path: P<u8>,
+		from_path_len: u32,
+		rel_fd: Fd,
+		to_path: P<u8>,
+		to_path_len: u32,
 	) -> crate::Result<ErrNo> {
-		Ok(ErrNo::NoSys) // unimplemented(todo): vfs doesn't support links
+		if !self.schedule().wasi_dirs {
+			return Ok(ErrNo::NoSys);
+		}
+		let target = read_path!(self, from_path, from_path_len);
+		let target = match target.to_str() {
+			Some(target) => target.to_owned(),
+			None => return Ok(ErrNo::Inval),
+		};
+		let path = read_path!(self, to_path, to_path_len);
+		let (_, path) = try_errno!(self.home_path(rel_fd, path));
+		let mut namespace = dirs::Namespace::new(&*self.ext);
+		let linked = namespace
+			.resolve_parent(&path)
+			.and_then(|path| namespace.symlink(&target, &path));
+		let changes = namespace.finish();
+		try_errno!(self.update_dirs(changes, linked)?);
+		Ok(ErrNo::Success)
 	}
 
 	pub fn path_unlink_file(
@@ -449,15 +636,69 @@ impl<'a> crate::Runtime<'a> {
 		path_len: u32,
 	) -> crate::Result<ErrNo> {
 		let path = read_path!(self, path, path_len);
-		let prev_len = bcfs!(self.bcfs.unlink(dir_fd, path));
+		if path.is_absolute() || !self.schedule().wasi_dirs {
+			let prev_len = bcfs!(self.bcfs.unlink(dir_fd, path));
+			self.ext
+				.inc_sstore_clears(prev_len as u64)
+				.map_err(|_| crate::runtime::Error::StorageUpdateError)?;
+			return Ok(ErrNo::Success);
+		}
+
+		let (dir_fd, path) = try_errno!(self.home_path(dir_fd, path));
+		let mut namespace = dirs::Namespace::new(&*self.ext);
+		let unlinked =
+			namespace
+				.resolve_parent(&path)
+				.and_then(|path| match namespace.kind(&path) {
+					Some(EntryKind::Dir) => Err(ErrNo::IsDir),
+					Some(EntryKind::Link) => {
+						namespace.remove_link(&path);
+						Ok(None)
+					}
+					_ => {
+						namespace.remove_entry(&path);
+						Ok(Some(path))
+					}
+				});
+		let changes = namespace.finish();
+		let path = match try_errno!(self.update_dirs(changes, unlinked)?) {
+			Some(path) => path,
+			None => return Ok(ErrNo::Success),
+		};
+		let prev_len = bcfs!(self.bcfs.unlink(dir_fd, Path::new(&path)));
 		self.ext
 			.inc_sstore_clears(prev_len as u64)
 			.map_err(|_| crate::runtime::Error::StorageUpdateError)?;
 		Ok(ErrNo::Success)
 	}
 
-	pub fn poll_oneoff(&mut self, _in: P<u32>, _out: P<u32>, _n_subs: u32) -> crate::Result<ErrNo> {
-		Ok(ErrNo::Success) // unimplemented(dontneed): nothing to poll
+	/// `poll_oneoff` as provided before `schedule.wasi_clocks`, without the number
+	/// of events of WASI, and when there was nothing to poll.
+	pub fn poll_oneoff_legacy(
+		&mut self,
+		_in: P<u32>,
+		_out: P<u32>,
+		_n_subs: u32,
+	) -> crate::Result<ErrNo> {
+		Ok(ErrNo::Success)
+	}
+
+	pub fn poll_oneoff(
+		&mut self,
+		in_: P<u8>,
+		out: P<u8>,
+		nsubscriptions: Size,
+		nevents: P<Size>,
+	) -> crate::Result<ErrNo> {
+		let subscriptions = self
+			.memory
+			.get::<_, u8>(in_, nsubscriptions as usize * SUBSCRIPTION_SIZE)?
+			.to_vec();
+		let events = try_errno!(self.poll(&subscriptions)?);
+		self.memory.set(out, &events)?;
+		self.memory
+			.set_value(nevents, (events.len() / EVENT_SIZE) as Size)?;
+		Ok(ErrNo::Success)
 	}
 
 	pub fn proc_exit(&mut self, rval: u32) -> crate::Result<ErrNo> {
@@ -544,6 +785,275 @@ impl<'a> crate::Runtime<'a> {
 }
 
 impl<'a> crate::Runtime<'a> {
+	/// Passes bytes written to stdout or stderr on to the tracer, if there is one,
+	/// until `OUTPUT_CAPTURE_LIMIT` bytes have been captured by this call frame.
+	pub(crate) fn capture_output(&mut self, fd: u32, bufs: &[IoSlice]) {
+		if !self.ext.traces_wasm_output() {
+			return;
+		}
+		let mut captured = Vec::new();
+		for buf in bufs {
+			let len = buf
+				.len()
+				.min(OUTPUT_CAPTURE_LIMIT - self.output_captured - captured.len());
+			captured.extend_from_slice(&buf[..len]);
+		}
+		if captured.is_empty() {
+			return;
+		}
+		self.output_captured += captured.len();
+		self.ext.trace_wasm_output(fd, &captured);
+	}
+
+	/// Resolves `path` relative to `dir_fd` into the BCFS directory it is in and its
+	/// path below the home directory.
+	fn home_path(&self, dir_fd: Fd, path: &Path) -> dirs::Result<(Fd, String)> {
+		match self.dir_fds.get(dir_fd) {
+			Some((preopen, dir)) => Ok((*preopen, dirs::join(dir, path)?)),
+			None => Ok((dir_fd, dirs::join("", path)?)),
+		}
+	}
+
+	/// Returns the time of the clock `clock_id`, in nanoseconds.
+	///
+	/// Time only passes as gas is consumed, at `WasmCosts::clock_nanos_per_gas`,
+	/// and as the contract sleeps in `poll_oneoff`, so that it is the same on every
+	/// node. The realtime and monotonic clocks start at the block timestamp, and the
+	/// process and thread clocks at zero. Before `schedule.wasi_clocks`, only the
+	/// realtime and monotonic clocks exist, and they stand at the block timestamp.
+	pub(crate) fn clock_now(&self, clock_id: u32) -> Option<u64> {
+		if !self.schedule().wasi_clocks {
+			return match clock_id {
+				0 | 1 => Some(self.ext.env_info().timestamp.saturating_mul(1_000_000_000)),
+				_ => None,
+			};
+		}
+		let elapsed = self
+			.gas_counter
+			.saturating_mul(self.schedule().wasm().clock_nanos_per_gas as u64)
+			.saturating_add(self.slept);
+		match clock_id {
+			0 | 1 => Some(
+				self.ext
+					.env_info()
+					.timestamp
+					.saturating_mul(1_000_000_000)
+					.saturating_add(elapsed),
+			),
+			2 | 3 => Some(elapsed),
+			_ => None,
+		}
+	}
+
+	/// Waits for the first of the WASI `subscriptions` to occur, returning the
+	/// WASI `event`s which did.
+	///
+	/// Storage is always ready to be read and written, so file descriptor
+	/// subscriptions occur at once. Otherwise, the clocks are advanced up to the
+	/// earliest clock subscription. The time is virtual, so the wait costs nodes
+	/// nothing and is not charged beyond the call itself.
+	pub(crate) fn poll(
+		&mut self,
+		subscriptions: &[u8],
+	) -> crate::Result<std::result::Result<Vec<u8>, ErrNo>> {
+		if subscriptions.is_empty() {
+			return Ok(Err(ErrNo::Inval));
+		}
+
+		// (userdata, type, time until the subscription occurs or `None` for unknown clocks)
+		let mut pending = Vec::with_capacity(subscriptions.len() / SUBSCRIPTION_SIZE);
+		for subscription in subscriptions.chunks_exact(SUBSCRIPTION_SIZE) {
+			let userdata = le_u64(&subscription[0..8]);
+			let event_type = subscription[8];
+			let wait = match event_type {
+				EVENTTYPE_CLOCK => {
+					let clock_id =
+						u32::from_le_bytes(subscription[24..28].try_into().expect("4 bytes; qed"));
+					let timeout = le_u64(&subscription[32..40]);
+					let flags =
+						u16::from_le_bytes(subscription[48..50].try_into().expect("2 bytes; qed"));
+					self.clock_now(clock_id).map(|now| {
+						if flags & SUBSCRIPTION_CLOCK_ABSTIME != 0 {
+							timeout.saturating_sub(now)
+						} else {
+							timeout
+						}
+					})
+				}
+				EVENTTYPE_FD_READ | EVENTTYPE_FD_WRITE => Some(0),
+				_ => return Ok(Err(ErrNo::Inval)),
+			};
+			pending.push((userdata, event_type, wait));
+		}
+
+		let wait = pending
+			.iter()
+			.map(|(_, _, wait)| wait.unwrap_or(0))
+			.min()
+			.unwrap_or(0);
+		self.slept = self.slept.saturating_add(wait);
+
+		let mut events = Vec::new();
+		for (userdata, event_type, subscription_wait) in pending {
+			let error = match subscription_wait {
+				Some(subscription_wait) if subscription_wait <= wait => ErrNo::Success,
+				Some(_) => continue,
+				None => ErrNo::Inval,
+			};
+			let mut event = [0u8; EVENT_SIZE];
+			event[0..8].copy_from_slice(&userdata.to_le_bytes());
+			event[8..10].copy_from_slice(&(error as u16).to_le_bytes());
+			event[10] = event_type;
+			// `nbytes` and `flags` of fd events are left at zero
+			events.extend_from_slice(&event);
+		}
+		Ok(Ok(events))
+	}
+
+	/// Finishes a change to the namespace which had `result`, given the number of
+	/// storage lookups it made, the number of bytes it read and its writes. Each
+	/// lookup is charged as a storage read and the bytes read like `fd_read`, and
+	/// the writes of a successful change are charged like `fd_write` before they
+	/// are applied.
+	fn update_dirs<T>(
+		&mut self,
+		(lookups, bytes_read, writes): (u64, u64, dirs::Writes),
+		result: dirs::Result<T>,
+	) -> crate::Result<dirs::Result<T>> {
+		self.adjusted_charge(|schedule| (schedule.sload_gas as u64).checked_mul(lookups))?;
+		self.storage_read_charge(bytes_read)?;
+		if result.is_err() {
+			return Ok(result);
+		}
+		for (key, value) in writes.iter() {
+			if let Some(value) = value {
+				let reset = self.ext.as_kvstore().contains(key);
+				self.storage_bytes_charge(value.len() as u64, reset)?;
+			}
+		}
+		dirs::apply(&mut *self.ext, writes);
+		Ok(result)
+	}
+
+	fn dir_filestat(&self) -> FileStat {
+		let now = Timestamp::from_sec(self.ext.env_info().timestamp);
+		FileStat {
+			device: Default::default(),
+			inode: Default::default(),
+			file_type: FileType::Directory,
+			num_links: 1,
+			file_size: 0,
+			atime: now,
+			mtime: now,
+			ctime: now,
+		}
+	}
+
+	/// Returns the arguments of the Blockchain WASI ABI, which carry the call metadata:
+	///
+	/// 0. the hex address of the executing code, i.e. the program name,
+	/// 1. the method selector: the first four bytes of the input in hex, or empty if
+	///    the input is shorter,
+	/// 2. the call type: `call`, `staticcall`, `delegatecall`, `callcode` or `create`,
+	/// 3. the call depth, in decimal,
+	/// 4. the block number, in decimal.
+	///
+	/// Contracts only get arguments from `schedule.wasi_args` on; before, argv is empty.
+	/// The layout is documented for contract authors in `docs/blockchain-wasi-abi.md`.
+	pub(crate) fn argv(&self) -> Vec<String> {
+		if !self.schedule().wasi_args {
+			return Vec::new();
+		}
+		let selector = match self.args.get(..4) {
+			Some(selector) => selector.iter().map(|b| format!("{:02x}", b)).collect(),
+			None => String::new(),
+		};
+		let call_type = match self.context.call_type {
+			_ if self.ext.is_create() => "create",
+			CallType::StaticCall => "staticcall",
+			CallType::DelegateCall => "delegatecall",
+			CallType::CallCode => "callcode",
+			CallType::Call | CallType::None => "call",
+		};
+		vec![
+			format!("{:x}", self.context.code_address),
+			selector,
+			call_type.to_string(),
+			self.ext.depth().to_string(),
+			self.ext.env_info().number.to_string(),
+		]
+	}
+
+	/// Returns the `KEY=value` environment variables of the schedule's `WasiEnviron`.
+	/// Addresses are in hex and numbers in decimal, as documented in
+	/// `docs/blockchain-wasi-abi.md`. The expiry and confidentiality of the contract
+	/// are read from state, and each charged as a storage read, once per execution.
+	pub(crate) fn environ(&mut self) -> crate::Result<Vec<String>> {
+		if let Some(ref environ) = self.environ_strings {
+			return Ok(environ.clone());
+		}
+		let mut environ = vec![
+			format!("ADDRESS={:x}", self.context.address),
+			format!("SENDER={:x}", self.context.sender),
+			format!("AAD={}", self.context.aad_str),
+			format!("VALUE={}", self.context.value),
+		];
+		if self.schedule().wasi_environ >= WasiEnviron::V2 {
+			self.adjusted_charge(|schedule| Some(schedule.sload_gas as u64))?;
+			let expiry = self
+				.ext
+				.storage_expiry(&self.context.address)
+				.map_err(|_| crate::runtime::Error::StorageReadError)?;
+			self.adjusted_charge(|schedule| Some(schedule.sload_gas as u64))?;
+			let confidential = self
+				.ext
+				.is_confidential_contract(&self.context.address)
+				.map_err(|_| crate::runtime::Error::StorageReadError)?;
+			let env_info = self.ext.env_info();
+			environ.extend(vec![
+				format!("ORIGIN={:x}", self.context.origin),
+				format!("CODE_ADDRESS={:x}", self.context.code_address),
+				format!("BLOCK_NUMBER={}", env_info.number),
+				format!("TIMESTAMP={}", env_info.timestamp),
+				format!("GAS_PRICE={}", self.context.gas_price),
+				format!("EXPIRY={}", expiry),
+				format!("CONFIDENTIAL={}", confidential),
+			]);
+		}
+		self.environ_strings = Some(environ.clone());
+		Ok(environ)
+	}
+
+	/// Writes NUL-terminated `strings` to `buf` and pointers to each of them to `ptrs`.
+	fn write_strings(
+		&self,
+		strings: &[String],
+		ptrs: P<P<u8>>,
+		mut buf: P<u8>,
+	) -> crate::Result<()> {
+		let ptrs = self.memory.get_mut(ptrs, strings.len())?;
+		for (string, ptr) in strings.iter().zip(ptrs.iter_mut()) {
+			*ptr = buf;
+			buf = self.memory.set(buf, format!("{}\0", string).as_bytes())?;
+		}
+		Ok(())
+	}
+
+	/// Writes the number of `strings` and the buffer size needed by `write_strings`.
+	fn write_strings_sizes(
+		&self,
+		strings: &[String],
+		count: P<u32>,
+		buf_size: P<u32>,
+	) -> crate::Result<()> {
+		self.memory.set_value(count, strings.len() as u32)?;
+		self.memory.set_value(
+			buf_size,
+			strings.iter().map(|s| s.len() + "\0".len()).sum::<usize>() as u32,
+		)?;
+		Ok(())
+	}
+
 	fn do_read(
 		&mut self,
 		fd: Fd,
@@ -572,23 +1082,7 @@ impl<'a> crate::Runtime<'a> {
 			None => bcfs!(self.bcfs.read_vectored(fd, &mut ioslices)),
 		};
 
-		// Charge sload gas, scaled by number of bytes.
-		let sload_gas = U256::from(self.schedule().sload_gas);
-		let bytes_len = U256::from(nbytes);
-
-		// gas <- ceiling(sload_gas * bytes_len / 32)
-		// Cannot overflow as gas and len are converted from u64s.
-		let mut gas = sload_gas * bytes_len / U256::from(32);
-		if sload_gas * bytes_len % U256::from(32) != U256::from(0) {
-			gas = gas + U256::from(1);
-		}
-
-		// Charge gas after checking for u64 overflow.
-		if gas > U256::from(std::u64::MAX) {
-			return Err(crate::runtime::Error::GasLimit);
-		} else {
-			self.adjusted_charge(|_| Some(gas.as_u64()))?;
-		}
+		self.storage_read_charge(nbytes as u64)?;
 
 		self.memory.set_value(
 			nread,
@@ -635,6 +1129,10 @@ impl<'a> crate::Runtime<'a> {
 			}
 		}
 
+		if u32::from(fd) == 1 || u32::from(fd) == 2 {
+			self.capture_output(u32::from(fd), &ioslices);
+		}
+
 		let prev_size = bcfs!(self.bcfs.filestat(fd)).file_size;
 
 		let nbytes = match offset {
@@ -659,3 +1157,7 @@ impl<'a> crate::Runtime<'a> {
 		Ok(ErrNo::Success)
 	}
 }
+
+fn le_u64(bytes: &[u8]) -> u64 {
+	u64::from_le_bytes(bytes.try_into().expect("called with 8 bytes; qed"))
+}
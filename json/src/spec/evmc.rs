@@ -0,0 +1,51 @@
+// Copyright 2015-2018 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! EVMC runtime params deserialization.
+
+/// EVMC runtime params.
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct EvmcParams {
+	/// Path of the EVMC shared library, if pinned by the spec.
+	///
+	/// The revision contracts are executed with follows the hard fork transitions.
+	pub library: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json;
+	use spec::evmc::EvmcParams;
+
+	#[test]
+	fn evmc_params_deserialization() {
+		let s = r#"{
+			"library": "/ssvm/libssvm-evmc.so"
+		}"#;
+
+		let deserialized: EvmcParams = serde_json::from_str(s).unwrap();
+		assert_eq!(
+			deserialized.library,
+			Some("/ssvm/libssvm-evmc.so".to_owned())
+		);
+	}
+
+	#[test]
+	fn evmc_params_are_optional() {
+		let deserialized: EvmcParams = serde_json::from_str("{}").unwrap();
+		assert_eq!(deserialized, EvmcParams { library: None });
+	}
+}
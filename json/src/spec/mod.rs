@@ -22,6 +22,7 @@ pub mod basic_authority;
 pub mod builtin;
 pub mod engine;
 pub mod ethash;
+pub mod evmc;
 pub mod genesis;
 pub mod hardcoded_sync;
 pub mod null_engine;
@@ -31,6 +32,7 @@ pub mod spec;
 pub mod state;
 pub mod tendermint;
 pub mod validator_set;
+pub mod wasm_policy;
 
 pub use self::account::Account;
 pub use self::authority_round::{AuthorityRound, AuthorityRoundParams};
@@ -38,12 +40,14 @@ pub use self::basic_authority::{BasicAuthority, BasicAuthorityParams};
 pub use self::builtin::{Builtin, Linear, Pricing};
 pub use self::engine::Engine;
 pub use self::ethash::{Ethash, EthashParams};
+pub use self::evmc::EvmcParams;
 pub use self::genesis::Genesis;
 pub use self::hardcoded_sync::HardcodedSync;
 pub use self::null_engine::{NullEngine, NullEngineParams};
-pub use self::params::Params;
+pub use self::params::{Params, WasmBackend};
 pub use self::seal::{AuthorityRoundSeal, Ethereum, Seal, TendermintSeal};
 pub use self::spec::Spec;
 pub use self::state::State;
 pub use self::tendermint::{Tendermint, TendermintParams};
 pub use self::validator_set::ValidatorSet;
+pub use self::wasm_policy::WasmPolicy;
@@ -18,8 +18,23 @@
 
 use bytes::Bytes;
 use hash::{Address, H256};
+use spec::{EvmcParams, WasmPolicy};
 use uint::{self, Uint};
 
+/// Runtimes wasm contracts can be executed with.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum WasmBackend {
+	/// The in-tree wasmi interpreter.
+	#[serde(rename = "wasmi")]
+	Wasmi,
+	/// An EVMC shared library, see `EvmcParams`.
+	#[serde(rename = "evmc")]
+	Evmc,
+	/// Both, checking the EVMC library against the wasmi interpreter.
+	#[serde(rename = "differential")]
+	Differential,
+}
+
 /// Spec params.
 #[derive(Debug, PartialEq, Deserialize)]
 pub struct Params {
@@ -100,6 +115,12 @@ pub struct Params {
 	#[serde(rename = "eip145Transition")]
 	pub eip145_transition: Option<Uint>,
 	/// See `CommonParams` docs.
+	#[serde(rename = "eip1283Transition")]
+	pub eip1283_transition: Option<Uint>,
+	/// See `CommonParams` docs.
+	#[serde(rename = "eip1283DisableTransition")]
+	pub eip1283_disable_transition: Option<Uint>,
+	/// See `CommonParams` docs.
 	#[serde(rename = "eip214Transition")]
 	pub eip214_transition: Option<Uint>,
 	/// See `CommonParams` docs.
@@ -140,6 +161,38 @@ pub struct Params {
 	/// Wasm activation block height, if not activated from start
 	#[serde(rename = "wasmActivationTransition")]
 	pub wasm_activation_transition: Option<Uint>,
+	/// Runtime used to execute wasm contracts.
+	#[serde(rename = "wasmBackend")]
+	pub wasm_backend: Option<WasmBackend>,
+	/// Restrictions on deployed wasm modules.
+	#[serde(rename = "wasmPolicy")]
+	pub wasm_policy: Option<WasmPolicy>,
+	/// Block at which the wasm policy starts being enforced.
+	#[serde(rename = "wasmPolicyTransition")]
+	pub wasm_policy_transition: Option<Uint>,
+	/// Block at which wasm contracts run by wasmi start being validated on deploy.
+	#[serde(rename = "wasmValidationTransition")]
+	pub wasm_validation_transition: Option<Uint>,
+	/// Block at which WASI contracts get the second version of environment variables.
+	#[serde(rename = "wasiEnvironV2Transition")]
+	pub wasi_environ_v2_transition: Option<Uint>,
+	/// Block at which WASI contracts get directories and symbolic links.
+	#[serde(rename = "wasiDirsTransition")]
+	pub wasi_dirs_transition: Option<Uint>,
+	/// Block at which WASI clocks advance with gas and can be waited on.
+	#[serde(rename = "wasiClocksTransition")]
+	pub wasi_clocks_transition: Option<Uint>,
+	/// Block at which WASI contracts start receiving the call metadata as arguments.
+	#[serde(rename = "wasiArgsTransition")]
+	pub wasi_args_transition: Option<Uint>,
+	/// Block at which logs emitted in a confidential context start being encrypted.
+	#[serde(rename = "confidentialLogsTransition")]
+	pub confidential_logs_transition: Option<Uint>,
+	/// EVMC runtime used to execute wasm contracts.
+	pub evmc: Option<EvmcParams>,
+	/// Secret of the software confidential context of development chains.
+	#[serde(rename = "devConfidentialSecret")]
+	pub dev_confidential_secret: Option<H256>,
 
 	/// Whether to run in benchmarking/debug mode.
 	pub benchmarking: Option<bool>,
@@ -147,9 +200,10 @@ pub struct Params {
 
 #[cfg(test)]
 mod tests {
-	use ethereum_types::U256;
+	use ethereum_types::{H256 as Eth256, U256};
+	use hash::H256;
 	use serde_json;
-	use spec::params::Params;
+	use spec::params::{Params, WasmBackend};
 	use uint::Uint;
 
 	#[test]
@@ -163,7 +217,18 @@ mod tests {
 			"accountStartNonce": "0x01",
 			"gasLimitBoundDivisor": "0x20",
 			"maxCodeSize": "0x1000",
-			"wasmActivationTransition": "0x1010"
+			"wasmActivationTransition": "0x1010",
+			"wasmBackend": "wasmi",
+			"wasmPolicy": { "forbidFloats": true },
+			"wasmPolicyTransition": "0x1818",
+			"wasmValidationTransition": "0x1919",
+			"wasiEnvironV2Transition": "0x2020",
+			"wasiDirsTransition": "0x3030",
+			"wasiClocksTransition": "0x4040",
+			"wasiArgsTransition": "0x6060",
+			"confidentialLogsTransition": "0x5050",
+			"evmc": { "library": "/ssvm/libssvm-evmc.so" },
+			"devConfidentialSecret": "0x0707070707070707070707070707070707070707070707070707070707070707"
 		}"#;
 
 		let deserialized: Params = serde_json::from_str(s).unwrap();
@@ -182,6 +247,49 @@ mod tests {
 			deserialized.wasm_activation_transition,
 			Some(Uint(U256::from(0x1010)))
 		);
+		assert_eq!(deserialized.wasm_backend, Some(WasmBackend::Wasmi));
+		assert_eq!(
+			deserialized
+				.wasm_policy
+				.and_then(|policy| policy.forbid_floats),
+			Some(true)
+		);
+		assert_eq!(
+			deserialized.wasm_policy_transition,
+			Some(Uint(U256::from(0x1818)))
+		);
+		assert_eq!(
+			deserialized.wasm_validation_transition,
+			Some(Uint(U256::from(0x1919)))
+		);
+		assert_eq!(
+			deserialized.wasi_environ_v2_transition,
+			Some(Uint(U256::from(0x2020)))
+		);
+		assert_eq!(
+			deserialized.wasi_dirs_transition,
+			Some(Uint(U256::from(0x3030)))
+		);
+		assert_eq!(
+			deserialized.wasi_clocks_transition,
+			Some(Uint(U256::from(0x4040)))
+		);
+		assert_eq!(
+			deserialized.wasi_args_transition,
+			Some(Uint(U256::from(0x6060)))
+		);
+		assert_eq!(
+			deserialized.confidential_logs_transition,
+			Some(Uint(U256::from(0x5050)))
+		);
+		assert_eq!(
+			deserialized.evmc.and_then(|evmc| evmc.library),
+			Some("/ssvm/libssvm-evmc.so".to_owned())
+		);
+		assert_eq!(
+			deserialized.dev_confidential_secret,
+			Some(H256(Eth256::from([7; 32])))
+		);
 	}
 
 	#[test]
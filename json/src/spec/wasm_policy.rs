@@ -0,0 +1,88 @@
+// Copyright 2015-2018 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Wasm module policy deserialization.
+
+use uint::{self, Uint};
+
+/// Restrictions on the wasm modules that can be deployed.
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct WasmPolicy {
+	/// Whether to reject modules using floating point instructions.
+	#[serde(rename = "forbidFloats")]
+	pub forbid_floats: Option<bool>,
+	/// Maximum initial memory of a module, in pages.
+	#[serde(rename = "maxInitialPages")]
+	#[serde(default, deserialize_with = "uint::validate_optional_u32")]
+	pub max_initial_pages: Option<Uint>,
+	/// Maximum memory of a module, in pages.
+	#[serde(rename = "maxPages")]
+	#[serde(default, deserialize_with = "uint::validate_optional_u32")]
+	pub max_pages: Option<Uint>,
+	/// Functions modules can import, as `module.field`.
+	#[serde(rename = "allowedImports")]
+	pub allowed_imports: Option<Vec<String>>,
+}
+
+#[cfg(test)]
+mod tests {
+	use ethereum_types::U256;
+	use serde_json;
+	use spec::wasm_policy::WasmPolicy;
+	use uint::Uint;
+
+	#[test]
+	fn wasm_policy_deserialization() {
+		let s = r#"{
+			"forbidFloats": true,
+			"maxInitialPages": "0x10",
+			"maxPages": "0x100",
+			"allowedImports": ["wasi_unstable.fd_write", "wasi_unstable.proc_exit"]
+		}"#;
+
+		let deserialized: WasmPolicy = serde_json::from_str(s).unwrap();
+		assert_eq!(deserialized.forbid_floats, Some(true));
+		assert_eq!(deserialized.max_initial_pages, Some(Uint(U256::from(0x10))));
+		assert_eq!(deserialized.max_pages, Some(Uint(U256::from(0x100))));
+		assert_eq!(
+			deserialized.allowed_imports,
+			Some(vec![
+				"wasi_unstable.fd_write".to_owned(),
+				"wasi_unstable.proc_exit".to_owned()
+			])
+		);
+	}
+
+	#[test]
+	#[should_panic(expected = "a value fitting in 32 bits")]
+	fn wasm_policy_pages_fit_in_32_bits() {
+		let _: WasmPolicy = serde_json::from_str(r#"{ "maxPages": "0x100000000" }"#).unwrap();
+	}
+
+	#[test]
+	fn wasm_policy_is_optional() {
+		let deserialized: WasmPolicy = serde_json::from_str("{}").unwrap();
+		assert_eq!(
+			deserialized,
+			WasmPolicy {
+				forbid_floats: None,
+				max_initial_pages: None,
+				max_pages: None,
+				allowed_imports: None,
+			}
+		);
+	}
+}
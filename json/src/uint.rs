@@ -44,6 +44,17 @@ impl Into<usize> for Uint {
 		u64::from(self.0) as usize
 	}
 }
+impl Into<u32> for Uint {
+	fn into(self) -> u32 {
+		let value = u64::from(self.0);
+		assert!(
+			value <= u64::from(u32::max_value()),
+			"Integer overflow when casting to u32"
+		);
+		value as u32
+	}
+}
+
 impl Into<u8> for Uint {
 	fn into(self) -> u8 {
 		u64::from(self.0) as u8
@@ -144,6 +155,24 @@ where
 	Ok(value)
 }
 
+pub fn validate_optional_u32<'de, D>(d: D) -> Result<Option<Uint>, D::Error>
+where
+	D: Deserializer<'de>,
+{
+	let value: Option<Uint> = Option::deserialize(d)?;
+
+	if let Some(value) = value {
+		if value.0 > U256::from(u32::max_value()) {
+			return Err(Error::invalid_value(
+				Unexpected::Str(&value.0.to_string()),
+				&"a value fitting in 32 bits",
+			));
+		}
+	}
+
+	Ok(value)
+}
+
 #[cfg(test)]
 mod test {
 	use ethereum_types::U256;
@@ -170,4 +199,10 @@ mod test {
 	fn uint_into() {
 		assert_eq!(U256::from(10), Uint(U256::from(10)).into());
 	}
+
+	#[test]
+	#[should_panic(expected = "Integer overflow when casting to u32")]
+	fn uint_into_u32_overflow() {
+		let _: u32 = Uint(U256::from(u64::from(u32::max_value()) + 1)).into();
+	}
 }
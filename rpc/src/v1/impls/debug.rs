@@ -0,0 +1,101 @@
+// Copyright 2015-2018 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Debug rpc implementation.
+
+use std::sync::Arc;
+
+use ethcore::error::Error as EthcoreError;
+use ethcore::machine::EthereumMachine;
+use ethcore::state::{Backend, State};
+use ethcore::trace::{ExecutiveVMTracer, NoopTracer};
+use ethcore::trace_ext::NoopExtTracer;
+use ethereum_types::H256;
+use jsonrpc_core::futures::future;
+use jsonrpc_core::BoxFuture;
+use transaction::SignedTransaction;
+use vm::EnvInfo;
+
+use v1::helpers::errors;
+use v1::traits::Debug;
+use v1::types::{WasmOutput, H256 as RpcH256};
+
+/// A transaction of the chain, with what it is to be replayed in.
+pub struct Replay<B: Backend> {
+	/// The transaction.
+	pub transaction: SignedTransaction,
+	/// Environment of the block the transaction was included in.
+	pub env_info: EnvInfo,
+	/// State the transaction was applied to, i.e. after the transactions
+	/// preceding it in its block.
+	pub state: State<B>,
+}
+
+/// Looks up the transactions the debug rpc replays.
+pub trait ReplaySource: Send + Sync + 'static {
+	/// Backend of the states transactions are replayed in.
+	type Backend: Backend;
+
+	/// Machine the transactions of the chain are executed by.
+	fn machine(&self) -> &EthereumMachine;
+
+	/// The transaction with the given hash and what to replay it in,
+	/// or `None` if the transaction is unknown.
+	fn replay(&self, hash: &H256) -> Result<Option<Replay<Self::Backend>>, EthcoreError>;
+}
+
+/// Debug rpc implementation.
+pub struct DebugClient<S> {
+	source: Arc<S>,
+}
+
+impl<S> DebugClient<S> {
+	/// Creates new DebugClient.
+	pub fn new(source: Arc<S>) -> Self {
+		DebugClient { source }
+	}
+}
+
+impl<S: ReplaySource> Debug for DebugClient<S> {
+	fn wasm_output(&self, hash: RpcH256) -> BoxFuture<Option<Vec<WasmOutput>>> {
+		let Replay {
+			transaction,
+			env_info,
+			mut state,
+		} = match try_bf!(self.source.replay(&hash.into()).map_err(errors::database)) {
+			Some(replay) => replay,
+			None => return Box::new(future::ok(None)),
+		};
+
+		let outcome = try_bf!(state
+			.apply_with_tracing(
+				&env_info,
+				self.source.machine(),
+				&transaction,
+				NoopTracer,
+				ExecutiveVMTracer::toplevel(),
+				NoopExtTracer,
+				false,
+			)
+			.map_err(errors::execution));
+
+		Box::new(future::ok(Some(
+			outcome
+				.vm_trace
+				.map_or_else(Vec::new, WasmOutput::from_trace),
+		)))
+	}
+}
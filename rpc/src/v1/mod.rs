@@ -36,11 +36,13 @@ mod tests;
 pub mod types;
 
 pub mod extractors;
+pub mod impls;
 pub mod informant;
 pub mod metadata;
 pub mod traits;
 
 pub use self::extractors::{RpcExtractor, WsDispatcher, WsExtractor, WsStats};
+pub use self::impls::*;
 pub use self::metadata::Metadata;
-pub use self::traits::{Eth, EthFilter, EthPubSub, EthSigning, Net, PubSub, Rpc, Web3};
+pub use self::traits::{Debug, Eth, EthFilter, EthPubSub, EthSigning, Net, PubSub, Rpc, Web3};
 pub use self::types::Origin;
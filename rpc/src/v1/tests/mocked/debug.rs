@@ -0,0 +1,123 @@
+// Copyright 2015-2018 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+
+use ethcore::error::Error as EthcoreError;
+use ethcore::ethereum;
+use ethcore::machine::EthereumMachine;
+use ethcore::state_db::StateDB;
+use ethcore::test_helpers::get_temp_state;
+use ethereum_types::{Address, H256, U256};
+use rustc_hex::ToHex;
+use serde_json::{self, Value};
+use transaction::{Action, SignedTransaction, Transaction};
+use vm::EnvInfo;
+
+use jsonrpc_core::IoHandler;
+use v1::{Debug, DebugClient, Replay, ReplaySource};
+
+const IO_CONTRACT: &[u8] =
+	include_bytes!("../../../../../ethcore/res/wasi-tests/target/service/io.wasm");
+
+/// Replays a single call to the `io` contract, which writes its input to stdout.
+struct TestReplaySource {
+	machine: EthereumMachine,
+	transaction: SignedTransaction,
+}
+
+impl ReplaySource for TestReplaySource {
+	type Backend = StateDB;
+
+	fn machine(&self) -> &EthereumMachine {
+		&self.machine
+	}
+
+	fn replay(&self, hash: &H256) -> Result<Option<Replay<StateDB>>, EthcoreError> {
+		if *hash != self.transaction.hash() {
+			return Ok(None);
+		}
+
+		let mut state = get_temp_state();
+		state.init_code(&contract(), IO_CONTRACT.to_vec()).unwrap();
+
+		let mut env_info = EnvInfo::default();
+		env_info.number = 100; // wasm activated at block 10
+		env_info.gas_limit = U256::from(10_000_000);
+		env_info.last_hashes = Arc::new(vec![H256::zero()]);
+
+		Ok(Some(Replay {
+			transaction: self.transaction.clone(),
+			env_info,
+			state,
+		}))
+	}
+}
+
+fn contract() -> Address {
+	Address::from(0x10)
+}
+
+fn io() -> (IoHandler, H256) {
+	let transaction = Transaction {
+		nonce: U256::zero(),
+		gas_price: U256::zero(),
+		gas: U256::from(1_000_000),
+		action: Action::Call(contract()),
+		value: U256::zero(),
+		data: b"hello, world!".to_vec(),
+	}
+	.fake_sign(Address::from(0x20));
+	let hash = transaction.hash();
+
+	let source = Arc::new(TestReplaySource {
+		machine: ethereum::new_kovan_wasm_test_machine(),
+		transaction,
+	});
+	let mut io = IoHandler::new();
+	io.extend_with(DebugClient::new(source).to_delegate());
+	(io, hash)
+}
+
+#[test]
+fn rpc_debug_wasm_output() {
+	let (io, hash) = io();
+
+	let request = format!(
+		r#"{{"jsonrpc": "2.0", "method": "debug_wasmOutput", "params": ["0x{:x}"], "id": 1}}"#,
+		hash
+	);
+	let response: Value = serde_json::from_str(&io.handle_request_sync(&request).unwrap()).unwrap();
+
+	let stdout: String = response["result"]
+		.as_array()
+		.unwrap()
+		.iter()
+		.filter(|output| output["fd"] == 1)
+		.map(|output| output["data"].as_str().unwrap()[2..].to_owned())
+		.collect();
+	assert!(stdout.starts_with(&b"the input was: hello, world!\n"[..].to_hex()));
+}
+
+#[test]
+fn rpc_debug_wasm_output_unknown_transaction() {
+	let (io, _) = io();
+
+	let request = r#"{"jsonrpc": "2.0", "method": "debug_wasmOutput", "params": ["0x0000000000000000000000000000000000000000000000000000000000000001"], "id": 1}"#;
+	let response = r#"{"jsonrpc":"2.0","result":null,"id":1}"#;
+
+	assert_eq!(io.handle_request_sync(request), Some(response.to_owned()));
+}
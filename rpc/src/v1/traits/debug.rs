@@ -0,0 +1,31 @@
+// Copyright 2015-2018 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Debug rpc interface.
+use jsonrpc_core::BoxFuture;
+
+use v1::types::{WasmOutput, H256};
+
+build_rpc_trait! {
+	/// Debug rpc interface.
+	pub trait Debug {
+		/// Replays the transaction with the given hash, returning what the wasm
+		/// contracts it called wrote to their standard output and error, or `None`
+		/// if the transaction is unknown.
+		#[rpc(name = "debug_wasmOutput")]
+		fn wasm_output(&self, H256) -> BoxFuture<Option<Vec<WasmOutput>>>;
+	}
+}
@@ -16,6 +16,7 @@
 
 //! Ethereum rpc interfaces.
 
+pub mod debug;
 pub mod eth;
 pub mod eth_pubsub;
 pub mod eth_signing;
@@ -24,6 +25,7 @@ pub mod pubsub;
 pub mod rpc;
 pub mod web3;
 
+pub use self::debug::Debug;
 pub use self::eth::{Eth, EthFilter};
 pub use self::eth_pubsub::EthPubSub;
 pub use self::eth_signing::EthSigning;
@@ -39,6 +39,7 @@ mod transaction_condition;
 mod transaction_outcome;
 mod transaction_request;
 mod uint;
+mod wasm_output;
 mod work;
 
 pub mod pubsub;
@@ -71,6 +72,7 @@ pub use self::transaction_condition::TransactionCondition;
 pub use self::transaction_outcome::TransactionOutcome;
 pub use self::transaction_request::TransactionRequest;
 pub use self::uint::{U128, U256, U64};
+pub use self::wasm_output::WasmOutput;
 pub use self::work::Work;
 
 // TODO [ToDr] Refactor to a proper type Vec of enums?
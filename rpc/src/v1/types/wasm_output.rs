@@ -0,0 +1,90 @@
+// Copyright 2015-2018 Parity Technologies (UK) Ltd.
+// This file is part of Parity.
+
+// Parity is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Parity is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Parity.  If not, see <http://www.gnu.org/licenses/>.
+
+use ethcore::trace::{VMTrace, WasmOutput as EthWasmOutput};
+use v1::types::Bytes;
+
+/// Bytes a wasm contract wrote to its standard output or error.
+#[derive(Debug, Serialize, PartialEq, Eq, Clone)]
+pub struct WasmOutput {
+	/// File descriptor written to, 1 for stdout and 2 for stderr.
+	pub fd: u32,
+	/// Bytes written.
+	pub data: Bytes,
+}
+
+impl WasmOutput {
+	/// Collects the output captured in `trace` and its subtraces, in the order
+	/// the calls were made.
+	pub fn from_trace(trace: VMTrace) -> Vec<WasmOutput> {
+		let mut output: Vec<WasmOutput> = trace.wasm_output.into_iter().map(Into::into).collect();
+		for sub in trace.subs {
+			output.extend(WasmOutput::from_trace(sub));
+		}
+		output
+	}
+}
+
+impl From<EthWasmOutput> for WasmOutput {
+	fn from(o: EthWasmOutput) -> WasmOutput {
+		WasmOutput {
+			fd: o.fd,
+			data: o.data.into(),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use ethcore::trace::{VMTrace, WasmOutput as EthWasmOutput};
+	use serde_json;
+	use v1::types::WasmOutput;
+
+	#[test]
+	fn wasm_output_serialization() {
+		let s = r#"{"fd":2,"data":"0x6f6f7073"}"#;
+
+		let output = WasmOutput {
+			fd: 2,
+			data: b"oops".to_vec().into(),
+		};
+
+		let serialized = serde_json::to_string(&output).unwrap();
+		assert_eq!(serialized, s);
+	}
+
+	#[test]
+	fn wasm_output_from_trace() {
+		let output = |fd, data: &[u8]| EthWasmOutput {
+			fd,
+			data: data.to_vec(),
+		};
+		let trace = VMTrace {
+			wasm_output: vec![output(1, b"outer")],
+			subs: vec![VMTrace {
+				wasm_output: vec![output(2, b"inner")],
+				..Default::default()
+			}],
+			..Default::default()
+		};
+
+		let fds: Vec<_> = WasmOutput::from_trace(trace)
+			.into_iter()
+			.map(|o| o.fd)
+			.collect();
+		assert_eq!(fds, vec![1, 2]);
+	}
+}